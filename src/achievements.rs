@@ -0,0 +1,165 @@
+//! Achievements unlocked by the players
+//!
+//! The rules are checked after each guess and when a session ends. Each achievement can only be unlocked once per
+//! player; unlocking one is announced on IRC, and the achievements of a player are shown on their profile page.
+use crate::game::GameMode;
+use anyhow::{anyhow, Result};
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Win with fewer guesses than this for [`Achievement::QuickWin`].
+const QUICK_WIN_GUESSES: usize = 5;
+
+/// Win after at least this many guesses for [`Achievement::Persistent`].
+const PERSISTENT_GUESSES: usize = 100;
+
+/// Cosine similarity above which a guess unlocks [`Achievement::CloseCall`].
+const CLOSE_CALL_SIMILARITY: f32 = 0.9;
+
+/// Number of consecutive days to play for [`Achievement::WeekStreak`].
+const STREAK_DAYS: usize = 7;
+
+/// An achievement.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Achievement {
+    /// Won a session for the first time.
+    FirstWin,
+    /// Won a session in fewer than `QUICK_WIN_GUESSES` guesses.
+    QuickWin,
+    /// Won a session after `PERSISTENT_GUESSES` guesses or more.
+    Persistent,
+    /// Made a guess with a cosine similarity above `CLOSE_CALL_SIMILARITY` that wasn't the word.
+    CloseCall,
+    /// Took part in sessions on `STREAK_DAYS` consecutive days.
+    WeekStreak,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 5] = [
+        Achievement::FirstWin,
+        Achievement::QuickWin,
+        Achievement::Persistent,
+        Achievement::CloseCall,
+        Achievement::WeekStreak,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Achievement::FirstWin => "first_win",
+            Achievement::QuickWin => "quick_win",
+            Achievement::Persistent => "persistent",
+            Achievement::CloseCall => "close_call",
+            Achievement::WeekStreak => "week_streak",
+        }
+    }
+
+    /// Name of the achievement, as shown to the players.
+    pub fn name(self) -> &'static str {
+        match self {
+            Achievement::FirstWin => "First win",
+            Achievement::QuickWin => "Quick win",
+            Achievement::Persistent => "Persistent",
+            Achievement::CloseCall => "Close call",
+            Achievement::WeekStreak => "Regular",
+        }
+    }
+
+    /// What the achievement is given for.
+    pub fn description(self) -> String {
+        match self {
+            Achievement::FirstWin => "won a game for the first time".to_string(),
+            Achievement::QuickWin => format!("won a game in fewer than {} guesses", QUICK_WIN_GUESSES),
+            Achievement::Persistent => format!("won a game after {} guesses or more", PERSISTENT_GUESSES),
+            Achievement::CloseCall => format!("made a guess closer than {} that wasn't the word", CLOSE_CALL_SIMILARITY),
+            Achievement::WeekStreak => format!("played on {} days in a row", STREAK_DAYS),
+        }
+    }
+
+    /// Whether `event` unlocks the achievement.
+    fn unlocked_by(self, event: &Event) -> bool {
+        match (self, event) {
+            (Achievement::FirstWin, Event::SessionEnded { won, .. }) => *won,
+            (Achievement::QuickWin, Event::SessionEnded { won, guesses, .. }) => *won && *guesses < QUICK_WIN_GUESSES,
+            (Achievement::Persistent, Event::SessionEnded { won, guesses, .. }) => {
+                *won && *guesses >= PERSISTENT_GUESSES
+            }
+            (Achievement::CloseCall, Event::Guess { cosine, mode }) => {
+                *mode != GameMode::Wordle && cosine.is_some_and(|cosine| cosine > CLOSE_CALL_SIMILARITY)
+            }
+            (Achievement::WeekStreak, Event::SessionEnded { days_in_a_row, .. }) => *days_in_a_row >= STREAK_DAYS,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Achievement {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Achievement> {
+        Achievement::ALL
+            .into_iter()
+            .find(|achievement| achievement.as_str() == s)
+            .ok_or_else(|| anyhow!("unknown achievement `{}`", s))
+    }
+}
+
+/// An achievement unlocked by a player.
+#[derive(Clone, Debug, Serialize)]
+pub struct UnlockedAchievement {
+    pub achievement: Achievement,
+    /// When the achievement was unlocked (UNIX timestamp in seconds).
+    pub date: i64,
+}
+
+impl UnlockedAchievement {
+    /// Date the achievement was unlocked, as shown to the players.
+    pub fn day(&self) -> String {
+        Utc.timestamp_opt(self.date, 0).unwrap().format("%Y-%m-%d").to_string()
+    }
+}
+
+/// Something a player did that may unlock achievements.
+pub enum Event {
+    /// The player made a guess that wasn't the word.
+    Guess {
+        /// Cosine similarity of the guess to the word, `None` if the session uses another metric.
+        cosine: Option<f32>,
+        mode: GameMode,
+    },
+    /// A session the player took part in ended.
+    SessionEnded {
+        /// Whether the player won the session.
+        won: bool,
+        /// Number of guesses of the player in the session.
+        guesses: usize,
+        /// Number of consecutive days, up to the session, on which the player took part in a session.
+        days_in_a_row: usize,
+    },
+}
+
+/// Returns the achievements unlocked by `event`, including the ones the player already has.
+pub fn unlocked(event: &Event) -> Vec<Achievement> {
+    Achievement::ALL
+        .into_iter()
+        .filter(|achievement| achievement.unlocked_by(event))
+        .collect()
+}
+
+/// Returns the number of consecutive days (UTC) ending with the last of `dates` on which there's one of `dates`.
+///
+/// `dates` are UNIX timestamps in seconds, in any order.
+pub fn days_in_a_row(dates: &[i64]) -> usize {
+    let mut days: Vec<i64> = dates.iter().map(|date| date.div_euclid(86400)).collect();
+    days.sort_unstable();
+    days.dedup();
+    let mut count = 0;
+    for (i, day) in days.iter().rev().enumerate() {
+        if *day != days[days.len() - 1] - i as i64 {
+            break;
+        }
+        count += 1;
+    }
+    count
+}
@@ -0,0 +1,95 @@
+//! Interface of the word models used by the game and the IRC commands
+//!
+//! `Words`, the in-memory (or memory-mapped) vector model, is the main implementation.
+use crate::words::{Difficulty, DifficultyLevel, GuessPolicy, Metric, NeighborTable, RankTable};
+use anyhow::Result;
+use rand::RngCore;
+use std::borrow::Cow;
+
+/// A vocabulary of words with their vectors.
+///
+/// Methods that take a word expect a word of the vocabulary, and return `None` if it's not, except the
+/// ones dealing with guesses.
+pub trait EmbeddingBackend: Send + Sync {
+    /// Default similarity metric.
+    fn metric(&self) -> Metric;
+
+    /// Number of words in the vocabulary.
+    fn len(&self) -> usize;
+
+    /// Whether lookups of words or guesses may block on network requests.
+    fn may_block(&self) -> bool;
+
+    /// Returns the `i`-th word of the vocabulary.
+    fn word(&self, i: usize) -> &str;
+
+    /// Returns the vector of a word of the vocabulary.
+    fn vector(&self, word: &str) -> Option<Cow<'_, [f32]>>;
+
+    /// Returns the vector of a guessed word or phrase, which may be outside of the vocabulary. May block.
+    fn guess_vector(&self, guess: &str) -> Result<Option<Cow<'_, [f32]>>>;
+
+    /// How guesses are cleaned up for this model.
+    fn guess_policy(&self) -> &GuessPolicy;
+
+    /// Cleans up a guess or a word typed by a player (case, punctuation, spaces) before looking it up.
+    fn normalize_guess(&self, guess: &str) -> String {
+        self.guess_policy().normalize(guess)
+    }
+
+    /// Returns the word of the vocabulary a guess refers to, or the guess itself if there's none.
+    fn resolve_guess<'a>(&'a self, guess: &'a str) -> &'a str;
+
+    /// Returns whether a guess must be rejected because it's a stop word.
+    fn is_rejected_stop_word(&self, guess: &str) -> bool;
+
+    /// Returns up to `count` words of the vocabulary spelled like `word`, the closest first.
+    fn spell_suggest(&self, word: &str, count: usize) -> Vec<String>;
+
+    /// Returns the `count` nearest neighbors of a word (excluding the word itself) with their similarity,
+    /// closest first.
+    fn neighbors(&self, word: &str, count: usize, metric: Metric) -> Option<Vec<(String, f32)>>;
+
+    /// Returns the `count` nearest neighbors of a word and the distribution of the similarities of the
+    /// vocabulary to it.
+    fn neighbor_table(&self, word: &str, count: usize, metric: Metric) -> Option<NeighborTable>;
+
+    /// `!thesaurus <word> <count>`: the nearest neighbors of a word with the default metric.
+    fn thesaurus(&self, word: &str, count: usize) -> Option<Vec<(String, f32)>>;
+
+    /// `!analogy`: the words nearest to the sum of the `positive` words minus the `negative` words.
+    fn analogy(&self, positive: &[&str], negative: &[&str], count: usize) -> Result<Vec<(String, f32)>>;
+
+    /// Returns the similarity between two words or phrases with the default metric. May block.
+    fn similarity(&self, a: &str, b: &str) -> Result<f32>;
+
+    /// Returns the similarities of the words of the vocabulary to `word`, to rank guesses.
+    fn rank_table(&self, word: &str, metric: Metric) -> Option<RankTable>;
+
+    /// Rank of a guess with the specified similarity to the word of a rank table.
+    fn rank(&self, table: &RankTable, similarity: f32) -> usize;
+
+    /// Estimates how hard a word is to find.
+    fn difficulty(&self, word: &str, metric: Metric) -> Option<Difficulty>;
+
+    /// Picks a random word to find.
+    fn pick_word(&self, rng: &mut dyn RngCore) -> String;
+
+    /// Picks a random word to find among those that satisfy the predicate. Returns `None` if there's none.
+    fn pick_word_where(&self, rng: &mut dyn RngCore, predicate: &dyn Fn(&str) -> bool) -> Option<String>;
+
+    /// Like `pick_word_where`, but only picks words of the specified difficulty level.
+    fn pick_word_of_difficulty(
+        &self,
+        rng: &mut dyn RngCore,
+        level: DifficultyLevel,
+        metric: Metric,
+        predicate: &dyn Fn(&str) -> bool,
+    ) -> Option<String>;
+
+    /// Words that may be picked as the word to find.
+    fn pickable_words(&self) -> Vec<&str>;
+
+    /// Number of occurrences of a word in the corpus, if known.
+    fn frequency(&self, word: &str) -> Option<u64>;
+}
@@ -0,0 +1,106 @@
+//! Definitions of the words to find
+//!
+//! The definition of the word to find is looked up when a session starts, in a local dictionary file or
+//! through a dictionary API, and cached in the database so that `!hint` and the end of the game never wait
+//! for the network.
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    time::Duration,
+};
+
+/// Definitions are cut to this number of characters to fit on an IRC line.
+const MAX_DEFINITION_LENGTH: usize = 200;
+
+fn default_definitions_timeout() -> u64 {
+    10
+}
+
+/// `[definitions]` section of the configuration.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DefinitionsConfig {
+    /// Local dictionary, with one word per line followed by a tab and its definition.
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Dictionary API queried for the words missing from `file`, with `{word}` replaced by the word (e.g.
+    /// `https://api.dictionaryapi.dev/api/v2/entries/en/{word}`). Responses must follow the format of the
+    /// Free Dictionary API.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Request timeout in seconds.
+    #[serde(default = "default_definitions_timeout")]
+    pub timeout: u64,
+}
+
+/// Looks up the definitions of words.
+pub struct DefinitionFetcher {
+    config: DefinitionsConfig,
+    agent: ureq::Agent,
+    /// Definitions of the local dictionary, by word.
+    entries: HashMap<String, String>,
+}
+
+impl DefinitionFetcher {
+    /// Reads the local dictionary, if there's one.
+    pub fn new(config: DefinitionsConfig) -> Result<DefinitionFetcher> {
+        let mut entries = HashMap::new();
+        if let Some(ref path) = config.file {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                if let Some((word, definition)) = line.split_once('\t') {
+                    entries.insert(word.trim().to_lowercase(), definition.trim().to_string());
+                }
+            }
+            info!("read {} definitions from `{}`", entries.len(), path);
+        }
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(config.timeout))
+            .build();
+        Ok(DefinitionFetcher { config, agent, entries })
+    }
+
+    /// Returns a short definition of a word, or `None` if none could be found.
+    ///
+    /// This blocks until the API replies.
+    pub fn fetch(&self, word: &str) -> Option<String> {
+        let definition = match self.entries.get(&word.to_lowercase()) {
+            Some(definition) => definition.clone(),
+            None => match self.request(word) {
+                Ok(definition) => definition?,
+                Err(err) => {
+                    warn!("could not fetch the definition of \"{}\": {}", word, err);
+                    return None;
+                }
+            },
+        };
+        // IRC messages are single-line
+        let definition = definition.split_whitespace().collect::<Vec<_>>().join(" ");
+        if definition.chars().count() > MAX_DEFINITION_LENGTH {
+            let cut: String = definition.chars().take(MAX_DEFINITION_LENGTH - 1).collect();
+            Some(format!("{}…", cut.trim_end()))
+        } else {
+            Some(definition)
+        }
+    }
+
+    /// Queries the dictionary API. Returns `None` if there's no API or if the word is unknown.
+    fn request(&self, word: &str) -> Result<Option<String>> {
+        let url = match self.config.url {
+            Some(ref url) => url.replace("{word}", word),
+            None => return Ok(None),
+        };
+        let response: Value = match self.agent.get(&url).call() {
+            Ok(response) => response.into_json()?,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let definition = response[0]["meanings"][0]["definitions"][0]["definition"]
+            .as_str()
+            .ok_or_else(|| anyhow!("unexpected response from the API"))?;
+        Ok(Some(definition.to_string()))
+    }
+}
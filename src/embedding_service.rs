@@ -0,0 +1,87 @@
+//! Client for external embedding services
+//!
+//! Talks to any service exposing an OpenAI-compatible embeddings endpoint
+//! (text-embeddings-inference, Ollama, LocalAI, ...), which makes it possible to use
+//! sentence-transformer models and to have multi-word phrases as targets.
+use crate::words::normalize;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// `[embedding_service]` section of the configuration.
+#[derive(Clone, Debug, Deserialize)]
+pub struct EmbeddingServiceConfig {
+    /// URL of the embeddings endpoint (e.g. `http://localhost:8080/v1/embeddings`).
+    pub url: String,
+    /// Model name sent with each request.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// API key, sent as a bearer token.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Words and phrases (one per line) that make up the vocabulary.
+    pub vocabulary_file: String,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<&'a str>,
+    input: &'a [&'a str],
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+pub struct EmbeddingService {
+    config: EmbeddingServiceConfig,
+    agent: ureq::Agent,
+}
+
+impl EmbeddingService {
+    pub fn new(config: EmbeddingServiceConfig) -> EmbeddingService {
+        let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(30)).build();
+        EmbeddingService { config, agent }
+    }
+
+    pub fn config(&self) -> &EmbeddingServiceConfig {
+        &self.config
+    }
+
+    /// Computes the embeddings of the specified texts. The returned vectors are L2-normalized.
+    ///
+    /// This blocks until the service replies.
+    pub fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let mut request = self.agent.post(&self.config.url);
+        if let Some(ref api_key) = self.config.api_key {
+            request = request.set("Authorization", &format!("Bearer {}", api_key));
+        }
+        let response: EmbeddingResponse = request
+            .send_json(EmbeddingRequest {
+                model: self.config.model.as_deref(),
+                input: texts,
+            })?
+            .into_json()?;
+
+        let mut vectors = vec![Vec::new(); texts.len()];
+        for data in response.data {
+            let mut v = data.embedding;
+            normalize(&mut v);
+            *vectors
+                .get_mut(data.index)
+                .ok_or_else(|| anyhow!("embedding service returned an invalid index"))? = v;
+        }
+        if vectors.iter().any(Vec::is_empty) {
+            return Err(anyhow!("embedding service did not return all embeddings"));
+        }
+        Ok(vectors)
+    }
+}
@@ -0,0 +1,67 @@
+//! Game events, and their publication to external services
+//!
+//! The game sends its events on a broadcast channel, which any task can subscribe to with [`Game::subscribe`].
+//!
+//! [`Game::subscribe`]: crate::game::Game::subscribe
+use anyhow::Result;
+use redis::AsyncCommands;
+use serde::Serialize;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+/// Number of events kept for the subscribers that lag behind. Older events are dropped for them.
+pub const EVENT_CAPACITY: usize = 256;
+
+/// Something that happened in the game.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum GameEvent {
+    /// A new session has started.
+    SessionStarted { session_id: i64 },
+    /// A player made a guess of a known word.
+    GuessMade {
+        session_id: i64,
+        nick: String,
+        guess: String,
+        distance: f32,
+    },
+    /// A hint about the word was given, with `!hint` or automatically.
+    Hinted {
+        session_id: i64,
+        /// Number of the hint in the session, from 1.
+        number: usize,
+        hint: String,
+    },
+    /// The session has ended.
+    SessionEnded {
+        session_id: i64,
+        word: String,
+        /// Nick of the winner, if there is one.
+        winner: Option<String>,
+    },
+}
+
+/// Publishes game events as JSON messages on a Redis pub/sub channel.
+///
+/// Events are dropped (and an error is logged) if the Redis server is unreachable.
+pub async fn redis_publisher(url: String, channel: String, mut events: Receiver<GameEvent>) -> Result<()> {
+    let client = redis::Client::open(url)?;
+    let mut conn = redis::aio::ConnectionManager::new(client).await?;
+    info!("publishing game events on redis channel `{}`", channel);
+
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(count)) => {
+                warn!("{} game events were not published to redis", count);
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+        let payload = serde_json::to_string(&event)?;
+        if let Err(err) = conn.publish::<_, _, ()>(&channel, payload).await {
+            error!("failed to publish event to redis: {}", err);
+        }
+    }
+
+    Ok(())
+}
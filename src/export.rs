@@ -0,0 +1,63 @@
+//! Export of the words that can be picked as the word to find (`wordlebot export-vocab --out <file>`)
+use crate::{backend::EmbeddingBackend, words::SharedWords};
+use anyhow::{anyhow, Result};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+/// Options of the `export-vocab` subcommand.
+pub struct ExportOptions {
+    /// Output file.
+    pub out: String,
+    /// Also estimate the difficulty of each word, which is slow on large vocabularies.
+    pub difficulty: bool,
+}
+
+impl ExportOptions {
+    /// Parses `--out <file> [--difficulty]`.
+    pub fn parse(args: &[String]) -> Result<ExportOptions> {
+        let mut out = None;
+        let mut difficulty = false;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--out" => out = Some(args.next().ok_or_else(|| anyhow!("expected a file after `--out`"))?.clone()),
+                "--difficulty" => difficulty = true,
+                _ => return Err(anyhow!("unknown option `{}`", arg)),
+            }
+        }
+        Ok(ExportOptions {
+            out: out.ok_or_else(|| anyhow!("`--out <file>` is required"))?,
+            difficulty,
+        })
+    }
+}
+
+/// Writes the words that can be picked as the word to find, one per line, followed by their frequency (if
+/// there's a frequency file) and their density (with `--difficulty`), separated by tabs.
+pub fn export_vocabulary(shared_words: SharedWords, options: ExportOptions) -> Result<()> {
+    let words: &dyn EmbeddingBackend = shared_words.get()?.as_ref();
+    let mut writer = BufWriter::new(File::create(&options.out)?);
+    let candidates = words.pickable_words();
+    for &word in &candidates {
+        let mut line = word.to_string();
+        if let Some(frequency) = words.frequency(word) {
+            line.push_str(&format!("\t{}", frequency));
+        }
+        if options.difficulty {
+            if let Some(difficulty) = words.difficulty(word, words.metric()) {
+                line.push_str(&format!("\t{}", difficulty.density));
+            }
+        }
+        writeln!(writer, "{}", line)?;
+    }
+    writer.flush()?;
+    println!(
+        "exported {} of the {} words of the vocabulary to `{}`",
+        candidates.len(),
+        words.len(),
+        options.out
+    );
+    Ok(())
+}
@@ -0,0 +1,166 @@
+//! fastText binary models (`.bin`)
+//!
+//! Only the input matrix and the dictionary are loaded: they are enough to compute the vector of any
+//! word from its character n-grams, including words that are not in the vocabulary.
+use anyhow::{bail, Result};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Read},
+};
+
+const FASTTEXT_MAGIC: i32 = 793712314;
+const FASTTEXT_VERSION: i32 = 12;
+
+/// A fastText model, able to synthesize vectors for out-of-vocabulary words.
+pub struct FastTextModel {
+    dim: usize,
+    minn: usize,
+    maxn: usize,
+    bucket: u32,
+    /// Number of words in the dictionary. The n-gram rows come after the word rows in `input`.
+    nwords: usize,
+    /// Row of each word of the dictionary.
+    words: HashMap<String, usize>,
+    /// Input matrix, row-major, `dim` columns.
+    input: Vec<f32>,
+}
+
+fn read_i32(reader: &mut impl Read) -> Result<i32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+fn read_i64(reader: &mut impl Read) -> Result<i64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(i64::from_le_bytes(buf))
+}
+
+fn read_u8(reader: &mut impl Read) -> Result<u8> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+/// FNV-1a, with the bytes sign-extended as in the reference implementation.
+fn hash(bytes: &[u8]) -> u32 {
+    let mut h: u32 = 2166136261;
+    for &b in bytes {
+        h ^= b as i8 as u32;
+        h = h.wrapping_mul(16777619);
+    }
+    h
+}
+
+impl FastTextModel {
+    pub fn load(path: &str) -> Result<FastTextModel> {
+        let _span = trace_span!("Loading fastText model").entered();
+
+        let mut reader = BufReader::new(File::open(path)?);
+        if read_i32(&mut reader)? != FASTTEXT_MAGIC || read_i32(&mut reader)? != FASTTEXT_VERSION {
+            bail!("`{}` is not a fastText model, or its version is not supported", path);
+        }
+
+        // args: dim, ws, epoch, minCount, neg, wordNgrams, loss, model, bucket, minn, maxn, lrUpdateRate, t
+        let mut args = [0i32; 12];
+        for arg in args.iter_mut() {
+            *arg = read_i32(&mut reader)?;
+        }
+        let _t = read_i64(&mut reader)?;
+        let (dim, bucket, minn, maxn) = (args[0] as usize, args[8] as u32, args[9] as usize, args[10] as usize);
+
+        // dictionary
+        let size = read_i32(&mut reader)? as usize;
+        let nwords = read_i32(&mut reader)? as usize;
+        let _nlabels = read_i32(&mut reader)?;
+        let _ntokens = read_i64(&mut reader)?;
+        let pruneidx_size = read_i64(&mut reader)?;
+        let mut words = HashMap::with_capacity(nwords);
+        for i in 0..size {
+            let mut word = Vec::new();
+            reader.read_until(0, &mut word)?;
+            word.pop();
+            let _count = read_i64(&mut reader)?;
+            let entry_type = read_u8(&mut reader)?;
+            // labels (type 1) come after the words
+            if entry_type == 0 {
+                words.insert(String::from_utf8_lossy(&word).into_owned(), i);
+            }
+        }
+        if pruneidx_size > 0 {
+            bail!("pruned fastText models are not supported");
+        }
+
+        if read_u8(&mut reader)? != 0 {
+            bail!("quantized fastText models are not supported");
+        }
+        let rows = read_i64(&mut reader)? as usize;
+        let cols = read_i64(&mut reader)? as usize;
+        if cols != dim || rows != nwords + bucket as usize {
+            bail!("unexpected input matrix size in fastText model");
+        }
+        let mut bytes = vec![0u8; rows * cols * 4];
+        reader.read_exact(&mut bytes)?;
+        let input = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        Ok(FastTextModel {
+            dim,
+            minn,
+            maxn,
+            bucket,
+            nwords,
+            words,
+            input,
+        })
+    }
+
+    /// Rows of the character n-grams of a word, as computed by fastText.
+    fn ngram_rows(&self, word: &str) -> Vec<usize> {
+        let word = format!("<{}>", word);
+        let bytes = word.as_bytes();
+        let mut rows = Vec::new();
+        for i in 0..bytes.len() {
+            // n-grams start on character boundaries
+            if bytes[i] & 0xC0 == 0x80 {
+                continue;
+            }
+            let mut j = i;
+            let mut n = 1;
+            while j < bytes.len() && n <= self.maxn {
+                j += 1;
+                while j < bytes.len() && bytes[j] & 0xC0 == 0x80 {
+                    j += 1;
+                }
+                if n >= self.minn && !(n == 1 && (i == 0 || j == bytes.len())) {
+                    rows.push(self.nwords + (hash(&bytes[i..j]) % self.bucket) as usize);
+                }
+                n += 1;
+            }
+        }
+        rows
+    }
+
+    /// Returns the vector of a word: the average of the vectors of the word itself (if it's in the
+    /// dictionary) and of its character n-grams. Returns `None` if the word has no n-grams.
+    pub fn word_vector(&self, word: &str) -> Option<Vec<f32>> {
+        let mut rows = self.ngram_rows(word);
+        if let Some(&row) = self.words.get(word) {
+            rows.push(row);
+        }
+        if rows.is_empty() {
+            return None;
+        }
+        let mut vector = vec![0.0; self.dim];
+        for row in &rows {
+            let row = &self.input[row * self.dim..(row + 1) * self.dim];
+            vector.iter_mut().zip(row).for_each(|(v, x)| *v += x);
+        }
+        vector.iter_mut().for_each(|v| *v /= rows.len() as f32);
+        Some(vector)
+    }
+}
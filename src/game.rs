@@ -1,260 +1,2366 @@
 //! Game
-use crate::words::Words;
-use anyhow::{anyhow, bail, Result};
-use futures::TryStreamExt;
-use rusqlite::{params, OptionalExtension};
+use crate::{
+    achievements::{self, Achievement, UnlockedAchievement},
+    backend::EmbeddingBackend,
+    definitions::DefinitionFetcher,
+    events::{GameEvent, EVENT_CAPACITY},
+    heat,
+    rating,
+    reverse::{ReverseGame, ReverseGuess},
+    storage::{SessionResult, Storage},
+    wordle::{self, LetterHint, WORDLE_WORD_LENGTH},
+    words::{Difficulty, DifficultyLevel, Metric, NeighborTable, RankTable, WordModels},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Datelike, Months, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
     sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 use tokio::{
-    sync::Mutex,
-    time::{Duration, Instant},
+    sync::{broadcast, mpsc::UnboundedSender, Mutex, Notify},
+    task::block_in_place,
+    time::Duration,
 };
 
+/// Rank (among the neighbors of the word to find) of the word revealed by the neighbor hint.
+const HINT_RANK: usize = 300;
+
+/// Ranks (among the neighbors of the word to find) of the words revealed by the category hint.
+const CATEGORY_RANKS: [usize; 3] = [30, 60, 100];
+
+/// Number of neighbors of the word to find among which misses are ranked (semantic mode).
+pub const NEAREST_RANKS: usize = 1000;
+
+/// Maximum number of corrections suggested for an unknown word.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Number of words picked before giving up finding one with enough close neighbors.
+const MAX_TARGET_ATTEMPTS: usize = 20;
+
+/// Number of nearest neighbors of the theme word among which the word to find of a themed session is picked, if the
+/// theme has no word list.
+const THEME_CLUSTER_SIZE: usize = 300;
+
+/// Number of remaining guesses of a cooperative session at which the players are warned.
+const BUDGET_WARNINGS: [usize; 5] = [50, 20, 10, 5, 1];
+
+/// Number of guesses of a cooperative session if `!start coop` doesn't specify one.
+pub const DEFAULT_GUESS_BUDGET: usize = 100;
+
+/// Duration of blitz sessions.
+pub const BLITZ_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// Factor of the points won in blitz sessions.
+const BLITZ_POINTS_FACTOR: i64 = 2;
+
+/// A vote to give up a session is cancelled if it doesn't reach the quorum within this delay.
+const GIVEUP_VOTE_DURATION: Duration = Duration::from_secs(15 * 60);
+
+/// Maximum number of guesses shown in a share text. Only the last ones are shown.
+const MAX_SHARE_SQUARES: usize = 30;
+
+/// Practice games are abandoned after this long without a guess.
+const PRACTICE_TIMEOUT: Duration = Duration::from_secs(24 * 3600);
+
+/// Maximum length of a team name.
+const MAX_TEAM_NAME_LENGTH: usize = 20;
+
+/// Length of a tournament if `!tournament start` doesn't specify one, in days.
+pub const DEFAULT_TOURNAMENT_DAYS: u64 = 7;
+
+/// Maximum length of a tournament name.
+const MAX_TOURNAMENT_NAME_LENGTH: usize = 50;
+
+/// Delay before trying again to end a session that has run out of time or a season, if it failed.
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Model types
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+#[derive(Clone, Debug, Serialize)]
 pub struct Player {
     pub id: i64,
     pub nick: String,
+    /// Total points won.
+    pub score: i64,
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////
-// Schema
-////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Overall results of a player.
+#[derive(Serialize)]
+pub struct PlayerStats {
+    /// The player, with their all-time score.
+    #[serde(flatten)]
+    pub player: Player,
+    /// Score of the player in the current season, if there are seasons.
+    pub season_score: Option<i64>,
+    /// Number of sessions won.
+    pub wins: i64,
+    /// Number of sessions in which the player made a guess.
+    pub games: i64,
+}
+
+/// Detailed results of a player.
+#[derive(Serialize)]
+pub struct PlayerProfile {
+    #[serde(flatten)]
+    pub stats: PlayerStats,
+    /// Average number of guesses of the player in the sessions they won.
+    pub average_guesses_to_win: Option<f64>,
+    /// Highest similarity of a guess of the player to the word to find, other than the word itself, in
+    /// semantic and rank sessions.
+    pub best_similarity: Option<f32>,
+    /// Number of consecutive sessions won by the player among the last ones they took part in.
+    pub streak: usize,
+    /// Rating of the player, if they have finished a session.
+    pub rating: Option<f64>,
+    /// Achievements of the player, the oldest first.
+    pub achievements: Vec<UnlockedAchievement>,
+}
+
+/// Best guess of a player in a session.
+#[derive(Serialize)]
+pub struct LeaderboardEntry {
+    pub nick: String,
+    pub guess: String,
+    /// Similarity of the guess to the word (letter score in Wordle sessions).
+    pub similarity: f32,
+    /// Rank of the guess among the neighbors of the word, if it's known.
+    pub rank: Option<usize>,
+    /// Heat of the guess (see [`NeighborTable::heat`]), if it's known.
+    pub heat: Option<f32>,
+}
+
+impl LeaderboardEntry {
+    /// Heat of the guess as an ASCII bar, if it's known.
+    pub fn heat_bar(&self) -> Option<String> {
+        self.heat.map(heat::ascii_bar)
+    }
+}
+
+/// Overall results of a team.
+#[derive(Serialize)]
+pub struct TeamStats {
+    pub name: String,
+    /// Total of the points won by the members for the team.
+    pub score: i64,
+    /// Number of players currently in the team.
+    pub members: i64,
+    /// Number of sessions won by a member for the team.
+    pub wins: i64,
+}
+
+/// A guess made in a session.
+#[derive(Serialize)]
+pub struct GuessRecord {
+    pub nick: String,
+    pub guess: String,
+    /// Distance of the guess to the word (letter score in Wordle sessions).
+    pub distance: f32,
+}
+
+/// A guess of a session transcript.
+#[derive(Serialize)]
+pub struct TranscriptGuess {
+    pub nick: String,
+    pub guess: String,
+    /// Similarity of the guess to the word (letter score in Wordle sessions).
+    pub similarity: f32,
+    /// When the guess was made, unknown for the guesses made before the dates were recorded.
+    pub timestamp: Option<i64>,
+    /// Seconds between the start of the session and the guess.
+    pub elapsed: Option<i64>,
+}
+
+/// Transcript of a session: its word, its guesses and its outcome, for post-mortems.
+#[derive(Serialize)]
+pub struct SessionTranscript {
+    #[serde(flatten)]
+    pub session: Session,
+    /// `None` while the session is in progress.
+    pub end_date: Option<i64>,
+    /// Nick of the winner.
+    pub winner: Option<String>,
+    /// Guesses in the order they were made. Empty if the guesses of the session were archived.
+    pub guesses: Vec<TranscriptGuess>,
+}
+
+/// Game type of a session.
+#[derive(Copy, Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+    /// Find the word using the semantic similarity of the guesses.
+    #[default]
+    Semantic,
+    /// Classic Wordle: find a 5-letter word with letter-by-letter feedback.
+    Wordle,
+    /// Like semantic, but the only feedback is the rank of the guess among all words of the vocabulary.
+    Rank,
+    /// Speed round: like semantic, but the session lasts 10 minutes, the word is easier to find unless a difficulty
+    /// is specified, and the points are doubled.
+    Blitz,
+}
+
+impl GameMode {
+    /// Name of the mode, as stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GameMode::Semantic => "semantic",
+            GameMode::Wordle => "wordle",
+            GameMode::Rank => "rank",
+            GameMode::Blitz => "blitz",
+        }
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<GameMode> {
+        match s {
+            "semantic" => Ok(GameMode::Semantic),
+            "wordle" => Ok(GameMode::Wordle),
+            "rank" => Ok(GameMode::Rank),
+            "blitz" => Ok(GameMode::Blitz),
+            _ => Err(anyhow!("unknown game mode `{}`", s)),
+        }
+    }
+}
+
+/// A season, during which the points won by the players add up to a separate score. Dates are UNIX
+/// timestamps in seconds.
+#[derive(Clone, Debug, Serialize)]
+pub struct Season {
+    pub id: i64,
+    pub start_date: i64,
+}
+
+/// A tournament, during which the registered players win tournament points for their place in each session. Dates
+/// are UNIX timestamps in seconds.
+#[derive(Clone, Debug, Serialize)]
+pub struct Tournament {
+    pub id: i64,
+    pub name: String,
+    pub start_date: i64,
+    /// The tournament ends at this date, and its winner is announced.
+    pub end_date: i64,
+}
+
+/// A game session. Dates are UNIX timestamps in seconds.
+#[derive(Clone, Debug, Serialize)]
+pub struct Session {
+    pub id: i64,
+    /// The word to guess.
+    pub word: String,
+    pub start_date: i64,
+    pub planned_end_date: i64,
+    /// Similarity metric used for the guesses of this session.
+    pub metric: Metric,
+    /// Game type.
+    pub mode: GameMode,
+    /// Difficulty of the word to guess, if it was estimated.
+    pub difficulty: Option<Difficulty>,
+    /// Language of the word model, `None` for the default model.
+    pub language: Option<String>,
+    /// Hard mode: a guess is only accepted if it's closer to the word than the previous guesses of the player
+    /// (semantic and rank modes).
+    pub strict: bool,
+    /// Cooperative mode: number of guesses shared by all the players. The session is lost when they are all used
+    /// without finding the word.
+    pub budget: Option<usize>,
+    /// Number of hints given so far.
+    pub hints: usize,
+    /// Theme of the word to find, if the session is themed.
+    pub theme: Option<String>,
+}
+
+/// A player who found the word of a session.
+#[derive(Clone, Debug)]
+pub struct SessionWinner {
+    pub player_id: i64,
+    pub nick: String,
+    /// Points won for finding the word, before the points for taking part and near misses.
+    pub points: i64,
+}
+
+/// Settings of a new session.
+#[derive(Clone, Debug, Default)]
+pub struct SessionOptions {
+    pub mode: GameMode,
+    /// Overrides the default similarity metric of the word model.
+    pub metric: Option<Metric>,
+    /// Overrides the default difficulty level.
+    pub difficulty: Option<DifficultyLevel>,
+    /// Language of the word model. The default model is used if unset.
+    pub language: Option<String>,
+    /// Hard mode: a guess is only accepted if it's closer to the word than the previous guesses of the player.
+    pub strict: bool,
+    /// Cooperative mode: number of guesses shared by all the players.
+    pub budget: Option<usize>,
+    /// Word to find, instead of a random one. It must be in the vocabulary.
+    pub word: Option<String>,
+    /// Theme of the word to find (e.g. `animals`).
+    pub theme: Option<String>,
+}
+
+/// Source of randomness used to pick the words to find.
+pub struct WordRng {
+    rng: StdRng,
+    seed: Option<u64>,
+    daily: bool,
+}
+
+impl WordRng {
+    /// With a seed, the sequence of picked words is reproducible. In daily mode, the word only depends on the
+    /// seed and on the day the session starts, so that several instances pick the same word of the day.
+    pub fn new(seed: Option<u64>, daily: bool) -> WordRng {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        WordRng { rng, seed, daily }
+    }
+
+    /// Returns the generator used to pick the word of a session starting at the specified unix time.
+    fn session_rng(&mut self, start_time_unix: u64) -> StdRng {
+        if self.daily {
+            let day = start_time_unix / (24 * 3600);
+            StdRng::seed_from_u64(self.seed.unwrap_or(0) ^ day.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+        } else {
+            StdRng::from_rng(&mut self.rng).expect("failed to seed the random generator")
+        }
+    }
+}
+
+impl Default for WordRng {
+    fn default() -> WordRng {
+        WordRng::new(None, false)
+    }
+}
+
+/// How the words to find are picked.
+#[derive(Default)]
+pub struct TargetSettings {
+    /// Number of past sessions whose word is not picked again.
+    pub recent_words_window: usize,
+    /// Default difficulty of the words to find.
+    pub difficulty_level: DifficultyLevel,
+    pub rng: WordRng,
+    /// Minimum number of neighbors of the word to find with a similarity of at least `close_similarity`.
+    /// Words with fewer close neighbors are nearly impossible to find, so another word is picked.
+    pub min_close_neighbors: usize,
+    pub close_similarity: f32,
+    /// Word lists of the themes, by theme name.
+    pub themes: HashMap<String, HashSet<String>>,
+}
+
+impl TargetSettings {
+    /// Returns whether a word has enough close neighbors to be picked.
+    fn has_close_neighbors(&self, words: &dyn EmbeddingBackend, word: &str, metric: Metric) -> bool {
+        if self.min_close_neighbors == 0 {
+            return true;
+        }
+        words
+            .neighbors(word, self.min_close_neighbors, metric)
+            .is_some_and(|neighbors| {
+                neighbors.len() == self.min_close_neighbors
+                    && neighbors.iter().all(|&(_, similarity)| similarity >= self.close_similarity)
+            })
+    }
+
+    /// Returns the words of a theme: its word list if it has one, or else the nearest neighbors of the theme word.
+    fn theme_words(&self, words: &dyn EmbeddingBackend, theme: &str, metric: Metric) -> Result<HashSet<String>> {
+        if let Some(list) = self.themes.get(theme) {
+            return Ok(list.clone());
+        }
+        let neighbors = words
+            .neighbors(&words.normalize_guess(theme), THEME_CLUSTER_SIZE, metric)
+            .ok_or_else(|| anyhow!("unknown theme \"{}\"", theme))?;
+        Ok(neighbors.into_iter().map(|(word, _)| word).collect())
+    }
+}
+
+fn default_win_points() -> f64 {
+    100.0
+}
+
+fn default_guess_penalty() -> f64 {
+    1.0
+}
+
+fn default_hour_penalty() -> f64 {
+    2.0
+}
+
+fn default_min_points() -> i64 {
+    10
+}
+
+fn default_participation_points() -> i64 {
+    1
+}
+
+fn default_near_miss_points() -> i64 {
+    1
+}
+
+fn default_near_miss_rank() -> usize {
+    100
+}
+
+fn default_max_near_misses() -> usize {
+    10
+}
+
+fn default_team_win_points() -> i64 {
+    5
+}
+
+fn default_hint_penalty() -> f64 {
+    10.0
+}
+
+fn default_tournament_points() -> Vec<i64> {
+    vec![10, 7, 5, 4, 3, 2, 1]
+}
+
+fn default_max_winners() -> usize {
+    1
+}
+
+fn default_later_winner_factor() -> f64 {
+    0.5
+}
+
+/// `[scoring]` section of the configuration: points given to the players when a session ends.
+///
+/// The winner gets `win_points`, minus `guess_penalty` for each of their guesses before the winning one and
+/// `hour_penalty` for each hour since the start of the session and `hint_penalty` for each hint given with `!hint`,
+/// but at least `min_points`.
+///
+/// The session ends once `max_winners` players found the word, or at its end date if `max_winners` is 0. Each winner
+/// after the first one gets `later_winner_factor` times the points the previous one would have got. The first winner
+/// is the winner of the session in the statistics. With several winners, the players can guess in private messages
+/// with `!guess`, so that the channels don't see the word.
+///
+/// With `late_solvers`, the session goes on after the first winner for the other players of the session, who can keep
+/// guessing in private messages until its end date, or until they all found the word. They get points as later
+/// winners. Meanwhile, the channels only see a recap when the session ends.
+///
+/// Whether or not someone won, each player who made a guess gets `participation_points`, plus
+/// `near_miss_points` for each different guess among the `near_miss_rank` nearest neighbors of the word
+/// (up to `max_near_misses` of them). Near misses don't count in Wordle sessions.
+///
+/// The other players of the session who played for the team of the winner get `team_win_points`. All the points
+/// won by the players of a team are also added to the score of the team.
+///
+/// During a tournament, the registered players of the session also win `tournament_points[i]` tournament points for
+/// the place `i` (from 0) among them: the winner first, then the closest best guesses.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScoringConfig {
+    #[serde(default = "default_win_points")]
+    pub win_points: f64,
+    #[serde(default = "default_guess_penalty")]
+    pub guess_penalty: f64,
+    #[serde(default = "default_hour_penalty")]
+    pub hour_penalty: f64,
+    #[serde(default = "default_min_points")]
+    pub min_points: i64,
+    #[serde(default = "default_participation_points")]
+    pub participation_points: i64,
+    #[serde(default = "default_near_miss_points")]
+    pub near_miss_points: i64,
+    #[serde(default = "default_near_miss_rank")]
+    pub near_miss_rank: usize,
+    #[serde(default = "default_max_near_misses")]
+    pub max_near_misses: usize,
+    #[serde(default = "default_team_win_points")]
+    pub team_win_points: i64,
+    #[serde(default = "default_hint_penalty")]
+    pub hint_penalty: f64,
+    #[serde(default = "default_tournament_points")]
+    pub tournament_points: Vec<i64>,
+    #[serde(default = "default_max_winners")]
+    pub max_winners: usize,
+    #[serde(default = "default_later_winner_factor")]
+    pub later_winner_factor: f64,
+    #[serde(default)]
+    pub late_solvers: bool,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> ScoringConfig {
+        ScoringConfig {
+            win_points: default_win_points(),
+            guess_penalty: default_guess_penalty(),
+            hour_penalty: default_hour_penalty(),
+            min_points: default_min_points(),
+            participation_points: default_participation_points(),
+            near_miss_points: default_near_miss_points(),
+            near_miss_rank: default_near_miss_rank(),
+            max_near_misses: default_max_near_misses(),
+            team_win_points: default_team_win_points(),
+            hint_penalty: default_hint_penalty(),
+            tournament_points: default_tournament_points(),
+            max_winners: default_max_winners(),
+            later_winner_factor: default_later_winner_factor(),
+            late_solvers: false,
+        }
+    }
+}
+
+impl ScoringConfig {
+    /// Points won by finding the word with `guesses` guesses (including the winning one), `elapsed` seconds
+    /// after the start of the session, after `hints` hints, at `place` (from 0) among the winners.
+    fn points(&self, guesses: usize, elapsed: i64, hints: usize, place: usize) -> i64 {
+        let penalty = self.guess_penalty * guesses.saturating_sub(1) as f64
+            + self.hour_penalty * elapsed.max(0) as f64 / 3600.0
+            + self.hint_penalty * hints as f64;
+        let points = ((self.win_points - penalty).round() as i64).max(self.min_points);
+        (points as f64 * self.later_winner_factor.powi(place as i32)).round() as i64
+    }
+
+    /// Whether a session ends once `winners` players found the word, outside of late solver mode.
+    fn enough_winners(&self, winners: usize) -> bool {
+        self.max_winners != 0 && winners >= self.max_winners
+    }
+
+    /// Whether several players can find the word of a session.
+    fn several_winners(&self) -> bool {
+        self.max_winners != 1 || self.late_solvers
+    }
+}
+
+/// A session ended with `!end`, and what is revealed about it.
+pub struct EndedSession {
+    pub word: String,
+    /// Definition of the word, if it's known.
+    pub definition: Option<String>,
+    /// Best guess of each player of the session, the closest first.
+    pub leaderboard: Vec<LeaderboardEntry>,
+}
 
-/// Creates the database tables.
-fn setup_schema(conn: &mut rusqlite::Connection) -> Result<()> {
-    // players: ID -> nick, score (total score)
-    // sessions (result of past sessions): ID -> start_date, end_date, planned_end_date, word, winner, is_current (whether the session is in progress)
-    // current_session:
-    // guesses (records all guesses made across all sessions): ID -> session ID, player ID, guess, cosine
-    conn.execute_batch(
-        // language=SQLITE-SQL
-        r#"
-CREATE TABLE IF NOT EXISTS players
-         (id      INTEGER PRIMARY KEY,
-          nick    TEXT UNIQUE NOT NULL,
-          score   INTEGER);
-
-CREATE TABLE IF NOT EXISTS sessions
-         (id               INTEGER PRIMARY KEY,
-		  start_date       INTEGER,
-          end_date         INTEGER,
-		  planned_end_date INTEGER,
-		  word             TEXT NOT NULL,
-		  winner_id        INTEGER REFERENCES players(id) ON DELETE NO ACTION,
-		  playing          INTEGER);
-
-CREATE TABLE IF NOT EXISTS current_session
-         (id            INTEGER PRIMARY KEY DEFAULT 0,
-          session_id    INTEGER REFERENCES sessions(id) ON DELETE NO ACTION);
-
-INSERT OR IGNORE INTO current_session(id) VALUES (0);
-
-CREATE TABLE IF NOT EXISTS guesses
-         (id         INTEGER PRIMARY KEY,
-          session_id INTEGER REFERENCES sessions(id) ON DELETE NO ACTION,
-          player_id  INTEGER REFERENCES players(id) ON DELETE NO ACTION,
-          guess      TEXT NOT NULL,
-          cosine     NUMERIC);
-          "#,
-    )?;
-
-    Ok(())
+/// Result of a vote to give up the current session.
+pub enum GiveUpVote {
+    /// The vote was counted, but there are not enough votes yet.
+    Counted { votes: usize, required: usize },
+    /// The player had already voted.
+    AlreadyVoted { votes: usize, required: usize },
+    /// Enough players voted: the session ended with no winner.
+    Ended {
+        word: String,
+        /// Definition of the word, if it's known.
+        definition: Option<String>,
+    },
+}
+
+/// A hint about the word to find, given by `!hint`.
+#[derive(Clone, Debug)]
+pub struct Hint {
+    pub text: String,
+    /// Number of the hint in the session, from 1. Each hint is stronger than the previous ones.
+    pub number: usize,
+    /// Number of hints available in the session.
+    pub count: usize,
+    /// Points the winner will lose for all the hints given so far.
+    pub penalty: i64,
+    /// Theme of the session, given with every hint at no cost.
+    pub theme: Option<String>,
 }
 
 /// The outcome of a guess.
 pub enum Outcome {
-    /// The player found the word and won. The game is now ended.
-    Win,
+    /// The player found the word and won.
+    Win {
+        /// Points added to the score of the player, including the ones for taking part and near misses if the
+        /// session ended.
+        points: i64,
+        /// Definition of the word, if it's known and the session ended.
+        definition: Option<String>,
+        /// Place of the player among the players who found the word, from 1.
+        place: usize,
+        /// Whether the session ended. Otherwise, other players can still find the word.
+        ended: bool,
+    },
+    /// The player already found the word of this session, which other players are still looking for.
+    AlreadyFound,
     /// The player did not find the word.
     Miss {
         /// The distance to the actual word.
         distance: f32,
+        /// Rank of the guess among the `NEAREST_RANKS` nearest neighbors of the word, 1 being the closest.
+        /// `None` if the guess is further than all of them.
+        rank: Option<usize>,
+        /// Distance of the best previous guess of the player in the session, `None` if this is their first guess.
+        best: Option<f32>,
+        /// Heat of the guess (see [`NeighborTable::heat`]).
+        heat: f32,
+    },
+    /// The player did not find the word (rank mode).
+    RankMiss {
+        /// Rank of the guess among the words of the vocabulary, 1 being the word to find.
+        rank: usize,
+    },
+    /// The player did not find the word (Wordle mode).
+    WordleMiss {
+        /// The guess, as looked up in the vocabulary.
+        guess: String,
+        /// Feedback for each letter of the guess.
+        hints: Vec<LetterHint>,
     },
     /// The player did not enter a recognized word
-    UnknownWord,
+    UnknownWord {
+        /// Words of the vocabulary with a similar spelling.
+        suggestions: Vec<String>,
+    },
+    /// The guess doesn't have the required number of letters (Wordle mode).
+    WrongLength { expected: usize },
+    /// The guess is a stop word, which can't be the word to find.
+    StopWord,
+    /// The guess is not closer to the word than the best previous guess of the player (hard mode). It is not
+    /// recorded.
+    NotCloser {
+        /// Distance of the best previous guess of the player.
+        best: f32,
+        /// Rank of the best previous guess of the player among the words of the vocabulary, in rank mode.
+        rank: Option<usize>,
+    },
+    /// The guess was recorded, but it used the last guess of the budget without finding the word (cooperative
+    /// mode). The session is now ended.
+    OutOfGuesses {
+        /// The word that nobody found.
+        word: String,
+        /// Definition of the word, if it's known.
+        definition: Option<String>,
+    },
+    /// The guess was already made in this session (semantic and rank modes). It is not recorded again.
+    AlreadyGuessed {
+        /// Nick of the player who made the guess first.
+        nick: String,
+        /// The distance to the actual word.
+        distance: f32,
+        /// Rank of the guess among the words of the vocabulary, in rank mode.
+        rank: Option<usize>,
+    },
+}
+
+/// Session and season changes that no player asked for, to announce them.
+#[derive(Clone, Debug)]
+pub enum SessionNotice {
+    /// The session ended because its planned end date was reached.
+    Expired {
+        /// The word that nobody found.
+        word: String,
+        /// Definition of the word, if it's known.
+        definition: Option<String>,
+    },
+    /// A session was started by the schedule.
+    Started {
+        /// Theme of the word to find, if the session is themed.
+        theme: Option<String>,
+    },
+    /// A session in which some players found the word ended, in late solver mode or with several winners.
+    Recap {
+        word: String,
+        /// Definition of the word, if it's known.
+        definition: Option<String>,
+        /// Nicks of the players who found the word, in the order they found it.
+        winners: Vec<String>,
+    },
+    /// Nobody found the word after some fraction of the session duration.
+    Hint { hint: Hint },
+    /// Few guesses are left in a cooperative session.
+    GuessesLeft { remaining: usize },
+    /// A season ended.
+    SeasonEnded {
+        /// Players who scored points during the season, with their season score, the best first.
+        standings: Vec<Player>,
+    },
+    /// Daily standings of the tournament in progress, after `day` days.
+    TournamentStandings {
+        name: String,
+        day: i64,
+        /// Players registered to the tournament, with their tournament points, the best first.
+        standings: Vec<Player>,
+    },
+    /// A tournament ended.
+    TournamentEnded {
+        name: String,
+        /// Players registered to the tournament, with their tournament points, the best first.
+        standings: Vec<Player>,
+    },
+    /// A player unlocked an achievement.
+    AchievementUnlocked { nick: String, achievement: Achievement },
+}
+
+/// Length of the seasons. Seasons follow the calendar in UTC: weeks start on Monday.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SeasonLength {
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl SeasonLength {
+    /// Start of the season that contains `date`.
+    fn season_start(self, date: DateTime<Utc>) -> DateTime<Utc> {
+        let date = date.date_naive();
+        let start = match self {
+            SeasonLength::Weekly => date - chrono::Duration::days(date.weekday().num_days_from_monday() as i64),
+            SeasonLength::Monthly => date.with_day(1).unwrap(),
+            SeasonLength::Yearly => date.with_ordinal(1).unwrap(),
+        };
+        Utc.from_utc_datetime(&start.and_time(NaiveTime::MIN))
+    }
+
+    /// Start of the season that follows the one that starts at `start`.
+    fn next_season_start(self, start: DateTime<Utc>) -> DateTime<Utc> {
+        let start = start.date_naive();
+        let next = match self {
+            SeasonLength::Weekly => start + chrono::Duration::days(7),
+            SeasonLength::Monthly => start.checked_add_months(Months::new(1)).unwrap(),
+            SeasonLength::Yearly => start.checked_add_months(Months::new(12)).unwrap(),
+        };
+        Utc.from_utc_datetime(&next.and_time(NaiveTime::MIN))
+    }
+}
+
+fn default_schedule_timezone() -> Tz {
+    Tz::UTC
+}
+
+/// `[schedule]` section of the configuration (formerly `[daily_start]`). Exactly one of `time` and `cron`
+/// must be set.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScheduleConfig {
+    /// Time of day at which a new session starts every day (`HH:MM`).
+    #[serde(default)]
+    pub time: Option<String>,
+    /// Cron expression of the start times of the sessions, either with 5 fields (`min hour day month
+    /// weekday`, e.g. `0 8 * * Mon-Fri` for weekdays at 8:00) or with seconds and an optional year.
+    #[serde(default)]
+    pub cron: Option<String>,
+    /// Time zone of `time` and `cron` (e.g. `Europe/Paris`).
+    #[serde(default = "default_schedule_timezone")]
+    pub timezone: Tz,
+    /// Game type of the scheduled sessions.
+    #[serde(default)]
+    pub mode: GameMode,
+    /// Overrides the default difficulty level.
+    #[serde(default)]
+    pub difficulty: Option<DifficultyLevel>,
+    /// Language of the word model. The default model is used if unset.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Hard mode: a guess is only accepted if it's closer to the word than the previous guesses of the player.
+    #[serde(default)]
+    pub strict: bool,
+    /// Cooperative mode: number of guesses shared by all the players.
+    #[serde(default)]
+    pub budget: Option<usize>,
+    /// Theme of the words to find.
+    #[serde(default)]
+    pub theme: Option<String>,
+}
+
+/// Start times of the scheduled sessions.
+enum StartTimes {
+    /// Every day at this local time.
+    Daily(NaiveTime),
+    Cron(Box<cron::Schedule>),
+}
+
+/// When and how the scheduled sessions are started.
+pub struct StartSchedule {
+    times: StartTimes,
+    timezone: Tz,
+    options: SessionOptions,
+}
+
+impl StartSchedule {
+    pub fn new(config: ScheduleConfig) -> Result<StartSchedule> {
+        let times = match (config.time, config.cron) {
+            (Some(time), None) => StartTimes::Daily(
+                NaiveTime::parse_from_str(&time, "%H:%M")
+                    .with_context(|| format!("invalid time of day `{}`, expected `HH:MM`", time))?,
+            ),
+            (None, Some(expression)) => {
+                // the cron crate expects seconds first
+                let full_expression = if expression.split_whitespace().count() == 5 {
+                    format!("0 {}", expression)
+                } else {
+                    expression.clone()
+                };
+                StartTimes::Cron(Box::new(
+                    cron::Schedule::from_str(&full_expression)
+                        .with_context(|| format!("invalid cron expression `{}`", expression))?,
+                ))
+            }
+            _ => bail!("exactly one of `time` and `cron` must be set"),
+        };
+        Ok(StartSchedule {
+            times,
+            timezone: config.timezone,
+            options: SessionOptions {
+                mode: config.mode,
+                difficulty: config.difficulty,
+                language: config.language,
+                strict: config.strict,
+                budget: config.budget,
+                theme: config.theme,
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Returns the first start time strictly after `after`, or `None` if there's none. Daily times that
+    /// don't exist on some day (daylight saving time change) are skipped that day.
+    fn next_start(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let time = match self.times {
+            StartTimes::Daily(time) => time,
+            StartTimes::Cron(ref schedule) => {
+                return schedule
+                    .after(&after.with_timezone(&self.timezone))
+                    .next()
+                    .map(|start| start.with_timezone(&Utc));
+            }
+        };
+        let mut date = after.with_timezone(&self.timezone).date_naive();
+        loop {
+            if let Some(start) = self.timezone.from_local_datetime(&date.and_time(time)).earliest() {
+                let start = start.with_timezone(&Utc);
+                if start > after {
+                    return Some(start);
+                }
+            }
+            date = date.succ_opt()?;
+        }
+    }
+}
+
+/// A practice game of a player, played in private messages. It is only kept in memory, and doesn't count towards
+/// scores, ratings or achievements.
+struct PracticeGame {
+    word: String,
+    metric: Metric,
+    /// Nearest neighbors of the word to find, to rank the guesses.
+    neighbor_table: NeighborTable,
+    /// Words guessed so far.
+    guesses: HashSet<String>,
+    /// Distance of the best guess so far.
+    best: Option<f32>,
+    /// When the last guess was made, or the game started.
+    last_activity: Instant,
 }
 
+impl PracticeGame {
+    fn expired(&self) -> bool {
+        self.last_activity.elapsed() >= PRACTICE_TIMEOUT
+    }
+}
+
+/// The state of the game is only changed by one task at a time, which awaits the database. Lookups in the word models
+/// don't await anything: the ones that scan the vocabulary or may block on network requests run with
+/// [`block_in_place`], so the runtime must be multi-threaded.
 struct GameState {
-    conn: rusqlite::Connection,
+    storage: Box<dyn Storage>,
     /// Current session ID. `None` if there's no game in progress.
     session_id: Option<i64>,
+    /// ID of the last session that ended since the bot started.
+    last_session_id: Option<i64>,
     /// Current word to guess.
     word: String,
-    /// Word database.
-    words: Arc<Words>,
+    /// Start and planned end dates of the current session.
+    start_date: i64,
+    planned_end_date: i64,
+    /// Similarity metric of the current session.
+    metric: Metric,
+    /// Game type of the current session.
+    mode: GameMode,
+    /// Difficulty of the word to find.
+    difficulty: Option<Difficulty>,
+    /// In rank mode, similarities of the words to the word to find. Computed on first use.
+    rank_table: Option<RankTable>,
+    /// Nearest neighbors of the word to find, used for ranks and hints. Computed when the session starts,
+    /// or on first use.
+    neighbor_table: Option<NeighborTable>,
+    /// Language of the current session, `None` for the default model.
+    language: Option<String>,
+    /// Whether the current session is in hard mode.
+    strict: bool,
+    /// Number of guesses shared by the players of the current session, in cooperative mode.
+    budget: Option<usize>,
+    /// Number of guesses recorded in the current session.
+    guess_count: usize,
+    /// Number of hints given in the current session.
+    hints: usize,
+    /// Theme of the current session, if it's themed.
+    theme: Option<String>,
+    /// Players who found the word of the current session, in the order they found it.
+    winners: Vec<SessionWinner>,
+    /// In late solver mode, once the word of the current session was found: IDs of the other players of the session,
+    /// who can keep looking for it in private messages until they find it.
+    late_solvers: Option<HashSet<i64>>,
+    /// Word models, which may still be loading.
+    models: WordModels,
+    /// Where to send game events, to all the subscribers.
+    events: broadcast::Sender<GameEvent>,
+    /// How the words to find are picked.
+    target: TargetSettings,
+    /// Looks up the definitions of the words to find, if configured.
+    definitions: Option<DefinitionFetcher>,
+    /// Definition of the word to find, if it's known.
+    definition: Option<String>,
+    /// Signaled when a session starts or ends, to reschedule the expiry of the session.
+    session_changed: Arc<Notify>,
+    /// Signaled when a tournament starts, to schedule its announcements.
+    tournament_changed: Arc<Notify>,
+    /// Points given to the winners.
+    scoring: ScoringConfig,
+    /// Where to announce the achievements unlocked by the players, if anywhere.
+    notices: Option<UnboundedSender<SessionNotice>>,
+    /// Guesses made in the current session: guess -> nick of the player who made it first, distance.
+    guesses: HashMap<String, (String, f32)>,
+    /// Distance of the best guess of each player of the current session, by player ID.
+    best_guesses: HashMap<i64, f32>,
+    /// Practice games in progress, by nick.
+    practice: HashMap<String, PracticeGame>,
+    /// Reverse games in progress, by nick.
+    reverse: HashMap<String, ReverseGame>,
+    /// Vote in progress to give up a session: session ID, start of the vote and player IDs of the voters.
+    giveup_vote: Option<(i64, Instant, HashSet<i64>)>,
 }
 
-impl GameState {
-    pub fn load(mut conn: rusqlite::Connection, words: Arc<Words>) -> Result<GameState> {
-        setup_schema(&mut conn)?;
-        // language=SQLITE-SQL
-        let session_id: Option<i64> =
-            conn.query_row(r#"SELECT session_id FROM current_session WHERE id=0"#, [], |row| {
-                row.get(0)
-            })?;
-
-        if let Some(session_id) = session_id {
-            let (planned_end_date, word): (u64, String) = conn.query_row(
-                "SELECT planned_end_date, word FROM sessions WHERE id=?1",
-                [session_id],
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            )?;
-            let end_date = UNIX_EPOCH.checked_add(Duration::from_secs(planned_end_date)).unwrap();
+/// Nick given to a player who asked to be forgotten.
+pub fn anonymous_nick(player_id: i64) -> String {
+    format!("forgotten-{}", player_id)
+}
+
+/// Formats a duration in seconds as hours and minutes, e.g. `1h05` or `12min`.
+fn format_elapsed(seconds: i64) -> String {
+    let minutes = seconds.max(0) / 60;
+    if minutes < 60 {
+        format!("{}min", minutes)
+    } else {
+        format!("{}h{:02}", minutes / 60, minutes % 60)
+    }
+}
+
+/// Runs lookups in a word model. If the model may block on network requests, the other tasks of the runtime are
+/// handed over to another thread in the meantime; otherwise, the lookups run in place.
+fn lookup<T>(words: &dyn EmbeddingBackend, f: impl FnOnce() -> T) -> T {
+    if words.may_block() {
+        block_in_place(f)
+    } else {
+        f()
+    }
+}
+
+/// Returns the IDs of the players of a session who didn't find its word.
+async fn late_solvers(storage: &mut dyn Storage, session_id: i64, winners: &[SessionWinner]) -> Result<HashSet<i64>> {
+    Ok(storage
+        .guess_counts(session_id)
+        .await?
+        .into_iter()
+        .map(|(player_id, _)| player_id)
+        .filter(|&player_id| !winners.iter().any(|winner| winner.player_id == player_id))
+        .collect())
+}
+
+/// Returns the definition of a word from the database, or looks it up and stores it. Returns `None` if there's no
+/// way to look up definitions or if none was found.
+async fn cached_definition(
+    storage: &mut dyn Storage,
+    definitions: Option<&DefinitionFetcher>,
+    word: &str,
+) -> Option<String> {
+    let definitions = definitions?;
+    match storage.definition(word).await {
+        Ok(Some(definition)) => return Some(definition),
+        Ok(None) => {}
+        Err(err) => warn!("could not read the definition of \"{}\": {}", word, err),
+    }
+    let definition = block_in_place(|| definitions.fetch(word))?;
+    if let Err(err) = storage.insert_definition(word, &definition).await {
+        warn!("could not store the definition of \"{}\": {}", word, err);
+    }
+    Some(definition)
+}
 
+impl GameState {
+    pub async fn load(
+        mut storage: Box<dyn Storage>,
+        models: WordModels,
+        target: TargetSettings,
+        definitions: Option<DefinitionFetcher>,
+        scoring: ScoringConfig,
+        notices: Option<UnboundedSender<SessionNotice>>,
+    ) -> Result<GameState> {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        if let Some(session) = storage.current_session().await? {
             info!(
                 "loaded game session id = {}, the word to find is \"{}\"",
-                session_id, word
+                session.id, session.word
             );
+            let definition = cached_definition(storage.as_mut(), definitions.as_ref(), &session.word).await;
+            let history = storage.guess_history(session.id).await?;
+            let guess_count = history.len();
+            let mut guesses = HashMap::new();
+            for (guess, nick, distance) in history {
+                guesses.entry(guess).or_insert((nick, distance));
+            }
+            let mut best_guesses = HashMap::new();
+            for entry in storage.best_guesses(session.id).await? {
+                if let Some(player_id) = storage.find_player(&entry.nick).await? {
+                    best_guesses.insert(player_id, entry.similarity);
+                }
+            }
+            let winners = storage.session_winners(session.id).await?;
+            let late_solvers = if scoring.late_solvers && !winners.is_empty() {
+                Some(late_solvers(storage.as_mut(), session.id, &winners).await?)
+            } else {
+                None
+            };
             Ok(GameState {
-                conn,
-                session_id: Some(session_id),
-                word,
-                words,
+                storage,
+                session_id: Some(session.id),
+                last_session_id: None,
+                word: session.word,
+                start_date: session.start_date,
+                planned_end_date: session.planned_end_date,
+                metric: session.metric,
+                mode: session.mode,
+                difficulty: session.difficulty,
+                rank_table: None,
+                neighbor_table: None,
+                language: session.language,
+                strict: session.strict,
+                budget: session.budget,
+                guess_count,
+                hints: session.hints,
+                theme: session.theme,
+                winners,
+                late_solvers,
+                models,
+                events,
+                target,
+                definitions,
+                definition,
+                session_changed: Arc::new(Notify::new()),
+                tournament_changed: Arc::new(Notify::new()),
+                scoring,
+                notices,
+                guesses,
+                best_guesses,
+                practice: HashMap::new(),
+                reverse: HashMap::new(),
+                giveup_vote: None,
             })
         } else {
             Ok(GameState {
-                conn,
+                storage,
                 session_id: None,
+                last_session_id: None,
                 word: "".to_string(),
-                words,
+                start_date: 0,
+                planned_end_date: 0,
+                metric: Metric::default(),
+                mode: GameMode::default(),
+                difficulty: None,
+                rank_table: None,
+                neighbor_table: None,
+                language: None,
+                strict: false,
+                budget: None,
+                guess_count: 0,
+                hints: 0,
+                theme: None,
+                winners: Vec::new(),
+                late_solvers: None,
+                models,
+                events,
+                target,
+                definitions,
+                definition: None,
+                session_changed: Arc::new(Notify::new()),
+                tournament_changed: Arc::new(Notify::new()),
+                scoring,
+                notices,
+                guesses: HashMap::new(),
+                best_guesses: HashMap::new(),
+                practice: HashMap::new(),
+                reverse: HashMap::new(),
+                giveup_vote: None,
             })
         }
     }
 
-    /// Records a guess into the DB.
-    pub fn insert_guess(&mut self, session_id: i64, guess: String, player_id: i64, cosine: f32) -> Result<()> {
-        self.conn.execute(
-            r#"INSERT INTO guesses(session_id, player_id, guess, cosine) VALUES (?1,?2,?3,?4)"#,
-            params![session_id, player_id, guess, cosine],
-        )?;
-        Ok(())
+    /// Returns the word model of the current session, or an error if it's still loading.
+    fn words(&self) -> Result<Arc<dyn EmbeddingBackend>> {
+        Ok(self.models.get(self.language.as_deref())?.get()?.clone())
     }
 
-    /// Processes a guess from a player
-    pub fn process_guess(&mut self, player_nick: String, guess: String) -> Result<Outcome> {
-        // return early if there's no game in progress
-        let session_id = if let Some(id) = self.session_id {
-            id
-        } else {
-            bail!("there's no game in progress");
-        };
-
-        // query or insert player ID
-        // language=SQLITE-SQL
-        let player_id: Option<i64> = self
-            .conn
-            .query_row("SELECT id FROM players WHERE nick==?1", [&player_nick], |row| {
-                row.get(0)
-            })
-            .optional()?;
-        let player_id = if let Some(id) = player_id {
-            id
-        } else {
-            // unknown player, insert into players
-            // language=SQLITE-SQL
-            self.conn.execute(
-                "INSERT INTO players(nick, score) VALUES (?1,?2);",
-                params![&player_nick, 0i64],
-            )?;
-            self.conn.last_insert_rowid()
-        };
+    /// Rank of a guess with the specified similarity to the word to find (rank mode).
+    fn rank(&mut self, similarity: f32) -> Result<usize> {
+        let words = self.words()?;
+        if self.rank_table.is_none() {
+            let table = block_in_place(|| words.rank_table(&self.word, self.metric))
+                .ok_or_else(|| anyhow!("could not find target word in vocabulary: this is a bug"))?;
+            self.rank_table = Some(table);
+        }
+        let table = self.rank_table.as_ref().unwrap();
+        Ok(lookup(words.as_ref(), || words.rank(table, similarity)))
+    }
 
-        // cleanup guess
-        let guess = guess.trim().to_lowercase();
+    /// Returns the neighbor table of the word to find, computing it if necessary.
+    fn neighbor_table(&mut self) -> Result<&NeighborTable> {
+        if self.neighbor_table.is_none() {
+            let words = self.words()?;
+            let table = block_in_place(|| words.neighbor_table(&self.word, NEAREST_RANKS.max(HINT_RANK), self.metric))
+                .ok_or_else(|| anyhow!("could not find target word in vocabulary: this is a bug"))?;
+            self.neighbor_table = Some(table);
+        }
+        Ok(self.neighbor_table.as_ref().unwrap())
+    }
 
-        // fetch guess vector
-        let v_guess: &[f32] = if let Some(vec) = self.words.vector(&guess) {
-            vec
-        } else {
-            // unknown word
-            return Ok(Outcome::UnknownWord);
-        };
+    /// Rank of a guess with the specified similarity among the nearest neighbors of the word to find
+    /// (semantic mode). Returns `None` if the guess is further than all of them.
+    fn nearest_rank(&mut self, similarity: f32) -> Result<Option<usize>> {
+        let neighbors = &self.neighbor_table()?.neighbors;
+        let nearest = &neighbors[..neighbors.len().min(NEAREST_RANKS)];
+        let rank = nearest.partition_point(|&(_, s)| s > similarity);
+        Ok((rank < nearest.len()).then_some(rank + 1))
+    }
 
-        // calculate cosine distance
-        let distance = if guess == self.word {
-            1.0f32
-        } else {
-            let v_target = self.words.vector(&self.word).ok_or(anyhow::Error::msg(
-                "could not find target word in vocabulary: this is a bug",
-            ))?;
-            v_guess.iter().zip(v_target.iter()).map(|(&a, &b)| a * b).sum()
-        };
+    /// Sends an event to the subscribers, if there are any.
+    fn emit(&self, event: GameEvent) {
+        // having no subscribers is not an error
+        let _ = self.events.send(event);
+    }
 
-        // record the guess
-        self.insert_guess(session_id, guess.clone(), player_id, distance)?;
+    /// Records the achievements unlocked by a player, and announces the ones they didn't have yet. Failures are
+    /// only logged, as achievements are not essential to the game.
+    async fn unlock_achievements(&mut self, player_id: i64, nick: &str, event: &achievements::Event) {
+        let now = Utc::now().timestamp();
+        for achievement in achievements::unlocked(event) {
+            match self.storage.unlock_achievement(player_id, achievement, now).await {
+                Ok(true) => {
+                    info!("{} unlocked the achievement {}", nick, achievement.as_str());
+                    if let Some(ref notices) = self.notices {
+                        let _ = notices.send(SessionNotice::AchievementUnlocked {
+                            nick: nick.to_string(),
+                            achievement,
+                        });
+                    }
+                }
+                Ok(false) => {}
+                Err(err) => warn!("could not record an achievement of {}: {}", nick, err),
+            }
+        }
+    }
 
-        if distance == 1.0 {
-            // player won, end the game
-            self.end_game(Some(player_id));
-            Ok(Outcome::Win)
-        } else {
-            // not a win
-            Ok(Outcome::Miss { distance })
+    /// Checks the achievements unlocked by the players of a session that just ended. `guess_counts` is the number
+    /// of guesses of each player of the session.
+    async fn session_achievements(&mut self, guess_counts: &HashMap<i64, usize>, winner_ids: &[i64]) -> Result<()> {
+        let nicks: HashMap<i64, String> = self
+            .storage
+            .fetch_players()
+            .await?
+            .into_iter()
+            .map(|player| (player.id, player.nick))
+            .collect();
+        for (&player_id, &guesses) in guess_counts {
+            let days_in_a_row = achievements::days_in_a_row(&self.storage.play_dates(player_id).await?);
+            let event = achievements::Event::SessionEnded {
+                won: winner_ids.contains(&player_id),
+                guesses,
+                days_in_a_row,
+            };
+            if let Some(nick) = nicks.get(&player_id) {
+                self.unlock_achievements(player_id, nick, &event).await;
+            }
         }
+        Ok(())
     }
 
-    fn end_game(&mut self, winner_id: Option<i64>) -> Result<()> {
-        if let Some(session_id) = self.session_id {
-            let actual_end_time = SystemTime::now();
-            let actual_end_time_unix = actual_end_time.duration_since(UNIX_EPOCH).unwrap().as_secs();
-            if let Some(winner_id) = winner_id {
-                // language=SQLITE-SQL
-                self.conn.execute(
-                    r#"
-                UPDATE sessions SET end_date=?1, winner=?2 WHERE id=?3;
-                UPDATE current_session SET session_id=NULL WHERE id=0;
-                "#,
-                    params![actual_end_time_unix, winner_id, session_id],
-                )?;
-            } else {
-                // language=SQLITE-SQL
-                self.conn.execute(
-                    r#"
-                UPDATE sessions SET end_date=?1 WHERE id=?2;
-                UPDATE current_session SET session_id=NULL WHERE id=0;
-                "#,
-                    params![actual_end_time_unix, session_id],
-                )?;
+    /// Returns the session in progress, if there is one.
+    pub fn current_session(&self) -> Option<Session> {
+        self.session_id.map(|id| Session {
+            id,
+            word: self.word.clone(),
+            start_date: self.start_date,
+            planned_end_date: self.planned_end_date,
+            metric: self.metric,
+            mode: self.mode,
+            difficulty: self.difficulty,
+            language: self.language.clone(),
+            strict: self.strict,
+            budget: self.budget,
+            hints: self.hints,
+            theme: self.theme.clone(),
+        })
+    }
+
+    /// Returns the ID of the session in progress, or else of the last session that ended since the game was loaded.
+    pub fn recent_session_id(&self) -> Option<i64> {
+        self.session_id.or(self.last_session_id)
+    }
+
+    /// Returns the best guess of each player of the current session, or of the last one if there's no game in
+    /// progress, the closest first. While the session is in progress, the players who found the word are left out,
+    /// so that the others can keep looking for it.
+    pub async fn session_leaderboard(&mut self) -> Result<Vec<LeaderboardEntry>> {
+        let session_id = self
+            .recent_session_id()
+            .ok_or_else(|| anyhow!("there's no game in progress"))?;
+        let mut entries = self.storage.best_guesses(session_id).await?;
+        if self.session_id == Some(session_id) {
+            entries.retain(|entry| entry.guess != self.word);
+        }
+        for entry in &mut entries {
+            entry.rank = match self.mode {
+                GameMode::Semantic | GameMode::Blitz => self.nearest_rank(entry.similarity)?,
+                GameMode::Rank => Some(self.rank(entry.similarity)?),
+                GameMode::Wordle => None,
+            };
+            if self.mode != GameMode::Wordle {
+                entry.heat = Some(self.neighbor_table()?.heat(entry.similarity));
             }
-            self.session_id = None;
-            Ok(())
-        } else {
-            bail!("there's no game in progress");
         }
+        Ok(entries)
     }
 
-    /// If a game is in progress, ends it with no winner. Then, picks a random word from the dictionary and starts a new game.
-    pub fn start_game(&mut self, game_duration: Duration) -> Result<()> {
-        if self.session_id.is_some() {
-            self.end_game(None)?;
+    /// Returns the last `limit` guesses of a session, the most recent first. The word of the session in progress is
+    /// left out.
+    pub async fn recent_guesses(&mut self, session_id: i64, limit: usize) -> Result<Vec<GuessRecord>> {
+        if self.session_id != Some(session_id) {
+            return self.storage.recent_guesses(session_id, limit).await;
         }
+        let mut guesses = self
+            .storage
+            .recent_guesses(session_id, limit + self.winners.len())
+            .await?;
+        guesses.retain(|guess| guess.guess != self.word);
+        guesses.truncate(limit);
+        Ok(guesses)
+    }
 
-        // pick a word from the dictionary
-        let word = self.words.pick_word();
-        let start_time = SystemTime::now();
-        let end_time = start_time.checked_add(game_duration).unwrap();
-        let start_time_unix = start_time.duration_since(UNIX_EPOCH).unwrap().as_secs();
+    /// Number of hints available in the current session: the length of the word, its first letter, a word close to
+    /// it, a few words of its category and its definition, if it's known.
+    fn hint_count(&self) -> usize {
+        if self.definition.is_some() {
+            5
+        } else {
+            4
+        }
+    }
+
+    /// Returns the next hint about the word to find, each one stronger than the previous ones and lowering the
+    /// points of the winner. Once all the hints were given, returns the last one again, at no cost.
+    pub async fn hint(&mut self) -> Result<Hint> {
+        let session_id = self.session_id.ok_or_else(|| anyhow!("there's no game in progress"))?;
+        let count = self.hint_count();
+        let number = (self.hints + 1).min(count);
+        let text = match number {
+            1 => format!("the word has {} letters", self.word.chars().count()),
+            2 => format!("the word starts with \"{}\"", self.word.chars().next().unwrap_or_default()),
+            3 => {
+                let neighbors = &self.neighbor_table()?.neighbors;
+                let (word, similarity) = neighbors[..neighbors.len().min(HINT_RANK)]
+                    .last()
+                    .cloned()
+                    .ok_or_else(|| anyhow!("the word has no neighbors"))?;
+                let hint_rank = neighbors.len().min(HINT_RANK);
+                // in rank mode, use the same ranks as the guesses
+                let rank = if self.mode == GameMode::Rank {
+                    self.rank(similarity)?
+                } else {
+                    hint_rank
+                };
+                format!("a word near rank {} is \"{}\"", rank, word)
+            }
+            4 => {
+                let neighbors = &self.neighbor_table()?.neighbors;
+                let category: Vec<&str> = CATEGORY_RANKS
+                    .iter()
+                    .filter_map(|&rank| neighbors.get(rank - 1))
+                    .map(|(word, _)| word.as_str())
+                    .collect();
+                if category.is_empty() {
+                    bail!("the word has no neighbors");
+                }
+                format!("the word goes with \"{}\"", category.join("\", \""))
+            }
+            _ => format!("definition: {}", self.definition.as_deref().unwrap_or_default()),
+        };
+        if number > self.hints {
+            self.storage.set_session_hints(session_id, number).await?;
+            self.hints = number;
+        }
+        self.emit(GameEvent::Hinted {
+            session_id,
+            number,
+            hint: text.clone(),
+        });
+        Ok(Hint {
+            text,
+            number,
+            count,
+            penalty: (self.scoring.hint_penalty * self.hints as f64).round() as i64,
+            theme: self.theme.clone(),
+        })
+    }
+
+    /// Processes a guess from a player
+    pub async fn process_guess(&mut self, player_nick: String, guess: String) -> Result<Outcome> {
+        // return early if there's no game in progress
+        let session_id = if let Some(id) = self.session_id {
+            id
+        } else {
+            bail!("there's no game in progress");
+        };
+        let words = self.words()?;
+
+        // query or insert player ID
+        let player_id = self.storage.player_id(&player_nick).await?;
+        if self.winners.iter().any(|winner| winner.player_id == player_id) {
+            return Ok(Outcome::AlreadyFound);
+        }
+        if self.late_solvers.as_ref().is_some_and(|late_solvers| !late_solvers.contains(&player_id)) {
+            bail!("the word was already found, only the players of this game can keep looking for it");
+        }
+
+        let (guess, rejected) = lookup(words.as_ref(), || {
+            let guess = words.normalize_guess(&guess);
+            let guess = words.resolve_guess(&guess).to_string();
+            let rejected = words.is_rejected_stop_word(&guess);
+            (guess, rejected)
+        });
+
+        if rejected {
+            return Ok(Outcome::StopWord);
+        }
+
+        if self.mode == GameMode::Wordle {
+            return self
+                .process_wordle_guess(session_id, player_id, player_nick, guess)
+                .await;
+        }
+
+        // the word itself may be found by several players
+        if let Some((nick, distance)) = self.guesses.get(&guess).filter(|_| guess != self.word).cloned() {
+            let rank = if self.mode == GameMode::Rank {
+                Some(self.rank(distance)?)
+            } else {
+                None
+            };
+            return Ok(Outcome::AlreadyGuessed { nick, distance, rank });
+        }
+
+        // fetch guess vector
+        let v_guess = if let Some(vec) = lookup(words.as_ref(), || words.guess_vector(&guess))? {
+            vec
+        } else {
+            // unknown word
+            return Ok(Outcome::UnknownWord {
+                suggestions: lookup(words.as_ref(), || words.spell_suggest(&guess, MAX_SUGGESTIONS)),
+            });
+        };
+
+        // calculate similarity
+        let v_target = lookup(words.as_ref(), || words.vector(&self.word)).ok_or(anyhow::Error::msg(
+            "could not find target word in vocabulary: this is a bug",
+        ))?;
+        let distance = self.metric.similarity(&v_guess, &v_target);
+        let won = guess == self.word;
+
+        // in hard mode, each guess of a player must be closer than their previous ones
+        let best = self.best_guesses.get(&player_id).copied();
+        if let Some(best) = best.filter(|&best| self.strict && !won && distance <= best) {
+            let rank = if self.mode == GameMode::Rank {
+                Some(self.rank(best)?)
+            } else {
+                None
+            };
+            return Ok(Outcome::NotCloser { best, rank });
+        }
+
+        // record the guess, credited to the team of the player
+        let team_id = self.storage.player_team(player_id).await?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        self.storage
+            .insert_guess(session_id, player_id, team_id, &guess, distance, now)
+            .await?;
+        self.guesses
+            .entry(guess.clone())
+            .or_insert_with(|| (player_nick.clone(), distance));
+        if best.is_none_or(|best| distance > best) {
+            self.best_guesses.insert(player_id, distance);
+        }
+        self.emit(GameEvent::GuessMade {
+            session_id,
+            nick: player_nick.clone(),
+            guess,
+            distance,
+        });
+
+        if !won {
+            let event = achievements::Event::Guess {
+                cosine: (self.metric == Metric::Cosine).then_some(distance),
+                mode: self.mode,
+            };
+            self.unlock_achievements(player_id, &player_nick, &event).await;
+        }
+
+        if let Some(outcome) = self.spend_guess(won).await? {
+            return Ok(outcome);
+        }
+
+        if won {
+            self.win(session_id, player_id, player_nick).await
+        } else if self.mode == GameMode::Rank {
+            Ok(Outcome::RankMiss {
+                rank: self.rank(distance)?,
+            })
+        } else {
+            // not a win
+            Ok(Outcome::Miss {
+                distance,
+                rank: self.nearest_rank(distance)?,
+                best,
+                heat: self.neighbor_table()?.heat(distance),
+            })
+        }
+    }
+
+    /// Counts a recorded guess against the budget of a cooperative session. Warns the players when few guesses are
+    /// left, and ends the session when the guess used the last one without finding the word.
+    async fn spend_guess(&mut self, won: bool) -> Result<Option<Outcome>> {
+        self.guess_count += 1;
+        let budget = match self.budget {
+            Some(budget) if !won => budget,
+            _ => return Ok(None),
+        };
+        let remaining = budget.saturating_sub(self.guess_count);
+        if remaining == 0 {
+            info!("no guesses left, the word was \"{}\"", self.word);
+            let word = self.word.clone();
+            let definition = self.definition.take();
+            self.end_game().await?;
+            return Ok(Some(Outcome::OutOfGuesses { word, definition }));
+        }
+        if BUDGET_WARNINGS.contains(&remaining) {
+            if let Some(ref notices) = self.notices {
+                let _ = notices.send(SessionNotice::GuessesLeft { remaining });
+            }
+        }
+        Ok(None)
+    }
+
+    /// Processes a guess in a Wordle session: the guess must be a known word of the right length.
+    async fn process_wordle_guess(
+        &mut self,
+        session_id: i64,
+        player_id: i64,
+        player_nick: String,
+        guess: String,
+    ) -> Result<Outcome> {
+        if guess.chars().count() != WORDLE_WORD_LENGTH {
+            return Ok(Outcome::WrongLength {
+                expected: WORDLE_WORD_LENGTH,
+            });
+        }
+        let words = self.words()?;
+        if !wordle::is_wordle_word(&guess) || lookup(words.as_ref(), || words.vector(&guess)).is_none() {
+            let suggestions = lookup(words.as_ref(), || words.spell_suggest(&guess, MAX_SUGGESTIONS))
+                .into_iter()
+                .filter(|word| wordle::is_wordle_word(word))
+                .collect();
+            return Ok(Outcome::UnknownWord { suggestions });
+        }
+
+        let hints = wordle::evaluate(&guess, &self.word);
+        let score = wordle::score(&hints);
+        let won = guess == self.word;
+
+        // record the guess, with the letter score in place of the similarity, credited to the team of the player
+        let team_id = self.storage.player_team(player_id).await?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        self.storage
+            .insert_guess(session_id, player_id, team_id, &guess, score, now)
+            .await?;
+        self.emit(GameEvent::GuessMade {
+            session_id,
+            nick: player_nick.clone(),
+            guess: guess.clone(),
+            distance: score,
+        });
+
+        if let Some(outcome) = self.spend_guess(won).await? {
+            return Ok(outcome);
+        }
+
+        if won {
+            self.win(session_id, player_id, player_nick).await
+        } else {
+            Ok(Outcome::WordleMiss { guess, hints })
+        }
+    }
+
+    /// Records that a player found the word, and ends the session if enough players found it.
+    async fn win(&mut self, session_id: i64, player_id: i64, nick: String) -> Result<Outcome> {
+        let guesses = self.storage.guess_count(session_id, player_id).await?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let place = self.winners.len();
+        let points = self.scoring.points(guesses, now - self.start_date, self.hints, place);
+        self.storage.add_winner(session_id, player_id, place, points, now).await?;
+        self.winners.push(SessionWinner {
+            player_id,
+            nick,
+            points,
+        });
+        let ended = if self.scoring.late_solvers {
+            // the other players of the session can keep looking for the word
+            let late_solvers = match self.late_solvers {
+                Some(ref mut late_solvers) => late_solvers,
+                None => {
+                    let late_solvers = late_solvers(self.storage.as_mut(), session_id, &self.winners).await?;
+                    self.late_solvers.insert(late_solvers)
+                }
+            };
+            late_solvers.remove(&player_id);
+            late_solvers.is_empty()
+        } else {
+            self.scoring.enough_winners(self.winners.len())
+        };
+        if !ended {
+            info!("{} found the word, place {}", self.winners[place].nick, place + 1);
+            let factor = if self.mode == GameMode::Blitz { BLITZ_POINTS_FACTOR } else { 1 };
+            return Ok(Outcome::Win {
+                points: points * factor,
+                definition: None,
+                place: place + 1,
+                ended: false,
+            });
+        }
+        // enough players found the word, end the game
+        let definition = self.definition.take();
+        let winners: Vec<String> = self.winners.iter().map(|winner| winner.nick.clone()).collect();
+        let points = self.end_game().await?;
+        if self.scoring.late_solvers && place > 0 {
+            // the channels didn't see the late solvers find the word
+            if let Some(ref notices) = self.notices {
+                let _ = notices.send(SessionNotice::Recap {
+                    word: self.word.clone(),
+                    definition: definition.clone(),
+                    winners,
+                });
+            }
+        }
+        Ok(Outcome::Win {
+            points,
+            definition,
+            place: place + 1,
+            ended: true,
+        })
+    }
+
+    /// Counts the vote of a player to give up the current session, and ends it with no winner once `quorum` players
+    /// voted, or all the players of the session if there are fewer. Only the players who made a guess in the session
+    /// can vote.
+    pub async fn give_up(&mut self, nick: &str, quorum: usize) -> Result<GiveUpVote> {
+        let session_id = self.session_id.ok_or_else(|| anyhow!("there's no game in progress"))?;
+        // the players may guess with any of their nicks
+        let mut players = HashSet::new();
+        for entry in self.storage.best_guesses(session_id).await? {
+            if let Some(player_id) = self.storage.find_player(&entry.nick).await? {
+                players.insert(player_id);
+            }
+        }
+        let player_id = match self.storage.find_player(nick).await? {
+            Some(player_id) if players.contains(&player_id) => player_id,
+            _ => bail!("only the players of this game can vote to give up"),
+        };
+        let required = quorum.clamp(1, players.len());
+
+        let vote = match self.giveup_vote {
+            Some((id, started, ref mut voters)) if id == session_id && started.elapsed() < GIVEUP_VOTE_DURATION => {
+                voters
+            }
+            _ => {
+                debug!("{} started a vote to give up", nick);
+                &mut self.giveup_vote.insert((session_id, Instant::now(), HashSet::new())).2
+            }
+        };
+        if !vote.insert(player_id) {
+            return Ok(GiveUpVote::AlreadyVoted {
+                votes: vote.len(),
+                required,
+            });
+        }
+        let votes = vote.len();
+        if votes < required {
+            return Ok(GiveUpVote::Counted { votes, required });
+        }
+
+        info!("the players gave up, the word was \"{}\"", self.word);
+        self.giveup_vote = None;
+        let word = self.word.clone();
+        let definition = self.definition.take();
+        self.end_game().await?;
+        Ok(GiveUpVote::Ended { word, definition })
+    }
+
+    /// Starts a practice game for a player, with a word of the default model. Returns the word of the practice game
+    /// it replaces, if the player had one.
+    pub fn start_practice(&mut self, nick: &str) -> Result<Option<String>> {
+        let words = self.models.get(None)?.get()?.clone();
+        let metric = words.metric();
+        let current = self.session_id.map(|_| self.word.clone());
+        let level = self.target.difficulty_level;
+        let (word, neighbor_table) = block_in_place(|| {
+            let word = words
+                .pick_word_of_difficulty(&mut rand::thread_rng(), level, metric, &|word| {
+                    current.as_deref() != Some(word)
+                })
+                .unwrap_or_else(|| words.pick_word(&mut rand::thread_rng()));
+            let neighbor_table = words.neighbor_table(&word, NEAREST_RANKS, metric);
+            (word, neighbor_table)
+        });
+        let neighbor_table =
+            neighbor_table.ok_or_else(|| anyhow!("could not find target word in vocabulary: this is a bug"))?;
+        debug!("{} started a practice game", nick);
+        self.practice.retain(|_, game| !game.expired());
+        let game = PracticeGame {
+            word,
+            metric,
+            neighbor_table,
+            guesses: HashSet::new(),
+            best: None,
+            last_activity: Instant::now(),
+        };
+        Ok(self.practice.insert(nick.to_string(), game).map(|game| game.word))
+    }
+
+    /// Ends the practice game of a player. Returns its word, or `None` if the player had no practice game.
+    pub fn stop_practice(&mut self, nick: &str) -> Option<String> {
+        self.practice
+            .remove(nick)
+            .filter(|game| !game.expired())
+            .map(|game| game.word)
+    }
+
+    /// Processes a guess in the practice game of a player. A win ends the practice game, and gives no points.
+    pub async fn practice_guess(&mut self, nick: &str, guess: &str) -> Result<Outcome> {
+        let words = self.models.get(None)?.get()?.clone();
+        let game = self
+            .practice
+            .get_mut(nick)
+            .filter(|game| !game.expired())
+            .ok_or_else(|| anyhow!("you have no practice game in progress, start one with !practice"))?;
+        game.last_activity = Instant::now();
+
+        let (guess, rejected) = lookup(words.as_ref(), || {
+            let guess = words.normalize_guess(guess);
+            let guess = words.resolve_guess(&guess).to_string();
+            let rejected = words.is_rejected_stop_word(&guess);
+            (guess, rejected)
+        });
+        if rejected {
+            return Ok(Outcome::StopWord);
+        }
+        let v_guess = match lookup(words.as_ref(), || words.guess_vector(&guess))? {
+            Some(vec) => vec,
+            None => {
+                return Ok(Outcome::UnknownWord {
+                    suggestions: lookup(words.as_ref(), || words.spell_suggest(&guess, MAX_SUGGESTIONS)),
+                })
+            }
+        };
+        let v_target = lookup(words.as_ref(), || words.vector(&game.word)).ok_or(anyhow::Error::msg(
+            "could not find target word in vocabulary: this is a bug",
+        ))?;
+        let distance = game.metric.similarity(&v_guess, &v_target);
+
+        if guess == game.word {
+            debug!("{} won their practice game", nick);
+            self.practice.remove(nick);
+            let definition = cached_definition(self.storage.as_mut(), self.definitions.as_ref(), &guess).await;
+            return Ok(Outcome::Win {
+                points: 0,
+                definition,
+                place: 1,
+                ended: true,
+            });
+        }
+        if !game.guesses.insert(guess) {
+            return Ok(Outcome::AlreadyGuessed {
+                nick: nick.to_string(),
+                distance,
+                rank: None,
+            });
+        }
+        let best = game.best;
+        if best.is_none_or(|best| distance > best) {
+            game.best = Some(distance);
+        }
+        let table = &game.neighbor_table;
+        let rank = table.neighbors.partition_point(|&(_, s)| s > distance);
+        Ok(Outcome::Miss {
+            distance,
+            rank: (rank < table.neighbors.len()).then_some(rank + 1),
+            best,
+            heat: table.heat(distance),
+        })
+    }
+
+    /// Starts a reverse game for a player, who picked `word` in the default model, and returns the first guess of
+    /// the bot. Replaces the reverse game of the player, if they had one.
+    pub fn start_reverse(&mut self, nick: &str, word: &str) -> Result<ReverseGuess> {
+        let words = self.models.get(None)?.get()?.clone();
+        let word = words.normalize_guess(word);
+        if lookup(words.as_ref(), || words.vector(&word)).is_none() {
+            bail!("\"{}\" is not in the vocabulary", word);
+        }
+        debug!("{} started a reverse game", nick);
+        self.reverse.retain(|_, game| !game.expired());
+        let mut game = ReverseGame::new(word);
+        let guess = block_in_place(|| game.guess(words.as_ref(), &mut rand::thread_rng()));
+        if matches!(guess, ReverseGuess::Guess { .. }) {
+            self.reverse.insert(nick.to_string(), game);
+        } else {
+            self.reverse.remove(nick);
+        }
+        Ok(guess)
+    }
+
+    /// Ends the reverse game of a player. Returns `false` if the player had no reverse game.
+    pub fn stop_reverse(&mut self, nick: &str) -> bool {
+        self.reverse.remove(nick).is_some_and(|game| !game.expired())
+    }
+
+    /// Records the similarity a player reported for the last guess of the bot in their reverse game, and returns the
+    /// next guess of the bot.
+    pub fn reverse_report(&mut self, nick: &str, similarity: f32) -> Result<ReverseGuess> {
+        let words = self.models.get(None)?.get()?.clone();
+        let game = self
+            .reverse
+            .get_mut(nick)
+            .filter(|game| !game.expired())
+            .ok_or_else(|| anyhow!("you have no reverse game in progress, start one with !reverse <word>"))?;
+        game.report(similarity)?;
+        let guess = block_in_place(|| game.guess(words.as_ref(), &mut rand::thread_rng()));
+        if !matches!(guess, ReverseGuess::Guess { .. }) {
+            debug!("{}'s reverse game is over", nick);
+            self.reverse.remove(nick);
+        }
+        Ok(guess)
+    }
+
+    /// Points of the players of the current session for taking part and for their near misses, by player ID.
+    async fn consolation_points(&mut self, session_id: i64) -> Result<HashMap<i64, i64>> {
+        let guesses = self.storage.session_guesses(session_id).await?;
+        let near_misses: HashSet<String> = if self.mode == GameMode::Wordle || self.scoring.near_miss_points == 0 {
+            HashSet::new()
+        } else {
+            let rank = self.scoring.near_miss_rank;
+            match self.neighbor_table() {
+                Ok(table) => table.neighbors.iter().take(rank).map(|(word, _)| word.clone()).collect(),
+                Err(err) => {
+                    warn!("no points for near misses: {}", err);
+                    HashSet::new()
+                }
+            }
+        };
+
+        let mut players: HashMap<i64, HashSet<&str>> = HashMap::new();
+        for (player_id, guess) in &guesses {
+            let player_near_misses = players.entry(*player_id).or_default();
+            if near_misses.contains(guess) {
+                player_near_misses.insert(guess);
+            }
+        }
+        Ok(players
+            .into_iter()
+            .map(|(player_id, player_near_misses)| {
+                let near_miss_count = player_near_misses.len().min(self.scoring.max_near_misses) as i64;
+                let points = self.scoring.participation_points + self.scoring.near_miss_points * near_miss_count;
+                (player_id, points)
+            })
+            .collect())
+    }
+
+    /// Gives `team_win_points` to the players of a session who didn't find the word but played for the same team as
+    /// a winner.
+    async fn add_team_win_points(
+        &mut self,
+        session_id: i64,
+        winner_ids: &[i64],
+        points: &mut HashMap<i64, i64>,
+    ) -> Result<()> {
+        let teams = self.storage.session_teams(session_id).await?;
+        let winner_teams: HashSet<i64> = teams
+            .iter()
+            .filter(|(player_id, _)| winner_ids.contains(player_id))
+            .map(|&(_, team)| team)
+            .collect();
+        for &(player_id, team) in &teams {
+            if winner_teams.contains(&team) && !winner_ids.contains(&player_id) {
+                *points.entry(player_id).or_default() += self.scoring.team_win_points;
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes a player join a team. Team names are case-insensitive. Returns the name of the team.
+    pub async fn join_team(&mut self, nick: &str, team: &str) -> Result<String> {
+        let team = team.trim().to_lowercase();
+        if team.is_empty()
+            || team.chars().count() > MAX_TEAM_NAME_LENGTH
+            || !team.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!(
+                "team names must have at most {} letters, digits, `-` or `_`",
+                MAX_TEAM_NAME_LENGTH
+            );
+        }
+        let player_id = self.storage.player_id(nick).await?;
+        self.storage.join_team(player_id, &team).await?;
+        Ok(team)
+    }
+
+    /// Makes `alias` another nick of the player `nick`. Fails if `alias` is already the nick of another player, who
+    /// must be merged instead.
+    pub async fn add_alias(&mut self, nick: &str, alias: &str) -> Result<()> {
+        let player_id = match self.storage.find_player(nick).await? {
+            Some(id) => id,
+            None => bail!("{} has never played", nick),
+        };
+        match self.storage.find_player(alias).await? {
+            Some(id) if id == player_id => Ok(()),
+            Some(_) => bail!("{} is another player, use !merge {} {} instead", alias, alias, nick),
+            None => self.storage.add_alias(alias, player_id).await,
+        }
+    }
+
+    /// Returns the nicks a player used in the current session: the guesses and the wins are recorded in memory by
+    /// nick, and the nicks of a player may change when players are merged or forgotten.
+    async fn session_nicks(&mut self, player_id: i64) -> Result<HashSet<String>> {
+        let mut nicks: Vec<String> = self.guesses.values().map(|(nick, _)| nick.clone()).collect();
+        nicks.sort();
+        nicks.dedup();
+        let mut session_nicks: HashSet<String> = self
+            .winners
+            .iter()
+            .filter(|winner| winner.player_id == player_id)
+            .map(|winner| winner.nick.clone())
+            .collect();
+        for nick in nicks {
+            if self.storage.find_player(&nick).await? == Some(player_id) {
+                session_nicks.insert(nick);
+            }
+        }
+        Ok(session_nicks)
+    }
+
+    /// Merges the player `from` into the player `into`. See [`Storage::merge_players`].
+    ///
+    /// In the current session, the guesses, the best guess and the win of `from` also go to `into`.
+    pub async fn merge_players(&mut self, from: &str, into: &str) -> Result<()> {
+        let (from_id, into_id) = match (
+            self.storage.find_player(from).await?,
+            self.storage.find_player(into).await?,
+        ) {
+            (Some(from_id), Some(into_id)) => (from_id, into_id),
+            (None, _) => bail!("{} has never played", from),
+            (_, None) => bail!("{} has never played", into),
+        };
+        if from_id == into_id {
+            bail!("{} and {} are already the same player", from, into);
+        }
+        let from_nicks = self.session_nicks(from_id).await?;
+        self.storage.merge_players(from_id, into_id).await?;
+
+        let into_nick = into.to_string();
+        for (guesser, _) in self.guesses.values_mut() {
+            if from_nicks.contains(guesser) {
+                guesser.clone_from(&into_nick);
+            }
+        }
+        // in hard mode, the guesses of both must be closer than the best one
+        if let Some(best) = self.best_guesses.remove(&from_id) {
+            let into_best = self.best_guesses.entry(into_id).or_insert(best);
+            *into_best = into_best.max(best);
+        }
+        // like in the database, `into` keeps their own win if they both found the word
+        let into_won = self.winners.iter().any(|winner| winner.player_id == into_id);
+        self.winners.retain(|winner| !(into_won && winner.player_id == from_id));
+        for winner in &mut self.winners {
+            if winner.player_id == from_id {
+                winner.player_id = into_id;
+                winner.nick.clone_from(&into_nick);
+            }
+        }
+        if let Some(late_solvers) = &mut self.late_solvers {
+            let from_late = late_solvers.remove(&from_id);
+            let into_late = late_solvers.remove(&into_id);
+            if (from_late || into_late) && !self.winners.iter().any(|winner| winner.player_id == into_id) {
+                late_solvers.insert(into_id);
+            }
+        }
+        if let Some((_, _, voters)) = &mut self.giveup_vote {
+            if voters.remove(&from_id) {
+                voters.insert(into_id);
+            }
+        }
+        info!("merged player {} into {}", from, into);
+        Ok(())
+    }
+
+    /// Anonymizes a player who asked to be forgotten. Returns `false` if they never played.
+    ///
+    /// See [`Storage::forget_player`]. The nicks of the player are also replaced in the guesses and the winners of the
+    /// current session, and removed from the vote to give up, the practice games and the reverse games.
+    pub async fn forget_player(&mut self, nick: &str) -> Result<bool> {
+        let player_id = match self.storage.find_player(nick).await? {
+            Some(id) => id,
+            None => return Ok(false),
+        };
+        let anonymous = anonymous_nick(player_id);
+        let mut nicks = self.session_nicks(player_id).await?;
+        nicks.extend(self.storage.forget_player(player_id, &anonymous).await?);
+        for (guesser, _) in self.guesses.values_mut() {
+            if nicks.contains(guesser) {
+                guesser.clone_from(&anonymous);
+            }
+        }
+        for winner in &mut self.winners {
+            if winner.player_id == player_id {
+                winner.nick.clone_from(&anonymous);
+            }
+        }
+        self.best_guesses.remove(&player_id);
+        if let Some((_, _, voters)) = &mut self.giveup_vote {
+            voters.remove(&player_id);
+        }
+        for nick in &nicks {
+            self.practice.remove(nick);
+            self.reverse.remove(nick);
+        }
+        info!("player {} was forgotten", player_id);
+        Ok(true)
+    }
+
+    /// Returns the new ratings of the players of a session that is about to end. `guess_counts` is the number of
+    /// guesses of each player of the session, and `winner_ids` the players who found the word in the order they
+    /// found it.
+    async fn rate_session(
+        &mut self,
+        guess_counts: &HashMap<i64, usize>,
+        winner_ids: &[i64],
+    ) -> Result<Vec<(i64, f64)>> {
+        let ratings: HashMap<i64, f64> = self.storage.player_ratings().await?.into_iter().collect();
+        let average_guesses = self.storage.average_guesses_to_win().await?;
+        Ok(rating::rate_session(&ratings, guess_counts, winner_ids, average_guesses))
+    }
+
+    /// Ends the current session, with the players who found the word as winners. Returns the points given to the
+    /// last winner.
+    ///
+    /// The results are computed first and recorded in a single transaction, so the session is either still in
+    /// progress or ended with all its points and ratings if the bot stops in the middle.
+    async fn end_game(&mut self) -> Result<i64> {
+        if let Some(session_id) = self.session_id {
+            let actual_end_time = SystemTime::now();
+            let actual_end_time_unix = actual_end_time.duration_since(UNIX_EPOCH).unwrap().as_secs();
+            let winners = std::mem::take(&mut self.winners);
+            let winner_ids: Vec<i64> = winners.iter().map(|winner| winner.player_id).collect();
+            let mut points = self.consolation_points(session_id).await?;
+            for winner in &winners {
+                *points.entry(winner.player_id).or_default() += winner.points;
+            }
+            self.add_team_win_points(session_id, &winner_ids, &mut points).await?;
+            if self.mode == GameMode::Blitz {
+                points.values_mut().for_each(|points| *points *= BLITZ_POINTS_FACTOR);
+            }
+            let winner_points = match winners.last() {
+                Some(winner) => points.get(&winner.player_id).copied().unwrap_or_default(),
+                None => 0,
+            };
+            let points: Vec<(i64, i64)> = points.into_iter().filter(|&(_, points)| points != 0).collect();
+            // the first player who found the word is the winner of the session
+            let winner = winners.first();
+            let winner_id = winner.map(|winner| winner.player_id);
+            let guess_counts = rating::guess_counts(&mut *self.storage, session_id).await?;
+            let ratings = self.rate_session(&guess_counts, &winner_ids).await?;
+            let tournament_points = self.tournament_points(session_id, &winner_ids).await?;
+            let share = match winner {
+                Some(winner) => match self
+                    .share_text(session_id, &winner.nick, actual_end_time_unix as i64 - self.start_date)
+                    .await
+                {
+                    Ok(share) => Some(share),
+                    Err(err) => {
+                        warn!("could not generate the share text of session {}: {}", session_id, err);
+                        None
+                    }
+                },
+                None => None,
+            };
+            let result = SessionResult {
+                end_date: actual_end_time_unix as i64,
+                winner_id,
+                share,
+                points,
+                ratings,
+                tournament_points,
+            };
+            self.storage.end_session(session_id, &result).await?;
+            if let Err(err) = self.session_achievements(&guess_counts, &winner_ids).await {
+                warn!("could not check the achievements of session {}: {}", session_id, err);
+            }
+            self.session_id = None;
+            self.last_session_id = Some(session_id);
+            self.definition = None;
+            self.session_changed.notify_one();
+            self.emit(GameEvent::SessionEnded {
+                session_id,
+                word: self.word.clone(),
+                winner: winner.map(|winner| winner.nick.clone()),
+            });
+            Ok(winner_points)
+        } else {
+            bail!("there's no game in progress");
+        }
+    }
+
+    /// Tournament points of the players of a session that is about to end, if they're registered to the tournament
+    /// in progress, by their place among the registered players of the session.
+    async fn tournament_points(&mut self, session_id: i64, winner_ids: &[i64]) -> Result<Vec<(i64, i64)>> {
+        let now = Utc::now().timestamp();
+        let tournament = match self.storage.current_tournament().await? {
+            Some(tournament) if now < tournament.end_date => tournament,
+            _ => return Ok(Vec::new()),
+        };
+        let registered: HashSet<i64> = self
+            .storage
+            .tournament_standings(tournament.id)
+            .await?
+            .into_iter()
+            .map(|player| player.id)
+            .collect();
+        // the winners come first in the order they found the word, then the best guesses
+        let mut places: Vec<i64> = winner_ids.iter().copied().filter(|id| registered.contains(id)).collect();
+        for entry in self.storage.best_guesses(session_id).await? {
+            if let Some(player_id) = self.storage.find_player(&entry.nick).await? {
+                if registered.contains(&player_id) && !places.contains(&player_id) {
+                    places.push(player_id);
+                }
+            }
+        }
+        Ok(places
+            .into_iter()
+            .zip(&self.scoring.tournament_points)
+            .filter(|&(_, &points)| points != 0)
+            .map(|(player_id, &points)| (player_id, points))
+            .collect())
+    }
+
+    /// Starts a tournament of `days` days. Fails if there's already one in progress.
+    pub async fn start_tournament(&mut self, name: Option<&str>, days: u64) -> Result<Tournament> {
+        if let Some(tournament) = self.storage.current_tournament().await? {
+            bail!("the tournament \"{}\" is already in progress", tournament.name);
+        }
+        if days == 0 {
+            bail!("a tournament lasts at least one day");
+        }
+        let now = Utc::now();
+        let name = match name.map(str::trim) {
+            Some(name) if name.chars().count() > MAX_TOURNAMENT_NAME_LENGTH => {
+                bail!("tournament names have at most {} characters", MAX_TOURNAMENT_NAME_LENGTH)
+            }
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => format!("tournament of {}", now.format("%Y-%m-%d")),
+        };
+        let start_date = now.timestamp();
+        let end_date = start_date + days as i64 * 24 * 3600;
+        let id = self.storage.start_tournament(&name, start_date, end_date).await?;
+        self.tournament_changed.notify_one();
+        info!("tournament {} started", id);
+        Ok(Tournament {
+            id,
+            name,
+            start_date,
+            end_date,
+        })
+    }
+
+    /// Registers a player to the tournament in progress. Returns the tournament, and `false` if the player was
+    /// already registered.
+    pub async fn join_tournament(&mut self, nick: &str) -> Result<(Tournament, bool)> {
+        let tournament = match self.storage.current_tournament().await? {
+            Some(tournament) if Utc::now().timestamp() < tournament.end_date => tournament,
+            _ => bail!("there's no tournament in progress"),
+        };
+        let player_id = self.storage.player_id(nick).await?;
+        let joined = self.storage.join_tournament(tournament.id, player_id).await?;
+        Ok((tournament, joined))
+    }
+
+    /// Ends a tournament. Returns its final standings.
+    async fn end_tournament(&mut self, tournament: &Tournament) -> Result<Vec<Player>> {
+        let standings = self.storage.tournament_standings(tournament.id).await?;
+        let winner_id = standings.first().filter(|player| player.score > 0).map(|player| player.id);
+        self.storage.end_tournament(tournament.id, winner_id).await?;
+        info!("tournament {} ended", tournament.id);
+        Ok(standings)
+    }
+
+    /// Ends the current season if it started before the season that contains the current date, and starts a
+    /// new one if needed. Returns the standings of the ended season.
+    async fn roll_season(&mut self, length: SeasonLength) -> Result<Option<Vec<Player>>> {
+        let start = length.season_start(Utc::now()).timestamp();
+        match self.storage.current_season().await? {
+            Some(season) if season.start_date >= start => Ok(None),
+            Some(season) => {
+                let standings = self.storage.season_scores(season.id).await?;
+                let winner_id = standings.first().map(|player| player.id);
+                self.storage.end_season(season.id, start, winner_id).await?;
+                self.storage.start_season(start).await?;
+                info!("season {} ended, a new season has started", season.id);
+                Ok(Some(standings))
+            }
+            None => {
+                self.storage.start_season(start).await?;
+                info!("first season started");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Ends the current session with no winner if its planned end date is past. Returns the ended session.
+    async fn expire(&mut self) -> Result<Option<SessionNotice>> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        match self.session_id {
+            Some(session_id) if now >= self.planned_end_date => {
+                let definition = self.definition.clone();
+                let winners: Vec<String> = self.winners.iter().map(|winner| winner.nick.clone()).collect();
+                self.end_game().await?;
+                info!("session {} ran out of time, the word was \"{}\"", session_id, self.word);
+                if !winners.is_empty() {
+                    return Ok(Some(SessionNotice::Recap {
+                        word: self.word.clone(),
+                        definition,
+                        winners,
+                    }));
+                }
+                Ok(Some(SessionNotice::Expired {
+                    word: self.word.clone(),
+                    definition,
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns a text without spoilers that the winner of a session can share: the number of guesses they needed,
+    /// how long the session lasted, and the heat of each of their guesses. `elapsed` is the duration of the session
+    /// in seconds.
+    async fn share_text(&mut self, session_id: i64, nick: &str, elapsed: i64) -> Result<String> {
+        let distances: Vec<f32> = self
+            .storage
+            .guess_history(session_id)
+            .await?
+            .into_iter()
+            .filter(|(_, guesser, _)| guesser == nick)
+            .map(|(_, _, distance)| distance)
+            .collect();
+        // the letter score of Wordle guesses is already between 0 and 1
+        let heats: Vec<f32> = if self.mode == GameMode::Wordle {
+            distances.clone()
+        } else {
+            let table = self.neighbor_table()?;
+            distances.iter().map(|&distance| table.heat(distance)).collect()
+        };
+        let skipped = heats.len().saturating_sub(MAX_SHARE_SQUARES);
+        let squares: String = heats[skipped..].iter().map(|&h| heat::emoji_square(h)).collect();
+        Ok(format!(
+            "Cabotin #{}: found in {} {}, {} {}{}",
+            session_id,
+            distances.len(),
+            if distances.len() == 1 { "guess" } else { "guesses" },
+            format_elapsed(elapsed),
+            if skipped > 0 { "…" } else { "" },
+            squares
+        ))
+    }
+
+    /// Ends the current session with no winner, and returns its word and leaderboard.
+    async fn end_and_reveal(&mut self) -> Result<EndedSession> {
+        let session_id = self.session_id.ok_or_else(|| anyhow!("there's no game in progress"))?;
+        // the ranks are computed with the word of the session
+        let leaderboard = self.session_leaderboard().await?;
+        let definition = self.definition.clone();
+        self.end_game().await?;
+        info!("session {} was ended, the word was \"{}\"", session_id, self.word);
+        Ok(EndedSession {
+            word: self.word.clone(),
+            definition,
+            leaderboard,
+        })
+    }
+
+    /// If a game is in progress, ends it with no winner. Then, picks a random word from the dictionary and starts a new game.
+    ///
+    /// Blitz sessions can't run alongside another game yet, so they are refused while a game of another mode is in
+    /// progress.
+    pub async fn start_game(&mut self, game_duration: Duration, options: SessionOptions) -> Result<()> {
+        if options.mode == GameMode::Blitz && self.session_id.is_some() && self.mode != GameMode::Blitz {
+            bail!("a game is already in progress, a blitz can only start once it's over");
+        }
+        // pick a word from the dictionary
+        let words = self.models.get(options.language.as_deref())?.get()?.clone();
+        // avoid the words of the last sessions, unless there's nothing else
+        let recent: HashSet<String> = self
+            .storage
+            .recent_words(self.target.recent_words_window)
+            .await?
+            .into_iter()
+            .collect();
+        let metric = options.metric.unwrap_or(words.metric());
+        let (game_duration, default_level) = match options.mode {
+            GameMode::Blitz => (BLITZ_DURATION, DifficultyLevel::Easy),
+            _ => (game_duration, self.target.difficulty_level),
+        };
+        let level = options.difficulty.unwrap_or(default_level);
+        let start_time = SystemTime::now();
+        let end_time = start_time.checked_add(game_duration).unwrap();
+        let start_time_unix = start_time.duration_since(UNIX_EPOCH).unwrap().as_secs();
         let end_time_unix = end_time.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let mut rng = self.target.rng.session_rng(start_time_unix);
+        let theme = options.theme.as_deref().map(str::to_lowercase);
+        // picking a word scans the vocabulary
+        let word = block_in_place(|| -> Result<String> {
+            let theme_words = match theme {
+                Some(ref theme) => Some(self.target.theme_words(words.as_ref(), theme, metric)?),
+                None => None,
+            };
+            let in_theme = |word: &str| theme_words.as_ref().is_none_or(|theme_words| theme_words.contains(word));
+            let no_word = || {
+                let theme = theme.as_deref().unwrap_or_default();
+                anyhow!("there are no words to find in the theme \"{}\"", theme)
+            };
+            Ok(match options.word {
+                Some(ref word) => {
+                    let word = words.normalize_guess(word);
+                    if words.vector(&word).is_none() {
+                        bail!("\"{}\" is not in the vocabulary", word);
+                    }
+                    if options.mode == GameMode::Wordle && !wordle::is_wordle_word(&word) {
+                        bail!("the word of a Wordle session must have {} letters", WORDLE_WORD_LENGTH);
+                    }
+                    word
+                }
+                None => match options.mode {
+                    GameMode::Semantic | GameMode::Rank | GameMode::Blitz => {
+                        let mut pick = || {
+                            words
+                                .pick_word_of_difficulty(&mut rng, level, metric, &|word| {
+                                    in_theme(word) && !recent.contains(word)
+                                })
+                                .or_else(|| match theme_words {
+                                    Some(_) => words.pick_word_where(&mut rng, &in_theme),
+                                    None => Some(words.pick_word(&mut rng)),
+                                })
+                                .ok_or_else(no_word)
+                        };
+                        // words with a sparse neighborhood are rerolled
+                        let mut word = pick()?;
+                        for _ in 1..MAX_TARGET_ATTEMPTS {
+                            if self.target.has_close_neighbors(words.as_ref(), &word, metric) {
+                                break;
+                            }
+                            debug!("\"{}\" has too few close neighbors, picking another word", word);
+                            word = pick()?;
+                        }
+                        word
+                    }
+                    GameMode::Wordle => words
+                        .pick_word_of_difficulty(&mut rng, level, metric, &|word| {
+                            wordle::is_wordle_word(word) && in_theme(word) && !recent.contains(word)
+                        })
+                        .or_else(|| {
+                            words.pick_word_where(&mut rng, &|word| wordle::is_wordle_word(word) && in_theme(word))
+                        })
+                        .ok_or_else(|| match theme {
+                            Some(_) => no_word(),
+                            None => anyhow!("there are no {}-letter words in the vocabulary", WORDLE_WORD_LENGTH),
+                        })?,
+                },
+            })
+        })?;
 
-        // start session
-        // language=SQLITE-SQL
-        self.conn.execute(
-            "INSERT INTO sessions(start_date, planned_end_date, word) VALUES (?1,?2,?3);",
-            params![start_time_unix, end_time_unix, word.clone()],
-        )?;
-        let session_id = self.conn.last_insert_rowid();
-        // language=SQLITE-SQL
-        self.conn.execute(
-            "UPDATE current_session SET session_id=?1 WHERE id=0;",
-            params![session_id],
-        )?;
+        if self.session_id.is_some() {
+            self.end_game().await?;
+        }
 
+        // start session
+        let difficulty = block_in_place(|| words.difficulty(&word, metric));
+        let mut session = Session {
+            id: 0,
+            word,
+            start_date: start_time_unix as i64,
+            planned_end_date: end_time_unix as i64,
+            metric,
+            mode: options.mode,
+            difficulty,
+            language: options.language,
+            strict: options.strict,
+            budget: options.budget,
+            hints: 0,
+            theme,
+        };
+        let session_id = self.storage.start_session(&session).await?;
+        session.id = session_id;
+        self.word = session.word;
         self.session_id = Some(session_id);
+        self.start_date = session.start_date;
+        self.planned_end_date = session.planned_end_date;
+        self.metric = session.metric;
+        self.mode = session.mode;
+        self.difficulty = session.difficulty;
+        self.language = session.language;
+        self.strict = session.strict;
+        self.budget = session.budget;
+        self.theme = session.theme;
+        self.guess_count = 0;
+        self.hints = 0;
+        self.winners.clear();
+        self.late_solvers = None;
+        self.definition = cached_definition(self.storage.as_mut(), self.definitions.as_ref(), &self.word).await;
+        self.rank_table = None;
+        self.neighbor_table = None;
+        self.guesses.clear();
+        self.best_guesses.clear();
+        if self.mode != GameMode::Wordle {
+            let table = self.neighbor_table()?;
+            debug!(
+                "similarities to the word to find: {} to {}, histogram {:?}",
+                table.min, table.max, table.histogram
+            );
+        }
+        if self.mode == GameMode::Rank {
+            self.rank_table = block_in_place(|| words.rank_table(&self.word, self.metric));
+        }
+        self.session_changed.notify_one();
+        self.emit(GameEvent::SessionStarted { session_id });
 
         info!(
             "new game started at {:?}, will end at {:?} (session_id={})",
@@ -269,25 +2375,725 @@ impl GameState {
 pub struct Game(Arc<Mutex<GameState>>);
 
 impl Game {
-    pub fn load(conn: rusqlite::Connection, words: Arc<Words>) -> Result<Game> {
-        Ok(Game(Arc::new(Mutex::new(GameState::load(conn, words)?))))
+    pub async fn load(
+        storage: Box<dyn Storage>,
+        models: WordModels,
+        target: TargetSettings,
+        definitions: Option<DefinitionFetcher>,
+        scoring: ScoringConfig,
+        notices: Option<UnboundedSender<SessionNotice>>,
+    ) -> Result<Game> {
+        let state = GameState::load(storage, models, target, definitions, scoring, notices).await?;
+        Ok(Game(Arc::new(Mutex::new(state))))
+    }
+
+    pub async fn current_session(&self) -> Option<Session> {
+        self.0.lock().await.current_session()
+    }
+
+    /// Returns a receiver of the game events that happen from now on.
+    pub async fn subscribe(&self) -> broadcast::Receiver<GameEvent> {
+        self.0.lock().await.events.subscribe()
+    }
+
+    /// Makes a player join a team. Returns the name of the team.
+    pub async fn join_team(&self, nick: String, team: String) -> Result<String> {
+        self.0.lock().await.join_team(&nick, &team).await
+    }
+
+    /// Makes `alias` another nick of the player `nick`, whose guesses and points go to the player.
+    pub async fn add_alias(&self, nick: String, alias: String) -> Result<()> {
+        self.0.lock().await.add_alias(&nick, &alias).await
+    }
+
+    /// Merges the player `from` into the player `into`, `from` becoming an alias of `into`.
+    pub async fn merge_players(&self, from: String, into: String) -> Result<()> {
+        self.0.lock().await.merge_players(&from, &into).await
+    }
+
+    /// Anonymizes a player who asked to be forgotten. Returns `false` if they never played.
+    pub async fn forget_player(&self, nick: String) -> Result<bool> {
+        self.0.lock().await.forget_player(&nick).await
+    }
+
+    /// Removes a player from their team. Returns `false` if they were not in a team.
+    pub async fn leave_team(&self, nick: String) -> Result<bool> {
+        let mut state = self.0.lock().await;
+        let player_id = state.storage.player_id(&nick).await?;
+        state.storage.leave_team(player_id).await
+    }
+
+    /// Starts a tournament of `days` days, named after its start date if `name` is unset.
+    pub async fn start_tournament(&self, name: Option<String>, days: u64) -> Result<Tournament> {
+        self.0.lock().await.start_tournament(name.as_deref(), days).await
+    }
+
+    /// Registers a player to the tournament in progress. Returns the tournament, and `false` if the player was
+    /// already registered.
+    pub async fn join_tournament(&self, nick: String) -> Result<(Tournament, bool)> {
+        self.0.lock().await.join_tournament(&nick).await
+    }
+
+    /// Returns the tournament in progress and its standings, if there's one.
+    pub async fn tournament_standings(&self) -> Result<Option<(Tournament, Vec<Player>)>> {
+        let mut state = self.0.lock().await;
+        match state.storage.current_tournament().await? {
+            Some(tournament) => {
+                let standings = state.storage.tournament_standings(tournament.id).await?;
+                Ok(Some((tournament, standings)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the results of all teams, the highest score first.
+    pub async fn team_leaderboard(&self) -> Result<Vec<TeamStats>> {
+        self.0.lock().await.storage.team_stats().await
+    }
+
+    pub async fn recent_session_id(&self) -> Option<i64> {
+        self.0.lock().await.recent_session_id()
+    }
+
+    /// Returns the last `limit` guesses of a session, the most recent first. The word of the session in progress is
+    /// left out.
+    pub async fn recent_guesses(&self, session_id: i64, limit: usize) -> Result<Vec<GuessRecord>> {
+        self.0.lock().await.recent_guesses(session_id, limit).await
+    }
+
+    /// Returns the transcript of an ended session, if there's one. Sessions in progress are not exported, so their
+    /// word isn't revealed.
+    pub async fn export_session(&self, session_id: i64) -> Result<Option<SessionTranscript>> {
+        let transcript = self.0.lock().await.storage.session_transcript(session_id).await?;
+        Ok(transcript.filter(|transcript| transcript.end_date.is_some()))
+    }
+
+    pub async fn fetch_players(&self) -> Result<Vec<Player>> {
+        self.0.lock().await.storage.fetch_players().await
+    }
+
+    /// Returns the results of all players, the highest season score first if there are seasons, then the
+    /// highest all-time score.
+    pub async fn leaderboard(&self) -> Result<Vec<PlayerStats>> {
+        self.0.lock().await.storage.player_stats().await
+    }
+
+    /// Returns the detailed results of a player, or `None` if nobody plays with that nick.
+    pub async fn player_stats(&self, nick: String) -> Result<Option<PlayerProfile>> {
+        self.0.lock().await.storage.player_profile(&nick).await
     }
 
     pub async fn process_guess(&self, player_nick: String, guess: String) -> Result<Outcome> {
-        let state = self.0.clone();
-        tokio::task::spawn_blocking(move || {
-            let mut state = state.blocking_lock();
-            state.process_guess(player_nick, guess)
-        })
-        .await?
+        self.0.lock().await.process_guess(player_nick, guess).await
     }
 
-    pub async fn start_game(&self, game_duration: Duration) -> Result<()> {
-        let state = self.0.clone();
-        tokio::task::spawn_blocking(move || {
-            let mut state = state.blocking_lock();
-            state.start_game(game_duration)
-        })
-        .await?
+    /// Returns whether several players can find the word of a session.
+    pub async fn several_winners(&self) -> bool {
+        self.0.lock().await.scoring.several_winners()
+    }
+
+    /// Returns whether the word of the current session was found, and the other players can only keep looking for
+    /// it in private messages (late solver mode).
+    pub async fn late_solving(&self) -> bool {
+        self.0.lock().await.late_solvers.is_some()
+    }
+
+    /// Returns whether a player can keep looking for the word of the current session in private messages, after it
+    /// was found (late solver mode).
+    pub async fn is_late_solver(&self, nick: &str) -> Result<bool> {
+        let mut state = self.0.lock().await;
+        if state.late_solvers.is_none() {
+            return Ok(false);
+        }
+        let player_id = state.storage.find_player(nick).await?;
+        let late_solvers = state.late_solvers.as_ref();
+        Ok(player_id.is_some_and(|id| late_solvers.is_some_and(|late_solvers| late_solvers.contains(&id))))
+    }
+
+    /// Counts the vote of a player to give up the current session. See [`GameState::give_up`].
+    pub async fn give_up(&self, nick: String, quorum: usize) -> Result<GiveUpVote> {
+        self.0.lock().await.give_up(&nick, quorum).await
+    }
+
+    /// Starts a practice game for a player. Returns the word of the practice game it replaces, if any.
+    pub async fn start_practice(&self, nick: String) -> Result<Option<String>> {
+        self.0.lock().await.start_practice(&nick)
+    }
+
+    /// Ends the practice game of a player. Returns its word, if the player had one.
+    pub async fn stop_practice(&self, nick: String) -> Option<String> {
+        self.0.lock().await.stop_practice(&nick)
+    }
+
+    pub async fn practice_guess(&self, nick: String, guess: String) -> Result<Outcome> {
+        self.0.lock().await.practice_guess(&nick, &guess).await
+    }
+
+    /// Starts a reverse game for a player, who picked `word`. Returns the first guess of the bot.
+    pub async fn start_reverse(&self, nick: String, word: String) -> Result<ReverseGuess> {
+        self.0.lock().await.start_reverse(&nick, &word)
+    }
+
+    /// Ends the reverse game of a player. Returns `false` if the player had none.
+    pub async fn stop_reverse(&self, nick: String) -> bool {
+        self.0.lock().await.stop_reverse(&nick)
+    }
+
+    /// Records the similarity a player reported for the last guess of the bot. Returns the next guess of the bot.
+    pub async fn reverse_report(&self, nick: String, similarity: f32) -> Result<ReverseGuess> {
+        self.0.lock().await.reverse_report(&nick, similarity)
+    }
+
+    pub async fn session_leaderboard(&self) -> Result<Vec<LeaderboardEntry>> {
+        self.0.lock().await.session_leaderboard().await
+    }
+
+    pub async fn hint(&self) -> Result<Hint> {
+        self.0.lock().await.hint().await
+    }
+
+    /// Ends the current session with no winner.
+    pub async fn end_game(&self) -> Result<()> {
+        self.0.lock().await.end_game().await.map(|_| ())
+    }
+
+    /// Returns the share text of the last session won by a player, if there's one.
+    pub async fn share(&self, nick: String) -> Result<Option<String>> {
+        self.0.lock().await.storage.last_share(&nick).await
+    }
+
+    /// Ends the current session with no winner, and returns its word and leaderboard.
+    pub async fn end_and_reveal(&self) -> Result<EndedSession> {
+        self.0.lock().await.end_and_reveal().await
+    }
+
+    pub async fn start_game(&self, game_duration: Duration, options: SessionOptions) -> Result<()> {
+        self.0.lock().await.start_game(game_duration, options).await
+    }
+
+    /// Ends each session with no winner when its planned end date is reached, and sends a notice to
+    /// `notices`. Also sends a hint after each fraction of the session duration of `auto_hints` if the session is
+    /// still in progress. Runs forever.
+    pub async fn expire_sessions(self, mut auto_hints: Vec<f64>, notices: UnboundedSender<SessionNotice>) {
+        auto_hints.sort_by(f64::total_cmp);
+        // session whose hints are tracked, and index in `auto_hints` of its next hint
+        let mut next_hint = (None, 0);
+        loop {
+            let (session, session_changed) = {
+                let state = self.0.lock().await;
+                (
+                    state.session_id.map(|id| (id, state.start_date, state.planned_end_date)),
+                    state.session_changed.clone(),
+                )
+            };
+            let (session_id, start_date, planned_end_date) = match session {
+                Some(session) => session,
+                None => {
+                    session_changed.notified().await;
+                    continue;
+                }
+            };
+
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+            let hint_date = |i: usize| {
+                let duration = (planned_end_date - start_date) as f64;
+                start_date + (auto_hints[i] * duration) as i64
+            };
+            if next_hint.0 != Some(session_id) {
+                // skip the hints that are already due, e.g. when the bot restarts
+                next_hint = (Some(session_id), (0..auto_hints.len()).filter(|&i| hint_date(i) <= now).count());
+            }
+            let hint_due = (next_hint.1 < auto_hints.len()).then(|| hint_date(next_hint.1));
+            let wakeup = hint_due.map_or(planned_end_date, |date| date.min(planned_end_date));
+            let delay = Duration::from_secs((wakeup - now).max(0) as u64);
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                // the session was replaced or won in the meantime
+                _ = session_changed.notified() => continue,
+            }
+
+            if hint_due.is_some_and(|date| date < planned_end_date) {
+                next_hint.1 += 1;
+                // the channels don't see the late solvers play
+                if self.late_solving().await {
+                    continue;
+                }
+                let result = self.0.lock().await.hint().await;
+                match result {
+                    Ok(hint) => {
+                        let _ = notices.send(SessionNotice::Hint { hint });
+                    }
+                    Err(err) => warn!("could not give a hint: {}", err),
+                }
+                continue;
+            }
+
+            let result = self.0.lock().await.expire().await;
+            match result {
+                Ok(Some(notice)) => {
+                    // nobody may be listening
+                    let _ = notices.send(notice);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    error!("could not end the session that ran out of time: {}", err);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    /// Writes a copy of the database to the backup directory. Returns the path of the copy.
+    pub async fn backup(&self) -> Result<String> {
+        self.0.lock().await.storage.backup().await
+    }
+
+    /// Backs up the database after each `interval`. Runs forever.
+    pub async fn back_up_periodically(self, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            match self.backup().await {
+                Ok(path) => info!("database backed up to `{}`", path),
+                Err(err) => error!("could not back up the database: {}", err),
+            }
+        }
+    }
+
+    /// Archives the guesses of the sessions that ended more than `days` days ago, now and then daily. Runs forever.
+    pub async fn archive_guesses_periodically(self, days: u64) {
+        loop {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+            let before = now - (days * 86400) as i64;
+            let result = self.0.lock().await.storage.archive_guesses(before).await;
+            match result {
+                Ok(0) => {}
+                Ok(count) => info!("archived {} guesses", count),
+                Err(err) => error!("could not archive guesses: {}", err),
+            }
+            tokio::time::sleep(Duration::from_secs(86400)).await;
+        }
+    }
+
+    /// Sends the standings of the tournament in progress to `notices` at the end of each day of the tournament, and
+    /// ends the tournament at its end date, sending its final standings. Runs forever.
+    pub async fn run_tournaments(self, notices: UnboundedSender<SessionNotice>) {
+        const DAY: i64 = 24 * 3600;
+        // tournament whose standings are announced, and the number of days announced
+        let mut announced = (None, 0);
+        loop {
+            let (result, tournament_changed) = {
+                let mut state = self.0.lock().await;
+                (
+                    state.storage.current_tournament().await,
+                    state.tournament_changed.clone(),
+                )
+            };
+            let tournament = match result {
+                Ok(Some(tournament)) => tournament,
+                Ok(None) => {
+                    tournament_changed.notified().await;
+                    continue;
+                }
+                Err(err) => {
+                    error!("could not fetch the tournament in progress: {}", err);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                    continue;
+                }
+            };
+            let now = Utc::now().timestamp();
+            let day = (now.min(tournament.end_date) - tournament.start_date) / DAY;
+            if announced.0 != Some(tournament.id) {
+                // skip the days that are already over, e.g. when the bot restarts
+                announced = (Some(tournament.id), day);
+            }
+
+            if now >= tournament.end_date {
+                let result = self.0.lock().await.end_tournament(&tournament).await;
+                match result {
+                    Ok(standings) => {
+                        let _ = notices.send(SessionNotice::TournamentEnded {
+                            name: tournament.name,
+                            standings,
+                        });
+                    }
+                    Err(err) => {
+                        error!("could not end the tournament: {}", err);
+                        tokio::time::sleep(RETRY_DELAY).await;
+                    }
+                }
+                continue;
+            }
+            if day > announced.1 {
+                let result = self.0.lock().await.storage.tournament_standings(tournament.id).await;
+                match result {
+                    Ok(standings) => {
+                        let _ = notices.send(SessionNotice::TournamentStandings {
+                            name: tournament.name.clone(),
+                            day,
+                            standings,
+                        });
+                    }
+                    Err(err) => error!("could not fetch the standings of the tournament: {}", err),
+                }
+                announced.1 = day;
+            }
+            let wakeup = (tournament.start_date + (day + 1) * DAY).min(tournament.end_date);
+            tokio::time::sleep(Duration::from_secs((wakeup - now).max(1) as u64)).await;
+        }
+    }
+
+    /// Ends the seasons when the next one is due, starting with the current one if it's over, and sends the
+    /// standings of the ended seasons to `notices`. Runs forever.
+    pub async fn roll_seasons(self, length: SeasonLength, notices: UnboundedSender<SessionNotice>) {
+        loop {
+            let result = self.0.lock().await.roll_season(length).await;
+            match result {
+                Ok(Some(standings)) => {
+                    let _ = notices.send(SessionNotice::SeasonEnded { standings });
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    error!("could not start a new season: {}", err);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                    continue;
+                }
+            }
+            let next = length.next_season_start(length.season_start(Utc::now()));
+            info!("next season starts at {}", next);
+            tokio::time::sleep((next - Utc::now()).to_std().unwrap_or_default()).await;
+        }
+    }
+
+    /// Starts a new session at each scheduled time, ending the current one, and sends a notice to `notices`.
+    /// The next start is computed from the clock, so missed starts are not caught up. Runs until there are no
+    /// more start times.
+    pub async fn start_scheduled(
+        self,
+        schedule: StartSchedule,
+        game_duration: Duration,
+        notices: UnboundedSender<SessionNotice>,
+    ) {
+        let mut after = Utc::now();
+        while let Some(start) = schedule.next_start(after) {
+            info!("next scheduled session at {}", start.with_timezone(&schedule.timezone));
+            tokio::time::sleep((start - Utc::now()).to_std().unwrap_or_default()).await;
+            match self.start_game(game_duration, schedule.options.clone()).await {
+                Ok(()) => {
+                    let _ = notices.send(SessionNotice::Started {
+                        theme: schedule.options.theme.clone(),
+                    });
+                }
+                Err(err) => error!("could not start the scheduled session: {}", err),
+            }
+            after = start;
+        }
+        info!("no more scheduled sessions");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        storage::{SqliteConfig, SqliteStorage},
+        words::{LoadProgress, ModelConfig, SharedWords, Words},
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+    /// Word model of the test games.
+    const MODEL: &str = "5 2\nchat 1 0\nchien 0.8 0.6\nlion 0.6 0.8\nsouris 0.9 0.44\ntable 0 1\n";
+
+    /// Loads a game with an in-memory database and the test model, and starts a session to find `word`. Returns the
+    /// game and its notices.
+    async fn start_game(scoring: ScoringConfig, word: &str) -> (Game, UnboundedReceiver<SessionNotice>) {
+        start_game_with(scoring, word, Default::default()).await
+    }
+
+    async fn start_game_with(
+        scoring: ScoringConfig,
+        word: &str,
+        options: SessionOptions,
+    ) -> (Game, UnboundedReceiver<SessionNotice>) {
+        static MODEL_FILES: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "wordlebot-test-{}-{}.txt",
+            std::process::id(),
+            MODEL_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, MODEL).unwrap();
+        let config: ModelConfig = toml::from_str(&format!("word2vec_model_file = {:?}", path)).unwrap();
+        let words = Words::load(&config, &LoadProgress::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let storage = Box::new(SqliteStorage::open(":memory:", &SqliteConfig::default()).await.unwrap());
+        let models = WordModels::from(SharedWords::from(words));
+        let (notices, receiver) = mpsc::unbounded_channel();
+        let game = Game::load(storage, models, TargetSettings::default(), None, scoring, Some(notices))
+            .await
+            .unwrap();
+        let options = SessionOptions {
+            word: Some(word.to_string()),
+            ..options
+        };
+        game.start_game(Duration::from_secs(3600), options).await.unwrap();
+        (game, receiver)
+    }
+
+    /// Returns the winners of the recap of a session, if it was sent.
+    fn recap_winners(notices: &mut UnboundedReceiver<SessionNotice>) -> Option<Vec<String>> {
+        while let Ok(notice) = notices.try_recv() {
+            if let SessionNotice::Recap { winners, .. } = notice {
+                return Some(winners);
+            }
+        }
+        None
+    }
+
+    async fn guess(game: &Game, nick: &str, guess: &str) -> Outcome {
+        game.process_guess(nick.to_string(), guess.to_string()).await.unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn forgotten_player() {
+        let scoring = ScoringConfig {
+            late_solvers: true,
+            ..Default::default()
+        };
+        let (game, mut notices) = start_game(scoring, "chat").await;
+        assert!(matches!(guess(&game, "alice", "chien").await, Outcome::Miss { .. }));
+        match guess(&game, "bob", "chien").await {
+            Outcome::AlreadyGuessed { nick, .. } => assert_eq!(nick, "alice"),
+            _ => panic!("bob's guess should be already guessed"),
+        }
+        assert!(matches!(guess(&game, "bob", "lion").await, Outcome::Miss { .. }));
+        assert!(matches!(
+            guess(&game, "alice", "chat").await,
+            Outcome::Win { ended: false, .. }
+        ));
+
+        assert!(game.forget_player("alice".to_string()).await.unwrap());
+        let anonymous = anonymous_nick(1);
+        match guess(&game, "bob", "chien").await {
+            Outcome::AlreadyGuessed { nick, .. } => assert_eq!(nick, anonymous),
+            _ => panic!("bob's guess should be already guessed"),
+        }
+        assert!(matches!(
+            guess(&game, "bob", "chat").await,
+            Outcome::Win { ended: true, .. }
+        ));
+        assert_eq!(recap_winners(&mut notices), Some(vec![anonymous, "bob".to_string()]));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn merged_players() {
+        let scoring = ScoringConfig {
+            max_winners: 0,
+            ..Default::default()
+        };
+        let strict = SessionOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let (game, _notices) = start_game_with(scoring, "chat", strict).await;
+        assert!(matches!(guess(&game, "alice", "souris").await, Outcome::Miss { .. }));
+        assert!(matches!(guess(&game, "bob", "lion").await, Outcome::Miss { .. }));
+        game.merge_players("bob".to_string(), "alice".to_string())
+            .await
+            .unwrap();
+
+        // bob is alice now: the guesses must be closer than her best guess
+        match guess(&game, "bob", "chien").await {
+            Outcome::NotCloser { best, .. } => assert!(best > 0.85, "{}", best),
+            _ => panic!("bob's guess should not be closer"),
+        }
+        match guess(&game, "carol", "lion").await {
+            Outcome::AlreadyGuessed { nick, .. } => assert_eq!(nick, "alice"),
+            _ => panic!("carol's guess should be already guessed"),
+        }
+        assert!(matches!(
+            guess(&game, "bob", "chat").await,
+            Outcome::Win { ended: false, .. }
+        ));
+        assert!(matches!(guess(&game, "alice", "chat").await, Outcome::AlreadyFound));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn blitz_during_game() {
+        let (game, _notices) = start_game(ScoringConfig::default(), "chat").await;
+        assert!(matches!(guess(&game, "alice", "lion").await, Outcome::Miss { .. }));
+        let blitz = SessionOptions {
+            mode: GameMode::Blitz,
+            word: Some("table".to_string()),
+            ..Default::default()
+        };
+        assert!(game.start_game(Duration::from_secs(3600), blitz).await.is_err());
+
+        // the game goes on
+        assert!(matches!(guess(&game, "bob", "chat").await, Outcome::Win { .. }));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn give_up_with_alias() {
+        let (game, _notices) = start_game(ScoringConfig::default(), "chat").await;
+        assert!(matches!(guess(&game, "alice", "lion").await, Outcome::Miss { .. }));
+        assert!(matches!(guess(&game, "bob", "table").await, Outcome::Miss { .. }));
+        game.add_alias("alice".to_string(), "al".to_string()).await.unwrap();
+
+        assert!(matches!(
+            game.give_up("al".to_string(), 2).await.unwrap(),
+            GiveUpVote::Counted { votes: 1, required: 2 }
+        ));
+        assert!(matches!(
+            game.give_up("alice".to_string(), 2).await.unwrap(),
+            GiveUpVote::AlreadyVoted { votes: 1, required: 2 }
+        ));
+        assert!(game.give_up("carol".to_string(), 2).await.is_err());
+        assert!(matches!(
+            game.give_up("bob".to_string(), 2).await.unwrap(),
+            GiveUpVote::Ended { .. }
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tournament_points_with_alias() {
+        let (game, _notices) = start_game(ScoringConfig::default(), "chat").await;
+        assert!(matches!(guess(&game, "alice", "lion").await, Outcome::Miss { .. }));
+        game.add_alias("alice".to_string(), "al".to_string()).await.unwrap();
+        game.start_tournament(None, 7).await.unwrap();
+        game.join_tournament("al".to_string()).await.unwrap();
+        game.join_tournament("bob".to_string()).await.unwrap();
+
+        assert!(matches!(guess(&game, "al", "souris").await, Outcome::Miss { .. }));
+        assert!(matches!(
+            guess(&game, "bob", "chat").await,
+            Outcome::Win { ended: true, .. }
+        ));
+        let (_, standings) = game.tournament_standings().await.unwrap().unwrap();
+        let standings: Vec<(&str, i64)> = standings
+            .iter()
+            .map(|player| (player.nick.as_str(), player.score))
+            .collect();
+        assert_eq!(standings, [("bob", 10), ("alice", 7)]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn hidden_word() {
+        let scoring = ScoringConfig {
+            max_winners: 0,
+            ..Default::default()
+        };
+        let (game, _notices) = start_game(scoring, "chat").await;
+        assert!(matches!(guess(&game, "alice", "lion").await, Outcome::Miss { .. }));
+        assert!(matches!(guess(&game, "bob", "table").await, Outcome::Miss { .. }));
+        assert!(matches!(
+            guess(&game, "alice", "chat").await,
+            Outcome::Win { ended: false, .. }
+        ));
+        assert!(matches!(guess(&game, "bob", "chien").await, Outcome::Miss { .. }));
+
+        // bob is still looking for the word
+        let leaderboard = game.session_leaderboard().await.unwrap();
+        let nicks: Vec<&str> = leaderboard.iter().map(|entry| entry.nick.as_str()).collect();
+        assert_eq!(nicks, ["bob"]);
+        let session_id = game.recent_session_id().await.unwrap();
+        let guesses = game.recent_guesses(session_id, 2).await.unwrap();
+        let words: Vec<&str> = guesses.iter().map(|guess| guess.guess.as_str()).collect();
+        assert_eq!(words, ["chien", "table"]);
+    }
+
+    fn schedule(config: &str) -> StartSchedule {
+        StartSchedule::new(toml::from_str(config).unwrap()).unwrap()
+    }
+
+    fn utc(date: &str) -> DateTime<Utc> {
+        date.parse().unwrap()
+    }
+
+    #[test]
+    fn next_daily_start() {
+        // Paris is at UTC+1 in winter and UTC+2 in summer
+        let paris = schedule("time = \"08:00\"\ntimezone = \"Europe/Paris\"");
+        let cases = [
+            // (after, next start)
+            ("2024-01-15T06:00:00Z", "2024-01-15T07:00:00Z"),
+            // strictly after
+            ("2024-01-15T07:00:00Z", "2024-01-16T07:00:00Z"),
+            ("2024-07-01T12:00:00Z", "2024-07-02T06:00:00Z"),
+            // daylight saving time starts on 2024-03-31 and ends on 2024-10-27
+            ("2024-03-30T07:00:00Z", "2024-03-31T06:00:00Z"),
+            ("2024-10-26T06:00:00Z", "2024-10-27T07:00:00Z"),
+            ("2024-12-31T08:00:00Z", "2025-01-01T07:00:00Z"),
+        ];
+        for (after, expected) in cases {
+            assert_eq!(paris.next_start(utc(after)), Some(utc(expected)), "after {}", after);
+        }
+
+        let utc_schedule = schedule("time = \"23:59\"");
+        assert_eq!(
+            utc_schedule.next_start(utc("2024-02-28T23:59:00Z")),
+            Some(utc("2024-02-29T23:59:00Z"))
+        );
+    }
+
+    #[test]
+    fn next_daily_start_around_dst_changes() {
+        let paris = schedule("time = \"02:30\"\ntimezone = \"Europe/Paris\"");
+        // 02:30 doesn't exist on 2024-03-31 (02:00 -> 03:00), that day is skipped
+        assert_eq!(
+            paris.next_start(utc("2024-03-30T02:00:00Z")),
+            Some(utc("2024-04-01T00:30:00Z"))
+        );
+        // 02:30 happens twice on 2024-10-27 (03:00 -> 02:00): only the first one
+        assert_eq!(
+            paris.next_start(utc("2024-10-26T12:00:00Z")),
+            Some(utc("2024-10-27T00:30:00Z"))
+        );
+        assert_eq!(
+            paris.next_start(utc("2024-10-27T00:30:00Z")),
+            Some(utc("2024-10-28T01:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn next_cron_start() {
+        let weekdays = schedule("cron = \"0 8 * * Mon-Fri\"\ntimezone = \"Europe/Paris\"");
+        let cases = [
+            // (after, next start)
+            ("2024-03-27T06:00:00Z", "2024-03-27T07:00:00Z"),
+            // friday -> monday, across the start of daylight saving time
+            ("2024-03-29T07:00:00Z", "2024-04-01T06:00:00Z"),
+            ("2024-10-25T06:00:00Z", "2024-10-28T07:00:00Z"),
+        ];
+        for (after, expected) in cases {
+            assert_eq!(weekdays.next_start(utc(after)), Some(utc(expected)), "after {}", after);
+        }
+
+        // first day of the month, with seconds
+        let monthly = schedule("cron = \"0 0 9 1 * *\"\ntimezone = \"Europe/Paris\"");
+        assert_eq!(
+            monthly.next_start(utc("2024-03-01T08:00:00Z")),
+            Some(utc("2024-04-01T07:00:00Z"))
+        );
+        assert_eq!(
+            monthly.next_start(utc("2024-10-15T00:00:00Z")),
+            Some(utc("2024-11-01T08:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn invalid_schedules() {
+        for config in [
+            "",
+            "time = \"08:00\"\ncron = \"0 8 * * *\"",
+            "time = \"8h\"",
+            "cron = \"0 8 * *\"",
+        ] {
+            assert!(
+                StartSchedule::new(toml::from_str(config).unwrap()).is_err(),
+                "{:?}",
+                config
+            );
+        }
     }
 }
@@ -0,0 +1,43 @@
+//! Heat bars
+//!
+//! The heat of a guess tells how close it is to the word to find, from 0 (further than any word of the vocabulary)
+//! to 1 (closer than all of them). It is calibrated on the similarities of the vocabulary to the word rather than on
+//! the raw similarity, whose scale depends on the word and the metric (see [`NeighborTable::heat`]), and shown as a
+//! bar after the misses on IRC and in the leaderboard of the web page.
+//!
+//! [`NeighborTable::heat`]: crate::words::NeighborTable::heat
+
+/// Number of squares of the emoji bars.
+const EMOJI_CELLS: usize = 5;
+
+/// Number of characters between the brackets of the ASCII bars.
+const ASCII_CELLS: usize = 10;
+
+/// Formats a heat as colored squares, e.g. `🟩🟩🟨⬜⬜`: a green square for each full cell, and a yellow one for a
+/// half-full cell.
+pub fn emoji_bar(heat: f32) -> String {
+    let halves = (heat.clamp(0.0, 1.0) * (2 * EMOJI_CELLS) as f32).round() as usize;
+    let (full, half) = (halves / 2, halves % 2);
+    format!(
+        "{}{}{}",
+        "🟩".repeat(full),
+        "🟨".repeat(half),
+        "⬜".repeat(EMOJI_CELLS - full - half)
+    )
+}
+
+/// Formats a heat as a single colored square, from white (cold) to green (hot).
+pub fn emoji_square(heat: f32) -> &'static str {
+    match heat {
+        h if h < 0.25 => "⬜",
+        h if h < 0.5 => "🟨",
+        h if h < 0.75 => "🟧",
+        _ => "🟩",
+    }
+}
+
+/// Formats a heat as an ASCII bar, e.g. `[#####-----]`.
+pub fn ascii_bar(heat: f32) -> String {
+    let full = (heat.clamp(0.0, 1.0) * ASCII_CELLS as f32).round() as usize;
+    format!("[{}{}]", "#".repeat(full), "-".repeat(ASCII_CELLS - full))
+}
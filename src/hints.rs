@@ -0,0 +1,102 @@
+//! Hints
+//!
+//! Hints are generated mechanically by the game (e.g. "a word near rank 300 is ..."), and can
+//! optionally be rephrased as riddles by a language model behind an OpenAI-compatible API.
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+fn default_llm_prompt() -> String {
+    "You are the host of a word guessing game. Rephrase the hint given by the user as a short, playful riddle \
+     (one or two sentences, in the language of the hint). Keep all the information of the hint, but never \
+     state the quoted word directly."
+        .to_string()
+}
+
+fn default_llm_timeout() -> u64 {
+    10
+}
+
+/// `[llm_hints]` section of the configuration.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LlmHintsConfig {
+    /// URL of the chat completions endpoint (e.g. `https://api.openai.com/v1/chat/completions`).
+    pub url: String,
+    /// API key, sent as a bearer token.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Model name.
+    pub model: String,
+    /// System prompt.
+    #[serde(default = "default_llm_prompt")]
+    pub prompt: String,
+    /// Request timeout in seconds. The plain hint is used if the API doesn't answer in time.
+    #[serde(default = "default_llm_timeout")]
+    pub timeout: u64,
+}
+
+/// Rephrases hints with a language model.
+pub struct LlmHinter {
+    config: LlmHintsConfig,
+    agent: ureq::Agent,
+    /// Plain hint -> rephrased hint.
+    cache: Mutex<HashMap<String, String>>,
+}
+
+impl LlmHinter {
+    pub fn new(config: LlmHintsConfig) -> LlmHinter {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(config.timeout))
+            .build();
+        LlmHinter {
+            config,
+            agent,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the rephrased version of the hint, or the hint itself if the API is unavailable.
+    ///
+    /// This blocks until the API replies.
+    pub fn phrase(&self, hint: &str) -> String {
+        if let Some(phrased) = self.cache.lock().unwrap().get(hint) {
+            return phrased.clone();
+        }
+        match self.request(hint) {
+            Ok(phrased) => {
+                self.cache.lock().unwrap().insert(hint.to_string(), phrased.clone());
+                phrased
+            }
+            Err(err) => {
+                warn!("could not rephrase hint, using plain hint: {}", err);
+                hint.to_string()
+            }
+        }
+    }
+
+    fn request(&self, hint: &str) -> Result<String> {
+        let mut request = self.agent.post(&self.config.url);
+        if let Some(ref api_key) = self.config.api_key {
+            request = request.set("Authorization", &format!("Bearer {}", api_key));
+        }
+        let response: Value = request
+            .send_json(json!({
+                "model": self.config.model,
+                "messages": [
+                    {"role": "system", "content": self.config.prompt},
+                    {"role": "user", "content": hint},
+                ],
+            }))?
+            .into_json()?;
+        let content = response["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| anyhow!("unexpected response from the API"))?
+            .trim();
+        if content.is_empty() {
+            return Err(anyhow!("empty response from the API"));
+        }
+        // IRC messages are single-line
+        Ok(content.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+}
@@ -0,0 +1,378 @@
+//! Approximate nearest-neighbor index (HNSW)
+//!
+//! Hierarchical navigable small world graphs (Malkov & Yashunin, 2016): each word is linked to some
+//! of its nearest neighbors on one or more layers, the upper layers being sparser. A search descends
+//! greedily from the top layer, then explores the neighborhood of the best nodes on the bottom layer.
+//!
+//! The index only stores the graph: similarities are computed by the caller, so that it works with
+//! any vector storage.
+//!
+//! Index files are made of (native byte order):
+//! - the magic bytes `WBHNSW01`
+//! - the fingerprint of the model the index was built from, the number of nodes and the entry point
+//!   (`u64::MAX` if there's none), as `u64`s
+//! - for each node: its number of layers, then for each layer its number of links and the links, as `u32`s
+use anyhow::{bail, Result};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashSet},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+const INDEX_FILE_MAGIC: &[u8; 8] = b"WBHNSW01";
+
+/// Number of links of a node on the upper layers. Nodes have twice as many links on the bottom layer.
+const M: usize = 16;
+/// Number of candidates considered when inserting a node.
+const EF_CONSTRUCTION: usize = 100;
+/// Minimum number of candidates considered by a search.
+const EF_SEARCH: usize = 64;
+
+/// A node and its similarity to the query, ordered by similarity.
+#[derive(Copy, Clone)]
+struct Scored {
+    similarity: f32,
+    node: u32,
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.similarity.total_cmp(&other.similarity)
+    }
+}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+/// Maximum number of links of a node on a layer.
+fn max_links(layer: usize) -> usize {
+    if layer == 0 {
+        2 * M
+    } else {
+        M
+    }
+}
+
+/// Approximate nearest-neighbor index over the rows of a matrix.
+pub struct Hnsw {
+    /// Node on the top layer where searches start.
+    entry: Option<u32>,
+    /// Links of each node, per layer.
+    links: Vec<Vec<Vec<u32>>>,
+}
+
+impl Hnsw {
+    /// Builds the index of `len` nodes. `similarity(a, b)` is the similarity between two nodes.
+    pub fn build(len: usize, similarity: impl Fn(usize, usize) -> f32) -> Hnsw {
+        let _span = trace_span!("Building HNSW index", len).entered();
+
+        // fixed seed, so that the index of a model is always the same
+        let mut rng = StdRng::seed_from_u64(0);
+        let level_factor = 1.0 / (M as f64).ln();
+        let mut index = Hnsw {
+            entry: None,
+            links: Vec::with_capacity(len),
+        };
+        for node in 0..len {
+            let level = (-rng.gen::<f64>().ln() * level_factor) as usize;
+            index.links.push(vec![Vec::new(); level + 1]);
+            index.insert(node as u32, level, &similarity);
+        }
+        index
+    }
+
+    fn level(&self, node: u32) -> usize {
+        self.links[node as usize].len() - 1
+    }
+
+    fn insert(&mut self, node: u32, level: usize, similarity: &impl Fn(usize, usize) -> f32) {
+        let entry = match self.entry {
+            Some(entry) => entry,
+            None => {
+                self.entry = Some(node);
+                return;
+            }
+        };
+        let top = self.level(entry);
+        let query = |other: u32| similarity(node as usize, other as usize);
+
+        let mut entries = vec![Scored {
+            similarity: query(entry),
+            node: entry,
+        }];
+        for layer in (level + 1..=top).rev() {
+            entries = self.search_layer(&query, &entries, 1, layer);
+        }
+        for layer in (0..=level.min(top)).rev() {
+            entries = self.search_layer(&query, &entries, EF_CONSTRUCTION, layer);
+            let neighbors: Vec<u32> = entries.iter().take(M).map(|scored| scored.node).collect();
+            for &neighbor in &neighbors {
+                let links = &mut self.links[neighbor as usize][layer];
+                links.push(node);
+                if links.len() > max_links(layer) {
+                    // keep the closest links
+                    links.sort_by_cached_key(|&other| {
+                        Reverse(Scored {
+                            similarity: similarity(neighbor as usize, other as usize),
+                            node: other,
+                        })
+                    });
+                    links.truncate(max_links(layer));
+                }
+            }
+            self.links[node as usize][layer] = neighbors;
+        }
+        if level > top {
+            self.entry = Some(node);
+        }
+    }
+
+    /// Searches the `ef` nodes of a layer most similar to the query, starting from `entries`. Returns them
+    /// from the most to the least similar.
+    fn search_layer(&self, query: &impl Fn(u32) -> f32, entries: &[Scored], ef: usize, layer: usize) -> Vec<Scored> {
+        let mut visited: HashSet<u32> = entries.iter().map(|scored| scored.node).collect();
+        let mut candidates: BinaryHeap<Scored> = entries.iter().copied().collect();
+        let mut results: BinaryHeap<Reverse<Scored>> = entries.iter().copied().map(Reverse).collect();
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some(candidate) = candidates.pop() {
+            let worst = results
+                .peek()
+                .map(|worst| worst.0.similarity)
+                .unwrap_or(f32::NEG_INFINITY);
+            if candidate.similarity < worst && results.len() >= ef {
+                break;
+            }
+            for &neighbor in &self.links[candidate.node as usize][layer] {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let scored = Scored {
+                    similarity: query(neighbor),
+                    node: neighbor,
+                };
+                let worst = results
+                    .peek()
+                    .map(|worst| worst.0.similarity)
+                    .unwrap_or(f32::NEG_INFINITY);
+                if results.len() < ef || scored.similarity > worst {
+                    candidates.push(scored);
+                    results.push(Reverse(scored));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+        results.into_sorted_vec().into_iter().map(|scored| scored.0).collect()
+    }
+
+    /// Returns (approximately) the `count` nodes most similar to the query with their similarity, from the
+    /// most to the least similar. `query(node)` is the similarity between the query and a node.
+    pub fn search(&self, count: usize, query: impl Fn(usize) -> f32) -> Vec<(usize, f32)> {
+        let entry = match self.entry {
+            Some(entry) => entry,
+            None => return Vec::new(),
+        };
+        let query = |node: u32| query(node as usize);
+        let mut entries = vec![Scored {
+            similarity: query(entry),
+            node: entry,
+        }];
+        for layer in (1..=self.level(entry)).rev() {
+            entries = self.search_layer(&query, &entries, 1, layer);
+        }
+        self.search_layer(&query, &entries, count.max(EF_SEARCH), 0)
+            .into_iter()
+            .take(count)
+            .map(|scored| (scored.node as usize, scored.similarity))
+            .collect()
+    }
+
+    /// Writes the index to a file.
+    pub fn write(&self, path: &str, fingerprint: u64) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(INDEX_FILE_MAGIC)?;
+        let entry = self.entry.map(u64::from).unwrap_or(u64::MAX);
+        for n in [fingerprint, self.links.len() as u64, entry] {
+            writer.write_all(&n.to_ne_bytes())?;
+        }
+        for layers in &self.links {
+            writer.write_all(&(layers.len() as u32).to_ne_bytes())?;
+            for links in layers {
+                writer.write_all(&(links.len() as u32).to_ne_bytes())?;
+                writer.write_all(bytemuck::cast_slice(links))?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads an index file. Returns `None` if the file doesn't exist or was built from another model.
+    pub fn read(path: &str, fingerprint: u64) -> Result<Option<Hnsw>> {
+        let mut reader = match File::open(path) {
+            Ok(file) => BufReader::new(file),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_FILE_MAGIC {
+            return Ok(None);
+        }
+        let read_u64 = |reader: &mut BufReader<File>| -> Result<u64> {
+            let mut buf = [0; 8];
+            reader.read_exact(&mut buf)?;
+            Ok(u64::from_ne_bytes(buf))
+        };
+        let read_u32 = |reader: &mut BufReader<File>| -> Result<u32> {
+            let mut buf = [0; 4];
+            reader.read_exact(&mut buf)?;
+            Ok(u32::from_ne_bytes(buf))
+        };
+        if read_u64(&mut reader)? != fingerprint {
+            return Ok(None);
+        }
+        let len = read_u64(&mut reader)? as usize;
+        let entry = match read_u64(&mut reader)? {
+            u64::MAX => None,
+            entry if (entry as usize) < len => Some(entry as u32),
+            _ => bail!("invalid entry point in HNSW index `{}`", path),
+        };
+
+        let mut links = Vec::with_capacity(len);
+        for _ in 0..len {
+            let layer_count = read_u32(&mut reader)? as usize;
+            if layer_count == 0 {
+                bail!("invalid HNSW index `{}`", path);
+            }
+            let mut layers = Vec::with_capacity(layer_count);
+            for _ in 0..layer_count {
+                let link_count = read_u32(&mut reader)? as usize;
+                let mut layer = vec![0u32; link_count];
+                reader.read_exact(bytemuck::cast_slice_mut(&mut layer))?;
+                if layer.iter().any(|&node| node as usize >= len) {
+                    bail!("invalid link in HNSW index `{}`", path);
+                }
+                layers.push(layer);
+            }
+            links.push(layers);
+        }
+        Ok(Some(Hnsw { entry, links }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIMENSIONS: usize = 16;
+
+    /// Random unit vectors.
+    fn random_vectors(count: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|_| {
+                let vector: Vec<f32> = (0..DIMENSIONS).map(|_| rng.gen_range(-1.0..1.0)).collect();
+                let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+                vector.into_iter().map(|x| x / norm).collect()
+            })
+            .collect()
+    }
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    /// The `count` vectors most similar to the query, from the most to the least similar.
+    fn brute_force(vectors: &[Vec<f32>], query: &[f32], count: usize) -> Vec<usize> {
+        let mut nodes: Vec<usize> = (0..vectors.len()).collect();
+        nodes.sort_by(|&a, &b| cosine(&vectors[b], query).total_cmp(&cosine(&vectors[a], query)));
+        nodes.truncate(count);
+        nodes
+    }
+
+    fn build(vectors: &[Vec<f32>]) -> Hnsw {
+        Hnsw::build(vectors.len(), |a, b| cosine(&vectors[a], &vectors[b]))
+    }
+
+    #[test]
+    fn search_matches_brute_force() {
+        let vectors = random_vectors(1000, 1);
+        let index = build(&vectors);
+        let count = 10;
+        let mut found = 0;
+        let queries = random_vectors(50, 2);
+        for query in &queries {
+            let expected = brute_force(&vectors, query, count);
+            let results = index.search(count, |node| cosine(&vectors[node], query));
+            assert_eq!(results.len(), count);
+            // sorted from the most to the least similar, with the right similarities
+            assert!(results.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+            for &(node, similarity) in &results {
+                assert_eq!(similarity, cosine(&vectors[node], query));
+            }
+            found += results.iter().filter(|(node, _)| expected.contains(node)).count();
+        }
+        let recall = found as f64 / (queries.len() * count) as f64;
+        assert!(recall >= 0.95, "recall {}", recall);
+    }
+
+    #[test]
+    fn search_finds_indexed_vectors() {
+        let vectors = random_vectors(500, 3);
+        let index = build(&vectors);
+        for (node, vector) in vectors.iter().enumerate() {
+            let results = index.search(1, |other| cosine(&vectors[other], vector));
+            assert_eq!(results[0].0, node);
+        }
+    }
+
+    #[test]
+    fn search_small_indexes() {
+        let index = Hnsw::build(0, |_, _| unreachable!());
+        assert!(index.search(5, |_| unreachable!()).is_empty());
+
+        // fewer nodes than requested: all of them, exactly as by brute force
+        let vectors = random_vectors(8, 4);
+        let index = build(&vectors);
+        let query = &random_vectors(1, 5)[0];
+        let results: Vec<usize> = index
+            .search(10, |node| cosine(&vectors[node], query))
+            .into_iter()
+            .map(|(node, _)| node)
+            .collect();
+        assert_eq!(results, brute_force(&vectors, query, 10));
+    }
+
+    #[test]
+    fn write_and_read() {
+        let vectors = random_vectors(300, 6);
+        let index = build(&vectors);
+        let path = std::env::temp_dir().join(format!("wordlebot-test-{}.hnsw", std::process::id()));
+        let path = path.to_str().unwrap();
+        index.write(path, 42).unwrap();
+        let read = Hnsw::read(path, 42).unwrap().unwrap();
+        let other_model = Hnsw::read(path, 43).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert!(other_model.is_none());
+        assert_eq!(read.entry, index.entry);
+        assert_eq!(read.links, index.links);
+    }
+}
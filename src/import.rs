@@ -0,0 +1,152 @@
+//! Merging of another instance's database into ours (`wordlebot import <other.db>`)
+//!
+//! Only supported with the SQLite backend.
+use crate::{game::Player, storage::setup_schema};
+use anyhow::{bail, Result};
+use rusqlite::{params, OptionalExtension};
+use std::collections::HashMap;
+
+/// Merges players, sessions and guesses from the database at `other_db_path` into the database at `db_path`.
+///
+/// Players with the same nick in both databases are considered to be the same person: their scores
+/// are added together and their guesses are attributed to the existing player.
+/// All imported rows get fresh IDs. Sessions that were still in progress in the other database are
+/// imported as finished at their planned end date.
+///
+/// A database can only be imported once: the import is refused if one of its sessions (same start date and word) is
+/// already in ours, which is the case after a previous import of the same database or of an older copy of it, or
+/// when importing a backup of ours. Importing it again would duplicate the sessions and count the scores twice.
+pub fn import_database(db_path: &str, other_db_path: &str) -> Result<()> {
+    let mut conn = rusqlite::Connection::open(db_path)?;
+    setup_schema(&mut conn)?;
+    let other = rusqlite::Connection::open_with_flags(other_db_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let tx = conn.transaction()?;
+
+    // imported sessions keep their start date and word
+    {
+        // language=SQLITE-SQL
+        let mut stmt = other.prepare("SELECT start_date, word FROM sessions")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let start_date: Option<i64> = row.get(0)?;
+            let word: String = row.get(1)?;
+            // language=SQLITE-SQL
+            let existing: Option<i64> = tx
+                .query_row(
+                    "SELECT id FROM sessions WHERE start_date IS ?1 AND word=?2",
+                    params![start_date, word],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(id) = existing {
+                bail!(
+                    "`{}` was already imported (session {} comes from it), not importing it twice",
+                    other_db_path,
+                    id
+                );
+            }
+        }
+    }
+
+    // players: old ID -> new ID
+    let mut player_ids = HashMap::new();
+    let mut merged_players = 0;
+    {
+        // language=SQLITE-SQL
+        let mut stmt = other.prepare("SELECT id, nick, score FROM players")?;
+        let players = stmt
+            .query_map([], |row| {
+                Ok(Player {
+                    id: row.get(0)?,
+                    nick: row.get(1)?,
+                    score: row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+                })
+            })?
+            .collect::<Result<Vec<Player>, _>>()?;
+
+        for player in players {
+            let score = player.score;
+            // language=SQLITE-SQL
+            let existing: Option<i64> = tx
+                .query_row("SELECT id FROM players WHERE nick=?1", [&player.nick], |row| row.get(0))
+                .optional()?;
+            let new_id = if let Some(id) = existing {
+                // nick collision: merge into the existing player
+                // language=SQLITE-SQL
+                tx.execute(
+                    "UPDATE players SET score=IFNULL(score,0)+?1 WHERE id=?2",
+                    params![score, id],
+                )?;
+                merged_players += 1;
+                id
+            } else {
+                // language=SQLITE-SQL
+                tx.execute(
+                    "INSERT INTO players(nick, score) VALUES (?1,?2)",
+                    params![&player.nick, score],
+                )?;
+                tx.last_insert_rowid()
+            };
+            player_ids.insert(player.id, new_id);
+        }
+    }
+
+    // sessions: old ID -> new ID
+    let mut session_ids = HashMap::new();
+    {
+        // language=SQLITE-SQL
+        let mut stmt =
+            other.prepare("SELECT id, start_date, end_date, planned_end_date, word, winner_id FROM sessions")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let start_date: Option<i64> = row.get(1)?;
+            let end_date: Option<i64> = row.get(2)?;
+            let planned_end_date: Option<i64> = row.get(3)?;
+            let word: String = row.get(4)?;
+            let winner_id: Option<i64> = row.get(5)?;
+            let winner_id = winner_id.and_then(|id| player_ids.get(&id).copied());
+            // language=SQLITE-SQL
+            tx.execute(
+                "INSERT INTO sessions(start_date, end_date, planned_end_date, word, winner_id, playing) VALUES (?1,?2,?3,?4,?5,0)",
+                params![start_date, end_date.or(planned_end_date), planned_end_date, word, winner_id],
+            )?;
+            session_ids.insert(id, tx.last_insert_rowid());
+        }
+    }
+
+    // guesses
+    let mut guess_count = 0;
+    {
+        // language=SQLITE-SQL
+        let mut stmt = other.prepare("SELECT session_id, player_id, guess, cosine FROM guesses ORDER BY id")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let session_id: Option<i64> = row.get(0)?;
+            let player_id: Option<i64> = row.get(1)?;
+            let guess: String = row.get(2)?;
+            let cosine: Option<f64> = row.get(3)?;
+            let session_id = session_id.and_then(|id| session_ids.get(&id).copied());
+            let player_id = player_id.and_then(|id| player_ids.get(&id).copied());
+            // language=SQLITE-SQL
+            tx.execute(
+                "INSERT INTO guesses(session_id, player_id, guess, cosine) VALUES (?1,?2,?3,?4)",
+                params![session_id, player_id, guess, cosine],
+            )?;
+            guess_count += 1;
+        }
+    }
+
+    tx.commit()?;
+
+    info!(
+        "imported {} players ({} merged with existing nicks), {} sessions and {} guesses from `{}`",
+        player_ids.len(),
+        merged_players,
+        session_ids.len(),
+        guess_count,
+        other_db_path
+    );
+    Ok(())
+}
@@ -1,12 +1,23 @@
 //! IRC bot interface
-use crate::{game::Outcome, Game, Words};
+use crate::{
+    game::{
+        EndedSession, GameMode, GiveUpVote, GuessRecord, Hint, LeaderboardEntry, Outcome, Player, PlayerProfile,
+        PlayerStats, SessionNotice, SessionOptions, TeamStats, BLITZ_DURATION, DEFAULT_GUESS_BUDGET,
+        DEFAULT_TOURNAMENT_DAYS, NEAREST_RANKS,
+    },
+    heat,
+    hints::LlmHinter,
+    rating,
+    reverse::ReverseGuess,
+    wordle::LetterHint,
+    words::{DifficultyLevel, SharedWords, StillLoading},
+    Game,
+};
 use anyhow::Error;
 use futures::StreamExt;
 use irc::client::prelude::*;
-use rand::Rng;
-use std::{cmp::Ordering, collections::HashMap, fs::File, future::Future, io::BufReader, sync::Arc, time::Duration};
-use tokio::time::Instant;
-use word2vec::{vectorreader::WordVectorReader, wordvectors::WordVector};
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::mpsc::UnboundedReceiver, time::Instant};
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Commands
@@ -26,9 +37,69 @@ pub enum GameCommandParseError {
 }
 
 pub enum GameCommand {
-    Start,
+    Start {
+        mode: GameMode,
+        difficulty: Option<DifficultyLevel>,
+        language: Option<String>,
+        /// Hard mode.
+        strict: bool,
+        /// Number of shared guesses, in cooperative mode.
+        budget: Option<usize>,
+        /// Theme of the word to find.
+        theme: Option<String>,
+    },
     Thesaurus { word: String, count: Option<usize> },
+    Analogy {
+        positive: Vec<String>,
+        negative: Vec<String>,
+        count: Option<usize>,
+    },
+    Similarity { a: String, b: String },
     Guess { word: String },
+    Hint,
+    /// The sender votes to end the session with no winner.
+    GiveUp,
+    Top,
+    /// Last guesses of the current or last session.
+    Guesses { count: Option<usize> },
+    Leaderboard,
+    /// Share text of the last session won by a player, the sender if `nick` is `None`.
+    Share { nick: Option<String> },
+    /// Results of a player, the sender if `nick` is `None`.
+    Stats { nick: Option<String> },
+    /// The sender joins a team.
+    JoinTeam { team: String },
+    /// The sender leaves their team.
+    LeaveTeam,
+    Teams,
+    /// Makes `alias` another nick of the player `nick`, for the owners of the bot.
+    AddAlias { nick: String, alias: String },
+    /// Merges the player `from` into the player `into`, for the owners of the bot.
+    Merge { from: String, into: String },
+    /// Anonymizes a player who asked to be forgotten, for the owners of the bot.
+    Forget { nick: String },
+    /// Backs up the database, for the owners of the bot.
+    Backup,
+    /// Ends the session and reveals the word, for the owners of the bot.
+    End,
+    /// Starts a session with a specific word, for the owners of the bot, in private messages.
+    SetWord { word: String },
+    /// The sender starts a practice game, in private messages.
+    StartPractice,
+    /// The sender gives up their practice game.
+    StopPractice,
+    /// The sender starts a reverse game, in which the bot guesses `word`, in private messages.
+    StartReverse { word: String },
+    /// The sender reports the similarity of the last guess of the bot in their reverse game.
+    ReverseReport { similarity: f32 },
+    /// The sender ends their reverse game.
+    StopReverse,
+    /// Standings of the tournament in progress.
+    Tournament,
+    /// The sender registers to the tournament in progress.
+    JoinTournament,
+    /// Starts a tournament of `days` days, for the owners of the bot.
+    StartTournament { days: Option<u64>, name: Option<String> },
     Halp,
 }
 
@@ -54,29 +125,596 @@ impl GameCommand {
                 word: split[1].to_string(),
                 count,
             })
-        } else if msg.starts_with("!guess ") {
+        } else if let Some(args) = msg.strip_prefix("!analogy ") {
             const SYNTAX_ERROR: GameCommandParseError = GameCommandParseError::SyntaxError {
-                expected: "!thesaurus <word>",
+                expected: "!analogy <word> [+<word>|-<word>...] [<count>]",
             };
 
-            let split: Vec<_> = msg.split(' ').collect();
-            if split.len() != 2 {
+            let mut args: Vec<&str> = args.split_whitespace().collect();
+            let count = match args.last().map(|arg| arg.parse::<usize>()) {
+                Some(Ok(count)) => {
+                    args.pop();
+                    Some(count)
+                }
+                _ => None,
+            };
+            let mut positive = Vec::new();
+            let mut negative = Vec::new();
+            for arg in args {
+                if let Some(word) = arg.strip_prefix('-') {
+                    negative.push(word.to_string());
+                } else {
+                    positive.push(arg.strip_prefix('+').unwrap_or(arg).to_string());
+                }
+            }
+            if positive.is_empty() || positive.iter().chain(&negative).any(String::is_empty) {
                 return Err(SYNTAX_ERROR);
             }
 
-            Ok(GameCommand::Guess {
-                word: split[1].to_string(),
+            Ok(GameCommand::Analogy {
+                positive,
+                negative,
+                count,
             })
+        } else if let Some(args) = msg.strip_prefix("!similarity ") {
+            match args.split_whitespace().collect::<Vec<_>>()[..] {
+                [a, b] => Ok(GameCommand::Similarity {
+                    a: a.to_string(),
+                    b: b.to_string(),
+                }),
+                _ => Err(GameCommandParseError::SyntaxError {
+                    expected: "!similarity <word> <word>",
+                }),
+            }
+        } else if let Some(word) = msg.strip_prefix("!guess ") {
+            // the guess may be a phrase of several words
+            let word = word.trim();
+            if word.is_empty() {
+                return Err(GameCommandParseError::SyntaxError {
+                    expected: "!guess <word>",
+                });
+            }
+
+            Ok(GameCommand::Guess { word: word.to_string() })
+        } else if msg == "!hint" {
+            Ok(GameCommand::Hint)
+        } else if msg == "!giveup" {
+            Ok(GameCommand::GiveUp)
+        } else if msg == "!top" {
+            Ok(GameCommand::Top)
+        } else if msg == "!guesses" {
+            Ok(GameCommand::Guesses { count: None })
+        } else if let Some(count) = msg.strip_prefix("!guesses ") {
+            match count.trim().parse::<usize>() {
+                Ok(count) => Ok(GameCommand::Guesses { count: Some(count) }),
+                Err(_) => Err(GameCommandParseError::SyntaxError {
+                    expected: "!guesses [<count>]",
+                }),
+            }
+        } else if msg == "!leaderboard" {
+            Ok(GameCommand::Leaderboard)
+        } else if msg == "!share" {
+            Ok(GameCommand::Share { nick: None })
+        } else if let Some(nick) = msg.strip_prefix("!share ") {
+            match nick.split_whitespace().collect::<Vec<_>>()[..] {
+                [nick] => Ok(GameCommand::Share {
+                    nick: Some(nick.to_string()),
+                }),
+                _ => Err(GameCommandParseError::SyntaxError {
+                    expected: "!share [<nick>]",
+                }),
+            }
+        } else if msg == "!stats" {
+            Ok(GameCommand::Stats { nick: None })
+        } else if let Some(nick) = msg.strip_prefix("!stats ") {
+            match nick.split_whitespace().collect::<Vec<_>>()[..] {
+                [nick] => Ok(GameCommand::Stats {
+                    nick: Some(nick.to_string()),
+                }),
+                _ => Err(GameCommandParseError::SyntaxError {
+                    expected: "!stats [<nick>]",
+                }),
+            }
+        } else if let Some(args) = msg.strip_prefix("!team ") {
+            match args.split_whitespace().collect::<Vec<_>>()[..] {
+                ["join", team] => Ok(GameCommand::JoinTeam { team: team.to_string() }),
+                ["leave"] => Ok(GameCommand::LeaveTeam),
+                _ => Err(GameCommandParseError::SyntaxError {
+                    expected: "!team join <team> | !team leave",
+                }),
+            }
+        } else if msg == "!teams" {
+            Ok(GameCommand::Teams)
+        } else if let Some(args) = msg.strip_prefix("!alias ") {
+            match args.split_whitespace().collect::<Vec<_>>()[..] {
+                ["add", nick, alias] => Ok(GameCommand::AddAlias {
+                    nick: nick.to_string(),
+                    alias: alias.to_string(),
+                }),
+                _ => Err(GameCommandParseError::SyntaxError {
+                    expected: "!alias add <nick> <alias>",
+                }),
+            }
+        } else if let Some(args) = msg.strip_prefix("!merge ") {
+            match args.split_whitespace().collect::<Vec<_>>()[..] {
+                [from, into] => Ok(GameCommand::Merge {
+                    from: from.to_string(),
+                    into: into.to_string(),
+                }),
+                _ => Err(GameCommandParseError::SyntaxError {
+                    expected: "!merge <merged nick> <kept nick>",
+                }),
+            }
+        } else if let Some(nick) = msg.strip_prefix("!forget ") {
+            match nick.split_whitespace().collect::<Vec<_>>()[..] {
+                [nick] => Ok(GameCommand::Forget { nick: nick.to_string() }),
+                _ => Err(GameCommandParseError::SyntaxError {
+                    expected: "!forget <nick>",
+                }),
+            }
+        } else if msg == "!backup" {
+            Ok(GameCommand::Backup)
+        } else if msg == "!end" {
+            Ok(GameCommand::End)
+        } else if let Some(word) = msg.strip_prefix("!setword ") {
+            let word = word.trim();
+            if word.is_empty() {
+                return Err(GameCommandParseError::SyntaxError {
+                    expected: "!setword <word>",
+                });
+            }
+
+            Ok(GameCommand::SetWord { word: word.to_string() })
+        } else if msg == "!practice" {
+            Ok(GameCommand::StartPractice)
+        } else if let Some(args) = msg.strip_prefix("!practice ") {
+            match args.trim() {
+                "stop" => Ok(GameCommand::StopPractice),
+                _ => Err(GameCommandParseError::SyntaxError {
+                    expected: "!practice [stop]",
+                }),
+            }
+        } else if let Some(args) = msg.strip_prefix("!reverse ") {
+            let mut args = args.split_whitespace();
+            match (args.next(), args.next()) {
+                (Some("stop"), None) => Ok(GameCommand::StopReverse),
+                (Some(arg), None) => match arg.parse::<f32>() {
+                    Ok(similarity) if similarity.is_finite() => Ok(GameCommand::ReverseReport { similarity }),
+                    _ => Ok(GameCommand::StartReverse { word: arg.to_string() }),
+                },
+                _ => Err(GameCommandParseError::SyntaxError {
+                    expected: "!reverse <word>|<similarity>|stop",
+                }),
+            }
+        } else if msg == "!tournament" {
+            Ok(GameCommand::Tournament)
+        } else if let Some(args) = msg.strip_prefix("!tournament ") {
+            const SYNTAX_ERROR: GameCommandParseError = GameCommandParseError::SyntaxError {
+                expected: "!tournament [join | start [<days>] [<name>]]",
+            };
+
+            let args = args.trim();
+            if args == "join" {
+                Ok(GameCommand::JoinTournament)
+            } else if args == "start" {
+                Ok(GameCommand::StartTournament { days: None, name: None })
+            } else if let Some(args) = args.strip_prefix("start ") {
+                // the name may contain spaces
+                let args = args.trim_start();
+                let (first, rest) = args.split_once(' ').unwrap_or((args, ""));
+                match first.parse::<u64>() {
+                    Ok(0) => Err(SYNTAX_ERROR),
+                    Ok(days) => Ok(GameCommand::StartTournament {
+                        days: Some(days),
+                        name: Some(rest.trim().to_string()).filter(|name| !name.is_empty()),
+                    }),
+                    Err(_) => Ok(GameCommand::StartTournament {
+                        days: None,
+                        name: Some(args.to_string()),
+                    }),
+                }
+            } else {
+                Err(SYNTAX_ERROR)
+            }
         } else if msg == "!halp" {
             Ok(GameCommand::Halp)
         } else if msg == "!start" {
-            Ok(GameCommand::Start)
+            Ok(GameCommand::Start {
+                mode: GameMode::Semantic,
+                difficulty: None,
+                language: None,
+                strict: false,
+                budget: None,
+                theme: None,
+            })
+        } else if let Some(args) = msg.strip_prefix("!start ") {
+            const SYNTAX_ERROR: GameCommandParseError = GameCommandParseError::SyntaxError {
+                expected: concat!(
+                    "!start [semantic|wordle|rank|blitz] [easy|normal|hard] [strict] [coop[=<guesses>]] ",
+                    "[theme=<theme>] [<language>]"
+                ),
+            };
+
+            // the mode, the difficulty, hard mode, cooperative mode, the theme and the language are all optional
+            let mut mode = None;
+            let mut difficulty = None;
+            let mut language = None;
+            let mut strict = false;
+            let mut budget = None;
+            let mut theme = None;
+            for arg in args.split_whitespace() {
+                if arg == "strict" && !strict {
+                    strict = true;
+                } else if arg == "coop" && budget.is_none() {
+                    budget = Some(DEFAULT_GUESS_BUDGET);
+                } else if let (None, Some(guesses)) = (budget, arg.strip_prefix("coop=")) {
+                    match guesses.parse() {
+                        Ok(guesses) if guesses > 0 => budget = Some(guesses),
+                        _ => return Err(SYNTAX_ERROR),
+                    }
+                } else if let (None, Some(name)) = (&theme, arg.strip_prefix("theme=")) {
+                    if name.is_empty() {
+                        return Err(SYNTAX_ERROR);
+                    }
+                    theme = Some(name.to_string());
+                } else if let (None, Ok(m)) = (mode, arg.parse()) {
+                    mode = Some(m);
+                } else if let (None, Ok(d)) = (difficulty, arg.parse()) {
+                    difficulty = Some(d);
+                } else if language.is_none() {
+                    language = Some(arg.to_string());
+                } else {
+                    return Err(SYNTAX_ERROR);
+                }
+            }
+            Ok(GameCommand::Start {
+                mode: mode.unwrap_or_default(),
+                difficulty,
+                language,
+                strict,
+                budget,
+                theme,
+            })
         } else {
             Err(GameCommandParseError::Unrecognized)
         }
     }
 }
 
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Formatting
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Formats Wordle feedback: the letters of the guess on IRC color backgrounds, followed by the
+/// same feedback as emoji for clients that don't show colors.
+fn format_letter_hints(guess: &str, hints: &[LetterHint]) -> String {
+    let mut letters = String::new();
+    let mut squares = String::new();
+    for (letter, hint) in guess.chars().zip(hints) {
+        // mIRC color codes: foreground,background
+        let (color, square) = match hint {
+            LetterHint::Correct => ("00,03", '🟩'),
+            LetterHint::Present => ("01,08", '🟨'),
+            LetterHint::Absent => ("00,14", '⬜'),
+        };
+        letters.push_str(&format!("\x03{} {} \x03", color, letter.to_uppercase()));
+        squares.push(square);
+    }
+    format!("{} {}", letters, squares)
+}
+
+/// Formats words with their similarity, e.g. the result of `!thesaurus`.
+fn format_neighbors(neighbors: &[(String, f32)]) -> String {
+    if neighbors.is_empty() {
+        return "no results".to_string();
+    }
+    neighbors
+        .iter()
+        .map(|(word, similarity)| format!("{} ({:.3})", word, similarity))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Number of players listed by `!top` and `!leaderboard`.
+const LEADERBOARD_SIZE: usize = 5;
+
+/// Number of guesses shown by `!guesses` if no count is given.
+const DEFAULT_GUESS_COUNT: usize = 10;
+
+/// Maximum number of guesses shown by `!guesses`.
+const MAX_GUESS_COUNT: usize = 30;
+
+/// Reply to `!top` and `!guesses` in a channel, once the word was found in late solver mode.
+const LATE_SOLVING_REPLY: &str = "the word was found! the guesses will be shown when the game is over";
+
+/// Formats the best guesses of the players of a session (`!top`).
+fn format_leaderboard(entries: &[LeaderboardEntry]) -> String {
+    entries
+        .iter()
+        .take(LEADERBOARD_SIZE)
+        .enumerate()
+        .map(|(i, entry)| match entry.rank {
+            Some(rank) => format!(
+                "{}. {}: {} ({:.3}, rank {})",
+                i + 1,
+                entry.nick,
+                entry.guess,
+                entry.similarity,
+                rank
+            ),
+            None => format!("{}. {}: {} ({:.3})", i + 1, entry.nick, entry.guess, entry.similarity),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats a miss (semantic mode) with its heat bar, telling whether it's warmer or colder than the best previous
+/// guess of the player.
+fn format_miss(distance: f32, rank: Option<usize>, best: Option<f32>, heat: f32) -> String {
+    let mut reply = match rank {
+        Some(rank) => format!("miss ({}, rank {}/{}) {}", distance, rank, NEAREST_RANKS, heat::emoji_bar(heat)),
+        None => format!(
+            "miss ({}, not in the top {}) {}",
+            distance,
+            NEAREST_RANKS,
+            heat::emoji_bar(heat)
+        ),
+    };
+    match best {
+        Some(best) if distance > best => reply.push_str(&format!(", warmer! {:.2} → {:.2}", best, distance)),
+        Some(best) if distance < best => reply.push_str(&format!(", colder than your best ({:.2})", best)),
+        _ => {}
+    }
+    reply
+}
+
+/// Formats the last guesses of a session, the most recent first (`!guesses`).
+fn format_guesses(guesses: &[GuessRecord]) -> String {
+    guesses
+        .iter()
+        .map(|guess| format!("{}: {} ({:.2})", guess.nick, guess.guess, guess.distance))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats the overall results of the best players (`!leaderboard`).
+fn format_player_stats(players: &[PlayerStats]) -> String {
+    players
+        .iter()
+        .take(LEADERBOARD_SIZE)
+        .enumerate()
+        .map(|(i, stats)| {
+            let season = match stats.season_score {
+                Some(score) => format!("{} this season, ", score),
+                None => String::new(),
+            };
+            format!(
+                "{}. {}: {} points ({}{} wins in {} games)",
+                i + 1,
+                stats.player.nick,
+                stats.player.score,
+                season,
+                stats.wins,
+                stats.games
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats the overall results of the best teams (`!teams`).
+fn format_team_stats(teams: &[TeamStats]) -> String {
+    teams
+        .iter()
+        .take(LEADERBOARD_SIZE)
+        .enumerate()
+        .map(|(i, team)| {
+            format!(
+                "{}. {}: {} points ({} wins, {} members)",
+                i + 1,
+                team.name,
+                team.score,
+                team.wins,
+                team.members
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats the final standings of a season.
+fn format_season_standings(standings: &[Player]) -> String {
+    match standings.split_first() {
+        Some((winner, rest)) => {
+            let mut announcement = format!("the season is over! winner: {} ({} points)", winner.nick, winner.score);
+            if !rest.is_empty() {
+                let others = rest
+                    .iter()
+                    .take(2)
+                    .map(|player| format!("{} ({})", player.nick, player.score))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                announcement.push_str(&format!(", then {}", others));
+            }
+            announcement.push_str(". a new season begins");
+            announcement
+        }
+        None => "the season is over, nobody scored. a new season begins".to_string(),
+    }
+}
+
+/// Formats the best players of a tournament and their tournament points.
+fn format_tournament_ranking(standings: &[Player]) -> String {
+    standings
+        .iter()
+        .take(5)
+        .enumerate()
+        .map(|(i, player)| format!("{}. {} ({})", i + 1, player.nick, player.score))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats the standings of a tournament in progress.
+fn format_tournament_standings(name: &str, standings: &[Player]) -> String {
+    if standings.is_empty() {
+        format!("tournament \"{}\": nobody registered yet, join with !tournament join", name)
+    } else {
+        format!("tournament \"{}\": {}", name, format_tournament_ranking(standings))
+    }
+}
+
+/// Formats the final standings of a tournament.
+fn format_tournament_results(name: &str, standings: &[Player]) -> String {
+    match standings.first() {
+        Some(winner) if winner.score > 0 => format!(
+            "the tournament \"{}\" is over! winner: {} ({} points). final standings: {}",
+            name,
+            winner.nick,
+            winner.score,
+            format_tournament_ranking(standings)
+        ),
+        _ => format!("the tournament \"{}\" is over, nobody scored", name),
+    }
+}
+
+/// Formats the detailed results of a player (`!stats`).
+fn format_profile(profile: &PlayerProfile) -> String {
+    let stats = &profile.stats;
+    let mut reply = format!(
+        "{}: {} points, {} wins in {} games",
+        stats.player.nick, stats.player.score, stats.wins, stats.games
+    );
+    if let Some(score) = stats.season_score {
+        reply.push_str(&format!(" ({} this season)", score));
+    }
+    reply.push_str(&format!(
+        ", rating {:.0}",
+        profile.rating.unwrap_or(rating::INITIAL_RATING)
+    ));
+    if let Some(average) = profile.average_guesses_to_win {
+        reply.push_str(&format!(", {:.1} guesses per win", average));
+    }
+    if let Some(similarity) = profile.best_similarity {
+        reply.push_str(&format!(", closest miss {:.3}", similarity));
+    }
+    if profile.streak > 0 {
+        reply.push_str(&format!(", {} wins in a row", profile.streak));
+    }
+    if !profile.achievements.is_empty() {
+        let names: Vec<&str> = profile
+            .achievements
+            .iter()
+            .map(|unlocked| unlocked.achievement.name())
+            .collect();
+        reply.push_str(&format!(", achievements: {}", names.join(", ")));
+    }
+    reply
+}
+
+/// Formats the next move of the bot in a reverse game.
+fn format_reverse_guess(guess: ReverseGuess) -> String {
+    match guess {
+        ReverseGuess::Guess { word, number } => format!("guess #{}: is it \"{}\"?", number, word),
+        ReverseGuess::Found { word, guesses } => format!("your word is \"{}\"! found in {} guesses", word, guesses),
+        ReverseGuess::GaveUp { guesses } => format!("I give up after {} guesses, you win!", guesses),
+    }
+}
+
+/// Formats a hint, rephrased by `hinter` if there's one. The plain hint is used if it can't be rephrased.
+async fn format_hint(hint: Hint, hinter: Option<&Arc<LlmHinter>>) -> String {
+    let text = match hinter {
+        Some(hinter) => {
+            let hinter = hinter.clone();
+            let text = hint.text.clone();
+            match tokio::task::spawn_blocking(move || hinter.phrase(&text)).await {
+                Ok(phrased) => phrased,
+                Err(err) => {
+                    warn!("could not rephrase hint, using plain hint: {}", err);
+                    hint.text
+                }
+            }
+        }
+        None => hint.text,
+    };
+    let theme = match hint.theme {
+        Some(theme) => format!("theme \"{}\", ", theme),
+        None => String::new(),
+    };
+    format!(
+        "hint {}/{}: {}{} (-{} points for the winner)",
+        hint.number, hint.count, theme, text, hint.penalty
+    )
+}
+
+/// Formats the end of a session with `!end`: the word, the closest guess and the leaderboard of the session.
+fn format_ended_session(ended: &EndedSession) -> String {
+    let mut reply = match ended.definition {
+        Some(ref definition) => format!("game over! the word was \"{}\": {}", ended.word, definition),
+        None => format!("game over! the word was \"{}\"", ended.word),
+    };
+    match ended.leaderboard.first() {
+        Some(closest) => {
+            reply.push_str(&format!(
+                "; closest guess: \"{}\" by {}; best guesses: {}",
+                closest.guess,
+                closest.nick,
+                format_leaderboard(&ended.leaderboard)
+            ));
+        }
+        None => reply.push_str("; nobody made a guess"),
+    }
+    reply
+}
+
+/// Whether the sender of a message is an owner of the bot, allowed to use the admin commands.
+///
+/// The owners are `nick!user@host` masks, e.g. `alice!*@user/alice`: anyone can take the nick of an owner who is
+/// offline, but not their cloak or host.
+fn is_owner(owners: &[String], message: &Message) -> bool {
+    match message.prefix {
+        Some(Prefix::Nickname(ref nick, ref user, ref host)) => {
+            let source = format!("{}!{}@{}", nick, user, host);
+            owners.iter().any(|owner| matches_mask(owner, &source))
+        }
+        _ => false,
+    }
+}
+
+/// Whether the source of a message matches an IRC mask, where `*` matches any characters and `?` any single one.
+/// Case-insensitive.
+fn matches_mask(mask: &str, source: &str) -> bool {
+    let mask: Vec<char> = mask.to_lowercase().chars().collect();
+    let source: Vec<char> = source.to_lowercase().chars().collect();
+    let (mut m, mut s) = (0, 0);
+    // last `*` of the mask, and the position in the source it matches up to
+    let mut star = None;
+    while s < source.len() {
+        if m < mask.len() && (mask[m] == '?' || mask[m] == source[s]) {
+            m += 1;
+            s += 1;
+        } else if m < mask.len() && mask[m] == '*' {
+            star = Some((m, s));
+            m += 1;
+        } else if let Some((star_m, star_s)) = star {
+            // let the `*` match one more character
+            m = star_m + 1;
+            s = star_s + 1;
+            star = Some((star_m, s));
+        } else {
+            return false;
+        }
+    }
+    mask[m..].iter().all(|&c| c == '*')
+}
+
+/// Reply to a command that failed.
+fn error_reply(err: &Error) -> String {
+    match err.downcast_ref::<StillLoading>() {
+        Some(loading) => loading.to_string(),
+        None => format!("something went wrong (`{}`)", err),
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Handler
 ////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -93,12 +731,25 @@ impl IrcSenderExt for Sender {
 
 const AWAKE_SECS: u64 = 15;
 
-pub async fn irc_handler(words: Arc<Words>, game: Game) -> Result<(), Error> {
+/// Interval between two checks of whether the word model is loaded, to announce it.
+const MODEL_READY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+pub async fn irc_handler(
+    words: SharedWords,
+    game: Game,
+    mut notices: UnboundedReceiver<SessionNotice>,
+    game_duration: Duration,
+    giveup_quorum: usize,
+    hinter: Option<Arc<LlmHinter>>,
+    announce_model_ready: bool,
+) -> Result<(), Error> {
     // load IRC config
     let config = Config::load("ircconf.toml").expect("failed to load `ircconf.toml`");
 
     // Create IRC client
     let self_name = config.nickname.clone().unwrap_or("cabotin".to_string());
+    // `nick!user@host` masks of the users allowed to use the admin commands
+    let owners = config.owners.clone();
     let mut client = Client::from_config(config).await?;
     client.identify()?;
 
@@ -107,80 +758,651 @@ pub async fn irc_handler(words: Arc<Words>, game: Game) -> Result<(), Error> {
 
     let mut last_wakeup = Instant::now();
 
+    // if the model is still loading, check regularly whether it's ready to announce it
+    let mut announce_pending = announce_model_ready && words.get().is_err();
+    let mut ready_check = tokio::time::interval(MODEL_READY_CHECK_INTERVAL);
+
     // process messages
-    while let Some(message) = stream.next().await.transpose()? {
+    loop {
+        let message = tokio::select! {
+            message = stream.next() => match message.transpose()? {
+                Some(message) => message,
+                None => break,
+            },
+            _ = ready_check.tick(), if announce_pending => {
+                // wait until the channels are joined
+                let channels = client.list_channels().unwrap_or_default();
+                if words.get().is_ok() && !channels.is_empty() {
+                    for channel in channels {
+                        sender.say(channel, "word model loaded, ready to play");
+                    }
+                    announce_pending = false;
+                }
+                continue;
+            }
+            Some(notice) = notices.recv() => {
+                let announcement = match notice {
+                    SessionNotice::Expired {
+                        word,
+                        definition: Some(definition),
+                    } => format!("time's up! the word was \"{}\": {}", word, definition),
+                    SessionNotice::Expired { word, definition: None } => {
+                        format!("time's up! the word was \"{}\"", word)
+                    }
+                    SessionNotice::Started { theme: Some(theme) } => {
+                        format!("a new game has started, theme: \"{}\"", theme)
+                    }
+                    SessionNotice::Started { theme: None } => "a new game has started".to_string(),
+                    SessionNotice::Recap { word, definition, winners } => {
+                        let definition = definition.map(|d| format!(": {}", d)).unwrap_or_default();
+                        format!("game over! the word was \"{}\"{}, found by {}", word, definition, winners.join(", "))
+                    }
+                    SessionNotice::Hint { hint } => {
+                        format!("nobody found the word yet, {}", format_hint(hint, hinter.as_ref()).await)
+                    }
+                    SessionNotice::GuessesLeft { remaining: 1 } => "only one guess left!".to_string(),
+                    SessionNotice::GuessesLeft { remaining } => format!("{} guesses left", remaining),
+                    SessionNotice::SeasonEnded { standings } => format_season_standings(&standings),
+                    SessionNotice::TournamentStandings { name, day, standings } => {
+                        format!("day {}, {}", day, format_tournament_standings(&name, &standings))
+                    }
+                    SessionNotice::TournamentEnded { name, standings } => format_tournament_results(&name, &standings),
+                    SessionNotice::AchievementUnlocked { nick, achievement } => format!(
+                        "{} unlocked the achievement \"{}\": {}",
+                        nick,
+                        achievement.name(),
+                        achievement.description()
+                    ),
+                };
+                for channel in client.list_channels().unwrap_or_default() {
+                    sender.say(channel, &announcement);
+                }
+                continue;
+            }
+        };
         //trace!("{}", message);
 
-        match message.command {
-            Command::PRIVMSG(ref target, ref msg) => {
-                let mut guess = None;
+        if let Command::PRIVMSG(ref target, ref msg) = message.command {
+            let mut guess = None;
+            // `!guess` in private plays in the session, when several players can find its word
+            let mut session_guess = false;
 
-                let msg = msg.trim();
+            // private messages are for practice games, and are answered privately
+            let private = !target.is_channel_name();
+            let target = message.response_target().unwrap_or(target);
 
-                if msg == self_name {
-                    trace!("bot wakeup");
-                    last_wakeup = Instant::now();
-                    sender.say(target, "oui?");
-                } else if msg.split_whitespace().count() == 1 {
-                    let now = Instant::now();
-                    if now.duration_since(last_wakeup).as_secs() < AWAKE_SECS {
-                        // single word & still awake, consider that a guess
-                        last_wakeup = now;
-                        guess = Some(msg.to_string());
+            let msg = msg.trim();
+
+            if msg == self_name {
+                trace!("bot wakeup");
+                last_wakeup = Instant::now();
+                sender.say(target, "oui?");
+            } else if private && !msg.starts_with('!') && msg.split_whitespace().count() == 1 {
+                // no need to wake the bot up in private
+                guess = Some(msg.to_string());
+            } else if !msg.starts_with('!') && msg.split_whitespace().count() == 1 {
+                let now = Instant::now();
+                if now.duration_since(last_wakeup).as_secs() < AWAKE_SECS {
+                    // single word & still awake, consider that a guess
+                    last_wakeup = now;
+                    guess = Some(msg.to_string());
+                }
+            } else {
+                match GameCommand::parse(msg) {
+                    Ok(GameCommand::Thesaurus { word, count }) => {
+                        let result = match words.get() {
+                            Ok(words) => {
+                                let words = words.clone();
+                                tokio::task::spawn_blocking(move || {
+                                    let word = words.normalize_guess(&word);
+                                    match words.thesaurus(&word, count.unwrap_or(1)) {
+                                        Some(neighbors) => format_neighbors(&neighbors),
+                                        None => "unknown word".to_string(),
+                                    }
+                                })
+                                .await?
+                            }
+                            Err(loading) => loading.to_string(),
+                        };
+                        sender.say(target, result);
                     }
-                } else {
-                    match GameCommand::parse(msg) {
-                        Ok(GameCommand::Thesaurus { word, count }) => {
-                            // this query may take some time and block the bot, but it's more like a feature really
-                            let result = words.thesaurus(&word, count.unwrap_or(1));
-                            sender.say(target, result);
-                        }
-                        Ok(GameCommand::Guess { word }) => {
-                            guess = Some(word);
-                        }
-                        Ok(GameCommand::Start) => {
-                            let reply = match game.start_game(Duration::from_secs(3600 * 24)).await {
-                                Ok(_) => "game started".to_string(),
-                                Err(err) => {
-                                    format!("something went wrong (`{}`)", err)
+                    Ok(GameCommand::Analogy {
+                        positive,
+                        negative,
+                        count,
+                    }) => {
+                        let result = match words.get() {
+                            Ok(words) => {
+                                let words = words.clone();
+                                tokio::task::spawn_blocking(move || {
+                                    let positive: Vec<String> =
+                                        positive.iter().map(|word| words.normalize_guess(word)).collect();
+                                    let negative: Vec<String> =
+                                        negative.iter().map(|word| words.normalize_guess(word)).collect();
+                                    let positive: Vec<&str> = positive.iter().map(String::as_str).collect();
+                                    let negative: Vec<&str> = negative.iter().map(String::as_str).collect();
+                                    match words.analogy(&positive, &negative, count.unwrap_or(1)) {
+                                        Ok(results) => format_neighbors(&results),
+                                        Err(err) => err.to_string(),
+                                    }
+                                })
+                                .await?
+                            }
+                            Err(loading) => loading.to_string(),
+                        };
+                        sender.say(target, result);
+                    }
+                    Ok(GameCommand::Similarity { a, b }) => {
+                        let result = match words.get() {
+                            Ok(words) => {
+                                let words = words.clone();
+                                tokio::task::spawn_blocking(move || {
+                                    let (a, b) = (words.normalize_guess(&a), words.normalize_guess(&b));
+                                    match words.similarity(&a, &b) {
+                                        Ok(similarity) => format!("{} ~ {}: {}", a, b, similarity),
+                                        Err(err) => err.to_string(),
+                                    }
+                                })
+                                .await?
+                            }
+                            Err(loading) => loading.to_string(),
+                        };
+                        sender.say(target, result);
+                    }
+                    Ok(GameCommand::Guess { word }) => {
+                        session_guess = private && game.several_winners().await;
+                        guess = Some(word);
+                    }
+                    Ok(GameCommand::Start {
+                        mode,
+                        difficulty,
+                        language,
+                        strict,
+                        budget,
+                        theme,
+                    }) => {
+                        let options = SessionOptions {
+                            mode,
+                            difficulty,
+                            language,
+                            strict,
+                            budget,
+                            theme: theme.clone(),
+                            ..Default::default()
+                        };
+                        let reply = match game.start_game(game_duration, options).await {
+                            Ok(_) => {
+                                let mut reply = "game started".to_string();
+                                if mode == GameMode::Blitz {
+                                    reply.push_str(&format!(
+                                        ", blitz: {} minutes to find the word, double points",
+                                        BLITZ_DURATION.as_secs() / 60
+                                    ));
+                                }
+                                if strict {
+                                    reply.push_str(", hard mode: each guess must beat your best");
                                 }
+                                if let Some(budget) = budget {
+                                    reply.push_str(&format!(", cooperative mode: {} guesses for everyone", budget));
+                                }
+                                if let Some(theme) = theme {
+                                    reply.push_str(&format!(", theme: \"{}\"", theme.to_lowercase()));
+                                }
+                                reply
+                            }
+                            Err(err) => error_reply(&err),
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::Hint) => {
+                        let reply = match game.hint().await {
+                            Ok(hint) => format_hint(hint, hinter.as_ref()).await,
+                            Err(err) => error_reply(&err),
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::GiveUp) => {
+                        if let Some(nick) = message.source_nickname() {
+                            let reply = match game.give_up(nick.to_string(), giveup_quorum).await {
+                                Ok(GiveUpVote::Counted { votes, required }) => format!(
+                                    "{} votes to give up ({}/{} votes, say !giveup to agree)",
+                                    nick, votes, required
+                                ),
+                                Ok(GiveUpVote::AlreadyVoted { votes, required }) => {
+                                    format!("you already voted to give up ({}/{} votes)", votes, required)
+                                }
+                                Ok(GiveUpVote::Ended {
+                                    word,
+                                    definition: Some(definition),
+                                }) => format!("you gave up! the word was \"{}\": {}", word, definition),
+                                Ok(GiveUpVote::Ended { word, definition: None }) => {
+                                    format!("you gave up! the word was \"{}\"", word)
+                                }
+                                Err(err) => error_reply(&err),
+                            };
+                            sender.say(target, reply);
+                        }
+                    }
+                    Ok(GameCommand::Top) if !private && game.late_solving().await => {
+                        sender.say(target, LATE_SOLVING_REPLY);
+                    }
+                    Ok(GameCommand::Guesses { .. }) if !private && game.late_solving().await => {
+                        sender.say(target, LATE_SOLVING_REPLY);
+                    }
+                    Ok(GameCommand::Top) => {
+                        let reply = match game.session_leaderboard().await {
+                            Ok(entries) if entries.is_empty() => "no guesses yet".to_string(),
+                            Ok(entries) => format_leaderboard(&entries),
+                            Err(err) => error_reply(&err),
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::Guesses { count }) => {
+                        let count = count.unwrap_or(DEFAULT_GUESS_COUNT).min(MAX_GUESS_COUNT);
+                        let reply = match game.recent_session_id().await {
+                            Some(session_id) => match game.recent_guesses(session_id, count).await {
+                                Ok(guesses) if guesses.is_empty() => "no guesses yet".to_string(),
+                                Ok(guesses) => format_guesses(&guesses),
+                                Err(err) => error_reply(&err),
+                            },
+                            None => "there's no game in progress".to_string(),
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::Leaderboard) => {
+                        let reply = match game.leaderboard().await {
+                            Ok(players) if players.is_empty() => "no players yet".to_string(),
+                            Ok(players) => format_player_stats(&players),
+                            Err(err) => error_reply(&err),
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::Share { nick }) => {
+                        let nick = nick.or_else(|| message.source_nickname().map(str::to_string));
+                        if let Some(nick) = nick {
+                            let reply = match game.share(nick.clone()).await {
+                                Ok(Some(share)) => share,
+                                Ok(None) => format!("{} has not won a game yet", nick),
+                                Err(err) => error_reply(&err),
                             };
                             sender.say(target, reply);
                         }
-                        Ok(GameCommand::Halp) => {
-                            sender.say(target, "coming soon");
+                    }
+                    Ok(GameCommand::Stats { nick }) => {
+                        let nick = nick.or_else(|| message.source_nickname().map(str::to_string));
+                        if let Some(nick) = nick {
+                            let reply = match game.player_stats(nick.clone()).await {
+                                Ok(Some(profile)) => format_profile(&profile),
+                                Ok(None) => format!("{} has never played", nick),
+                                Err(err) => error_reply(&err),
+                            };
+                            sender.say(target, reply);
+                        }
+                    }
+                    Ok(GameCommand::JoinTeam { team }) => {
+                        if let Some(nick) = message.source_nickname() {
+                            let reply = match game.join_team(nick.to_string(), team).await {
+                                Ok(team) => format!("{} joined team {}", nick, team),
+                                Err(err) => error_reply(&err),
+                            };
+                            sender.say(target, reply);
+                        }
+                    }
+                    Ok(GameCommand::LeaveTeam) => {
+                        if let Some(nick) = message.source_nickname() {
+                            let reply = match game.leave_team(nick.to_string()).await {
+                                Ok(true) => format!("{} left their team", nick),
+                                Ok(false) => format!("{} is not in a team", nick),
+                                Err(err) => error_reply(&err),
+                            };
+                            sender.say(target, reply);
                         }
-                        Err(err) => {
-                            match err {
-                                GameCommandParseError::Unrecognized => {
-                                    // the message was not meant for us
+                    }
+                    Ok(GameCommand::Teams) => {
+                        let reply = match game.team_leaderboard().await {
+                            Ok(teams) if teams.is_empty() => "no teams yet".to_string(),
+                            Ok(teams) => format_team_stats(&teams),
+                            Err(err) => error_reply(&err),
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::AddAlias { nick, alias }) => {
+                        let reply = if !is_owner(&owners, &message) {
+                            "only the owners of the bot can add aliases".to_string()
+                        } else {
+                            match game.add_alias(nick.clone(), alias.clone()).await {
+                                Ok(()) => format!("{} is now an alias of {}", alias, nick),
+                                Err(err) => error_reply(&err),
+                            }
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::Merge { from, into }) => {
+                        let reply = if !is_owner(&owners, &message) {
+                            "only the owners of the bot can merge players".to_string()
+                        } else {
+                            match game.merge_players(from.clone(), into.clone()).await {
+                                Ok(()) => format!("{} merged into {}", from, into),
+                                Err(err) => error_reply(&err),
+                            }
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::Forget { nick }) => {
+                        let reply = if !is_owner(&owners, &message) {
+                            "only the owners of the bot can forget players".to_string()
+                        } else {
+                            match game.forget_player(nick.clone()).await {
+                                Ok(true) => format!("{} has been forgotten", nick),
+                                Ok(false) => format!("{} has never played", nick),
+                                Err(err) => error_reply(&err),
+                            }
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::Backup) => {
+                        let reply = if !is_owner(&owners, &message) {
+                            "only the owners of the bot can back up the database".to_string()
+                        } else {
+                            match game.backup().await {
+                                Ok(path) => format!("database backed up to `{}`", path),
+                                Err(err) => error_reply(&err),
+                            }
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::End) => {
+                        if !is_owner(&owners, &message) {
+                            sender.say(target, "only the owners of the bot can end the game");
+                        } else {
+                            match game.end_and_reveal().await {
+                                Ok(ended) => {
+                                    // announced everywhere, as the command may come from a private message
+                                    let announcement = format_ended_session(&ended);
+                                    let mut channels = client.list_channels().unwrap_or_default();
+                                    if private {
+                                        channels.push(target.to_string());
+                                    }
+                                    for channel in channels {
+                                        sender.say(channel, &announcement);
+                                    }
                                 }
-                                GameCommandParseError::SyntaxError { expected } => {
-                                    sender.say(target, format!("syntax error: {}", expected));
+                                Err(err) => sender.say(target, error_reply(&err)),
+                            }
+                        }
+                    }
+                    Ok(GameCommand::SetWord { word }) => {
+                        let reply = if !is_owner(&owners, &message) {
+                            "only the owners of the bot can set the word".to_string()
+                        } else if !private {
+                            "send me !setword in a private message, so the word stays secret".to_string()
+                        } else {
+                            let options = SessionOptions {
+                                word: Some(word),
+                                ..Default::default()
+                            };
+                            match game.start_game(game_duration, options).await {
+                                Ok(_) => {
+                                    for channel in client.list_channels().unwrap_or_default() {
+                                        sender.say(channel, "a new game has started");
+                                    }
+                                    "game started with your word".to_string()
                                 }
+                                Err(err) => error_reply(&err),
+                            }
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::StartPractice) if !private => {
+                        sender.say(target, "send me !practice in a private message to start a practice game");
+                    }
+                    Ok(GameCommand::StartPractice) => {
+                        if let Some(nick) = message.source_nickname() {
+                            let reply = match game.start_practice(nick.to_string()).await {
+                                Ok(Some(word)) => format!(
+                                    "the word of your last practice game was \"{}\"; new practice game started, \
+                                     send me your guesses",
+                                    word
+                                ),
+                                Ok(None) => "practice game started, send me your guesses; \
+                                             it doesn't count towards your scores"
+                                    .to_string(),
+                                Err(err) => error_reply(&err),
+                            };
+                            sender.say(target, reply);
+                        }
+                    }
+                    Ok(GameCommand::StopPractice) => {
+                        if let Some(nick) = message.source_nickname() {
+                            let reply = match game.stop_practice(nick.to_string()).await {
+                                Some(word) => format!("practice game over, the word was \"{}\"", word),
+                                None => "you have no practice game in progress".to_string(),
+                            };
+                            sender.say(target, reply);
+                        }
+                    }
+                    Ok(GameCommand::StartReverse { .. }) if !private => {
+                        sender.say(
+                            target,
+                            "send me !reverse <word> in a private message, and I'll try to guess your word",
+                        );
+                    }
+                    Ok(GameCommand::StartReverse { word }) => {
+                        if let Some(nick) = message.source_nickname() {
+                            let reply = match game.start_reverse(nick.to_string(), word).await {
+                                Ok(ReverseGuess::Guess { word, .. }) => format!(
+                                    "my first guess is \"{}\"; tell me how close it is to your word with \
+                                     !reverse <similarity> (!similarity {} <your word> tells you)",
+                                    word, word
+                                ),
+                                Ok(guess) => format_reverse_guess(guess),
+                                Err(err) => error_reply(&err),
+                            };
+                            sender.say(target, reply);
+                        }
+                    }
+                    Ok(GameCommand::ReverseReport { similarity }) => {
+                        if let Some(nick) = message.source_nickname() {
+                            let reply = match game.reverse_report(nick.to_string(), similarity).await {
+                                Ok(guess) => format_reverse_guess(guess),
+                                Err(err) => error_reply(&err),
+                            };
+                            sender.say(target, reply);
+                        }
+                    }
+                    Ok(GameCommand::StopReverse) => {
+                        if let Some(nick) = message.source_nickname() {
+                            let reply = if game.stop_reverse(nick.to_string()).await {
+                                "reverse game over, you win!"
+                            } else {
+                                "you have no reverse game in progress"
+                            };
+                            sender.say(target, reply);
+                        }
+                    }
+                    Ok(GameCommand::Tournament) => {
+                        let reply = match game.tournament_standings().await {
+                            Ok(Some((tournament, standings))) => {
+                                format_tournament_standings(&tournament.name, &standings)
+                            }
+                            Ok(None) => "there's no tournament in progress".to_string(),
+                            Err(err) => error_reply(&err),
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::JoinTournament) => {
+                        if let Some(nick) = message.source_nickname() {
+                            let reply = match game.join_tournament(nick.to_string()).await {
+                                Ok((tournament, true)) => {
+                                    format!("{} joined the tournament \"{}\"", nick, tournament.name)
+                                }
+                                Ok((tournament, false)) => {
+                                    format!("{} is already registered to the tournament \"{}\"", nick, tournament.name)
+                                }
+                                Err(err) => error_reply(&err),
+                            };
+                            sender.say(target, reply);
+                        }
+                    }
+                    Ok(GameCommand::StartTournament { days, name }) => {
+                        let reply = if !is_owner(&owners, &message) {
+                            "only the owners of the bot can start tournaments".to_string()
+                        } else {
+                            let days = days.unwrap_or(DEFAULT_TOURNAMENT_DAYS);
+                            match game.start_tournament(name, days).await {
+                                Ok(tournament) => format!(
+                                    "the tournament \"{}\" has started for {} days! join with !tournament join",
+                                    tournament.name, days
+                                ),
+                                Err(err) => error_reply(&err),
+                            }
+                        };
+                        sender.say(target, reply);
+                    }
+                    Ok(GameCommand::Halp) => {
+                        sender.say(target, "coming soon");
+                    }
+                    Err(err) => {
+                        match err {
+                            GameCommandParseError::Unrecognized => {
+                                // the message was not meant for us
+                            }
+                            GameCommandParseError::SyntaxError { expected } => {
+                                sender.say(target, format!("syntax error: {}", expected));
                             }
                         }
                     }
                 }
+            }
 
-                // handle guess
-                if let Some(guess) = guess {
-                    let nick = message.source_nickname();
-                    if let Some(nick) = nick {
-                        let outcome = game.process_guess(nick.to_string(), guess).await;
-                        let reply = match outcome {
-                            Ok(Outcome::Win) => "you guessed the word".to_string(),
-                            Ok(Outcome::Miss { distance }) => format!("miss ({})", distance),
-                            Ok(Outcome::UnknownWord) => format!("unknown word"),
-                            Err(err) => format!("something went wrong (`{}`)", err),
+            // handle guess
+            if let Some(guess) = guess {
+                let nick = message.source_nickname();
+                // after a win in late solver mode, the game goes on in private messages only
+                let late = game.late_solving().await;
+                if late && !private {
+                    sender.say(
+                        target,
+                        "the word was found! the other players can keep looking for it with !guess in private",
+                    );
+                } else if let Some(nick) = nick {
+                    if late && private && !session_guess {
+                        session_guess = game.is_late_solver(nick).await.unwrap_or_default();
+                    }
+                    let outcome = if private && !session_guess {
+                        game.practice_guess(nick.to_string(), guess).await
+                    } else {
+                        game.process_guess(nick.to_string(), guess).await
+                    };
+                    if let (true, false, Ok(Outcome::Win { place, ended, .. })) = (session_guess, late, &outcome) {
+                        // the word is not revealed to the channels
+                        let announcement = if *ended {
+                            format!("{} found the word (place {}), the game is over!", nick, place)
+                        } else {
+                            format!("{} found the word (place {})!", nick, place)
                         };
-                        sender.say(target.clone(), reply);
+                        for channel in client.list_channels().unwrap_or_default() {
+                            sender.say(channel, &announcement);
+                        }
                     }
+                    let reply = match outcome {
+                        Ok(Outcome::Win { definition: None, .. }) if private && !session_guess => {
+                            "you guessed the word, practice game over".to_string()
+                        }
+                        Ok(Outcome::Win {
+                            definition: Some(definition),
+                            ..
+                        }) if private && !session_guess => {
+                            format!("you guessed the word, practice game over: {}", definition)
+                        }
+                        Ok(Outcome::Win {
+                            points,
+                            place,
+                            ended: false,
+                            ..
+                        }) => format!(
+                            "you guessed the word, place {} (+{} points)! the others are still looking for it",
+                            place, points
+                        ),
+                        Ok(Outcome::Win {
+                            points,
+                            definition: None,
+                            ..
+                        }) => format!("you guessed the word (+{} points)", points),
+                        Ok(Outcome::Win {
+                            points,
+                            definition: Some(definition),
+                            ..
+                        }) => format!("you guessed the word (+{} points): {}", points, definition),
+                        Ok(Outcome::AlreadyFound) => "you already found the word".to_string(),
+                        Ok(Outcome::Miss {
+                            distance,
+                            rank,
+                            best,
+                            heat,
+                        }) => format_miss(distance, rank, best, heat),
+                        Ok(Outcome::RankMiss { rank }) => format!("miss (rank {})", rank),
+                        Ok(Outcome::WordleMiss { guess, hints }) => format_letter_hints(&guess, &hints),
+                        Ok(Outcome::UnknownWord { suggestions }) if suggestions.is_empty() => "unknown word".to_string(),
+                        Ok(Outcome::UnknownWord { suggestions }) => {
+                            format!("unknown word, did you mean: {}?", suggestions.join(", "))
+                        }
+                        Ok(Outcome::WrongLength { expected }) => format!("the word has {} letters", expected),
+                        Ok(Outcome::StopWord) => "too common to be the word".to_string(),
+                        Ok(Outcome::OutOfGuesses {
+                            word,
+                            definition: Some(definition),
+                        }) => format!("no guesses left, you all lost! the word was \"{}\": {}", word, definition),
+                        Ok(Outcome::OutOfGuesses { word, definition: None }) => {
+                            format!("no guesses left, you all lost! the word was \"{}\"", word)
+                        }
+                        Ok(Outcome::NotCloser { rank: Some(rank), .. }) => {
+                            format!("not closer than your best guess (rank {})", rank)
+                        }
+                        Ok(Outcome::NotCloser { best, .. }) => format!("not closer than your best guess ({:.2})", best),
+                        // in private session guesses, another player may have made the guess
+                        Ok(Outcome::AlreadyGuessed {
+                            nick: ref guesser,
+                            distance,
+                            ..
+                        }) if private && guesser == nick => {
+                            format!("you already guessed it ({:.2})", distance)
+                        }
+                        Ok(Outcome::AlreadyGuessed {
+                            nick,
+                            rank: Some(rank),
+                            ..
+                        }) => format!("already guessed by {} (rank {})", nick, rank),
+                        Ok(Outcome::AlreadyGuessed { nick, distance, .. }) => {
+                            format!("already guessed by {} ({:.2})", nick, distance)
+                        }
+                        Err(err) => error_reply(&err),
+                    };
+                    sender.say(target, reply);
                 }
             }
-            _ => (),
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owner_masks() {
+        let owners = vec!["alice!*@user/alice".to_string(), "b?b!bob@*.example.org".to_string()];
+        let from = |prefix: &str| Message::new(Some(prefix), "PRIVMSG", vec!["#chan", "!end"]).unwrap();
+        assert!(is_owner(&owners, &from("alice!~alice@user/alice")));
+        assert!(is_owner(&owners, &from("Alice!a@USER/alice")));
+        assert!(is_owner(&owners, &from("bob!bob@host.example.org")));
+        assert!(is_owner(&owners, &from("bib!bob@a.b.example.org")));
+        // only the nick of an owner
+        assert!(!is_owner(&owners, &from("alice!alice@example.org")));
+        assert!(!is_owner(&owners, &from("bob!bob@example.org")));
+        assert!(!is_owner(&owners, &from("alice")));
+        assert!(!is_owner(&[], &from("alice!~alice@user/alice")));
+    }
+}
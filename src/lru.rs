@@ -0,0 +1,49 @@
+//! Small least-recently-used cache
+//!
+//! Meant for a few hundred entries: evicting an entry scans the whole cache.
+use std::{borrow::Borrow, collections::HashMap, hash::Hash};
+
+pub struct LruCache<K, V> {
+    capacity: usize,
+    /// Values, with the time of their last use.
+    entries: HashMap<K, (V, u64)>,
+    /// Incremented on each use of an entry.
+    clock: u64,
+}
+
+impl<K: Hash + Eq, V: Clone> LruCache<K, V> {
+    pub fn new(capacity: usize) -> LruCache<K, V> {
+        LruCache {
+            capacity,
+            entries: HashMap::with_capacity(capacity),
+            clock: 0,
+        }
+    }
+
+    /// Returns a copy of the value of a key, if it's cached, and marks it as recently used.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.clock += 1;
+        let (value, last_use) = self.entries.get_mut(key)?;
+        *last_use = self.clock;
+        Some(value.clone())
+    }
+
+    /// Caches a value, evicting the least recently used one if the cache is full.
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            // the times of last use are all different
+            if let Some(oldest) = self.entries.values().map(|&(_, last_use)| last_use).min() {
+                self.entries.retain(|_, &mut (_, last_use)| last_use != oldest);
+            }
+        }
+        self.clock += 1;
+        self.entries.insert(key, (value, self.clock));
+    }
+}
@@ -1,18 +1,52 @@
 #[macro_use]
 extern crate tracing;
 
+mod achievements;
+mod backend;
+mod definitions;
+mod embedding_service;
+mod events;
+mod export;
+mod fasttext;
 mod game;
+mod heat;
+mod hints;
+mod hnsw;
+mod import;
 mod irccmd;
+mod lru;
+mod matrix;
+mod rating;
+mod remote;
+mod reverse;
+mod rpc;
+mod server;
+mod simulate;
+mod storage;
+mod validate;
+mod wordle;
 mod words;
-//mod server;
 
-use anyhow::Error;
-use futures::prelude::*;
+use anyhow::{Context, Error};
+use futures::{future::try_join_all, TryFutureExt};
 use serde::Deserialize;
-use std::{fs::File, io::Read, sync::Arc, time::Duration};
-use tokio::{join, try_join};
+use std::{collections::HashMap, fs::File, io::Read, sync::Arc, time::Duration};
+use tokio::{time::Instant, try_join};
 
-use crate::{game::Game, irccmd::irc_handler, words::Words};
+use crate::{
+    definitions::{DefinitionFetcher, DefinitionsConfig},
+    embedding_service::{EmbeddingService, EmbeddingServiceConfig},
+    export::ExportOptions,
+    game::{anonymous_nick, Game, ScheduleConfig, ScoringConfig, SeasonLength, StartSchedule, TargetSettings, WordRng},
+    hints::{LlmHinter, LlmHintsConfig},
+    irccmd::irc_handler,
+    remote::{ModelApiSettings, RemoteModelConfig, RemoteWords},
+    rpc::RpcSettings,
+    server::launch_server,
+    simulate::SimulationOptions,
+    storage::{DbBackend, SqliteConfig},
+    words::{read_word_list, DifficultyLevel, LoadProgress, ModelConfig, SharedWords, WordModels, Words},
+};
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Setup
@@ -41,29 +75,204 @@ struct Game {
 // Config
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-fn default_model_file() -> String {
-    "word2vec.bin".to_string()
-}
-
 fn default_db_path() -> String {
     "game.db".to_string()
 }
 
+fn default_http_address() -> String {
+    "0.0.0.0:3000".to_string()
+}
+
+fn default_redis_channel() -> String {
+    "wordlebot".to_string()
+}
+
 fn default_game_duration() -> Duration {
     Duration::from_secs(3600 * 24)
 }
 
+fn default_giveup_quorum() -> usize {
+    3
+}
+
+fn default_recent_words_window() -> usize {
+    100
+}
+
+fn default_close_neighbor_similarity() -> f32 {
+    0.5
+}
+
 #[derive(Debug, Deserialize)]
 struct AppConfig {
-    /// Word2Vec model binary
-    #[serde(default = "default_model_file")]
-    word2vec_model_file: String,
+    /// Word model
+    #[serde(flatten)]
+    model: ModelConfig,
+    /// Word models of other languages, by language code (`[models.en]`). Sessions use the main model unless
+    /// they specify one of these languages.
+    #[serde(default)]
+    models: HashMap<String, ModelConfig>,
+    /// External embedding service. If set, it is used instead of the word2vec model file.
+    embedding_service: Option<EmbeddingServiceConfig>,
+    /// Word model served by another instance. If set, it is used instead of the word2vec model file.
+    remote_model: Option<RemoteModelConfig>,
+    /// Database backend (`sqlite` or `postgres`)
+    #[serde(default)]
+    db_backend: DbBackend,
     /// Sqlite game DB path
     #[serde(default = "default_db_path")]
     db_path: String,
+    /// Pragmas and connections of the `sqlite` backend
+    #[serde(default)]
+    sqlite: SqliteConfig,
+    /// PostgreSQL connection string, used by the `postgres` backend
+    postgres_url: Option<String>,
+    /// Number of days after which the guesses of the ended sessions are archived: only the number of guesses, best
+    /// miss and team of each player are kept. Guesses are never archived if unset.
+    guess_retention: Option<u64>,
+    /// Address of the web server.
+    #[serde(default = "default_http_address")]
+    http_address: String,
+    /// Language model used to rephrase hints. Plain hints are used if unset.
+    llm_hints: Option<LlmHintsConfig>,
+    /// Where to look up the definitions of the words to find. Definitions are not given if unset.
+    definitions: Option<DefinitionsConfig>,
+    /// Token required to use the JSON-RPC admin interface (`POST /rpc`). The interface is disabled if unset.
+    admin_token: Option<String>,
+    /// Token required to query the main word model (`POST /model`), for instances configured with
+    /// `remote_model`. The model is not served if unset.
+    model_api_token: Option<String>,
+    /// Redis server to publish game events to (e.g. `redis://127.0.0.1/`). Events are not published if unset.
+    redis_url: Option<String>,
+    /// Redis pub/sub channel on which game events are published.
+    #[serde(default = "default_redis_channel")]
+    redis_channel: String,
     /// Game duration in seconds.
     #[serde(default = "default_game_duration")]
     game_duration: Duration,
+    /// Fractions of the duration of a session after which a hint is announced if nobody has found the word yet
+    /// (e.g. `[0.5, 0.75, 0.9]`). The hints are the ones of `!hint`, and lower the points of the winner the same way.
+    #[serde(default)]
+    auto_hints: Vec<f64>,
+    /// Number of players who must vote with `!giveup` to end a session with no winner. All the players of the
+    /// session are enough if there are fewer.
+    #[serde(default = "default_giveup_quorum")]
+    giveup_quorum: usize,
+    /// Start new sessions at fixed times. Sessions are only started with `!start` if unset.
+    #[serde(alias = "daily_start")]
+    schedule: Option<ScheduleConfig>,
+    /// Points given to the winners of the sessions.
+    #[serde(default)]
+    scoring: ScoringConfig,
+    /// Length of the seasons (`weekly`, `monthly` or `yearly`). Scores are also kept per season, and the standings
+    /// are announced when a season ends. There are no seasons if unset.
+    season_length: Option<SeasonLength>,
+    /// Number of past sessions whose word is not picked again.
+    #[serde(default = "default_recent_words_window")]
+    recent_words_window: usize,
+    /// Difficulty of the words to find (`easy`, `normal` or `hard`).
+    #[serde(default)]
+    difficulty: DifficultyLevel,
+    /// Minimum number of neighbors with a similarity of at least `close_neighbor_similarity` that the word
+    /// to find must have. Words with sparser neighborhoods are rerolled.
+    #[serde(default)]
+    min_close_neighbors: usize,
+    #[serde(default = "default_close_neighbor_similarity")]
+    close_neighbor_similarity: f32,
+    /// Word lists of the themes of the sessions, by theme name (e.g. `animals = "themes/animals.txt"`). The words to
+    /// find of the other themes are picked among the nearest neighbors of the theme in the word model.
+    #[serde(default)]
+    themes: HashMap<String, String>,
+    /// Announce on the IRC channels when the word model is loaded.
+    #[serde(default)]
+    announce_model_ready: bool,
+    /// Seed of the random generator used to pick the words to find, to make the picks reproducible.
+    rng_seed: Option<u64>,
+    /// Derive the word to find from the seed and the current day, so that instances sharing the same
+    /// configuration pick the same word on the same day.
+    #[serde(default)]
+    daily_word: bool,
+}
+
+/// Loads the main configuration file (`cfg.toml`).
+fn load_config() -> AppConfig {
+    let mut config_str = String::new();
+    File::open("cfg.toml")
+        .expect("failed to open main configuration file `cfg.toml`")
+        .read_to_string(&mut config_str)
+        .expect("failed to read configuration file");
+    toml::from_str(&config_str).expect("invalid config file")
+}
+
+/// Connects to the remote word model if there's one, or loads the word model, or the vocabulary from the
+/// embedding service if there's one, and makes it available through `words`.
+async fn load_words(
+    model: ModelConfig,
+    embedding_service: Option<EmbeddingServiceConfig>,
+    remote_model: Option<RemoteModelConfig>,
+    words: SharedWords,
+) -> Result<(), Error> {
+    if let Some(remote_config) = remote_model {
+        info!("Connecting to remote word model `{}`.", remote_config.url);
+        let remote = tokio::task::spawn_blocking(move || RemoteWords::connect(remote_config))
+            .await?
+            .context("could not connect to remote word model")?;
+        info!("Connected to remote word model.");
+        words.set(remote);
+        return Ok(());
+    }
+
+    let progress = words.progress().clone();
+    let name = match embedding_service {
+        Some(ref service_config) => service_config.url.clone(),
+        None => model.word2vec_model_file.clone(),
+    };
+    let reporter = tokio::spawn(report_progress(name, progress.clone()));
+    let loaded = if let Some(service_config) = embedding_service {
+        info!("Loading vocabulary from embedding service `{}`.", service_config.url);
+        let service = EmbeddingService::new(service_config);
+        let loaded = tokio::task::spawn_blocking(move || Words::load_from_service(service, model.metric, &progress))
+            .await?
+            .context("could not load vocabulary from embedding service")?;
+        info!("Done loading vocabulary.");
+        loaded
+    } else {
+        info!("Loading word model file, this may take some time.");
+        let loaded = tokio::task::spawn_blocking(move || Words::load(&model, &progress))
+            .await?
+            .context("could not load word database")?;
+        info!("Done loading word model.");
+        loaded
+    };
+    reporter.abort();
+    words.set(loaded);
+    Ok(())
+}
+
+/// Interval between two progress reports while loading a word model.
+const PROGRESS_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Logs the progress of the loading of a word model periodically. Never returns.
+async fn report_progress(name: String, progress: LoadProgress) {
+    let start = Instant::now();
+    let mut interval = tokio::time::interval(PROGRESS_REPORT_INTERVAL);
+    // the first tick completes immediately
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let rate = progress.bytes() as f64 / 1e6 / start.elapsed().as_secs_f64();
+        let words = match progress.words() {
+            (words, Some(total)) => format!("{}/{} words", words, total),
+            (words, None) => format!("{} words", words),
+        };
+        info!(
+            "loading `{}`: {}% ({}, {:.1} MB/s)",
+            name,
+            progress.percent(),
+            words,
+            rate
+        );
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -79,31 +288,235 @@ async fn main() -> Result<(), Error> {
     tracing_subscriber::fmt::init();
 
     // load main config file (IRC config loaded separately)
-    let config: AppConfig = {
-        let mut config_str = String::new();
-        File::open("cfg.toml")
-            .expect("failed to open main configuration file `cfg.toml`")
-            .read_to_string(&mut config_str)
-            .expect("failed to read configuration file");
-        toml::from_str(&config_str).expect("invalid config file")
-    };
+    let config = load_config();
+
+    // subcommands
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("import") => {
+            let other_db = args.get(2).expect("usage: wordlebot import <other.db>");
+            if config.db_backend != DbBackend::Sqlite {
+                anyhow::bail!("import is only supported with the sqlite backend");
+            }
+            return import::import_database(&config.db_path, other_db);
+        }
+        Some("export-vocab") => {
+            let options = ExportOptions::parse(&args[2..])?;
+            let words = SharedWords::default();
+            load_words(
+                config.model,
+                config.embedding_service,
+                config.remote_model,
+                words.clone(),
+            )
+            .await?;
+            return export::export_vocabulary(words, options);
+        }
+        Some("recompute-ratings") => {
+            let mut storage = storage::open(
+                config.db_backend,
+                &config.db_path,
+                &config.sqlite,
+                config.postgres_url.as_deref(),
+            )
+            .await?;
+            let sessions = rating::recompute_ratings(&mut *storage).await?;
+            println!("recomputed the ratings from {} sessions", sessions);
+            return Ok(());
+        }
+        Some("restore") => {
+            let backup = args.get(2).expect("usage: wordlebot restore <backup.db>");
+            if config.db_backend != DbBackend::Sqlite {
+                anyhow::bail!("restore is only supported with the sqlite backend");
+            }
+            storage::restore_backup(&config.db_path, backup)?;
+            println!("restored `{}` from `{}`", config.db_path, backup);
+            return Ok(());
+        }
+        Some("forget") => {
+            let nick = args.get(2).expect("usage: wordlebot forget <nick>");
+            let mut storage = storage::open(
+                config.db_backend,
+                &config.db_path,
+                &config.sqlite,
+                config.postgres_url.as_deref(),
+            )
+            .await?;
+            match storage.find_player(nick).await? {
+                Some(player_id) => {
+                    storage.forget_player(player_id, &anonymous_nick(player_id)).await?;
+                    println!("{} has been forgotten", nick);
+                }
+                None => println!("{} has never played", nick),
+            }
+            return Ok(());
+        }
+        Some("export-session") => {
+            let session_id = args
+                .get(2)
+                .and_then(|id| id.parse().ok())
+                .expect("usage: wordlebot export-session <session ID>");
+            let mut storage = storage::open(
+                config.db_backend,
+                &config.db_path,
+                &config.sqlite,
+                config.postgres_url.as_deref(),
+            )
+            .await?;
+            match storage.session_transcript(session_id).await? {
+                Some(transcript) => println!("{}", serde_json::to_string_pretty(&transcript)?),
+                None => println!("there's no session {}", session_id),
+            }
+            return Ok(());
+        }
+        Some("validate-model") => {
+            let path = args.get(2).map(String::as_str);
+            return validate::validate_model(config.model, path);
+        }
+        Some("simulate") => {
+            let options = SimulationOptions::parse(&args[2..])?;
+            let words = SharedWords::default();
+            load_words(
+                config.model,
+                config.embedding_service,
+                config.remote_model,
+                words.clone(),
+            )
+            .await?;
+            return simulate::simulate(words, options).await;
+        }
+        Some(other) => {
+            panic!("unknown subcommand `{}`", other);
+        }
+        None => {}
+    }
 
-    trace!("word2vec model file : `{}`", config.word2vec_model_file);
+    trace!("word model file     : `{}`", config.model.word2vec_model_file);
+    trace!("word model format   : {:?}", config.model.model_format);
+    trace!("database backend    : {:?}", config.db_backend);
     trace!("database file       : `{}`", config.db_path);
 
-    info!("Loading word model file, this may take some time.");
-    let words = Arc::new(Words::load(&config.word2vec_model_file).expect("could not load word database"));
-    info!("Done loading word model.");
+    // the word models are loaded in the background, the bot replies that they're still loading in the meantime
+    let words = SharedWords::default();
+    let mut load_tasks = vec![tokio::spawn(load_words(
+        config.model.clone(),
+        config.embedding_service.clone(),
+        config.remote_model.clone(),
+        words.clone(),
+    ))];
+    let mut languages = HashMap::new();
+    for (language, model) in &config.models {
+        trace!("word model file ({}): `{}`", language, model.word2vec_model_file);
+        let language_words = SharedWords::default();
+        load_tasks.push(tokio::spawn(load_words(model.clone(), None, None, language_words.clone())));
+        languages.insert(language.clone(), language_words);
+    }
+    let models = WordModels::new(words.clone(), languages);
+
+    let storage = storage::open(
+        config.db_backend,
+        &config.db_path,
+        &config.sqlite,
+        config.postgres_url.as_deref(),
+    )
+    .await
+        .expect("can't connect to database");
+
+    let definitions = config
+        .definitions
+        .clone()
+        .map(DefinitionFetcher::new)
+        .transpose()
+        .context("could not read definitions")?;
+    let schedule = config
+        .schedule
+        .clone()
+        .map(StartSchedule::new)
+        .transpose()
+        .context("invalid `schedule`")?;
+    let mut themes = HashMap::new();
+    for (theme, path) in &config.themes {
+        let words =
+            read_word_list(path).with_context(|| format!("could not read the word list of theme `{}`", theme))?;
+        themes.insert(theme.to_lowercase(), words);
+    }
+    if config.auto_hints.iter().any(|fraction| !(0.0..1.0).contains(fraction)) {
+        anyhow::bail!("`auto_hints` must be fractions of the session duration, between 0 and 1");
+    }
+    let (notices_tx, notices_rx) = tokio::sync::mpsc::unbounded_channel();
+    let game = Game::load(
+        storage,
+        models,
+        TargetSettings {
+            recent_words_window: config.recent_words_window,
+            difficulty_level: config.difficulty,
+            rng: WordRng::new(config.rng_seed, config.daily_word),
+            min_close_neighbors: config.min_close_neighbors,
+            close_similarity: config.close_neighbor_similarity,
+            themes,
+        },
+        definitions,
+        config.scoring.clone(),
+        Some(notices_tx.clone()),
+    )
+    .await
+    .expect("could not start game");
+
+    // publish game events to redis if configured
+    if let Some(redis_url) = config.redis_url.clone() {
+        tokio::spawn(
+            events::redis_publisher(redis_url, config.redis_channel.clone(), game.subscribe().await)
+                .inspect_err(|err| error!("redis event publisher stopped: {}", err)),
+        );
+    }
 
-    let connection = rusqlite::Connection::open(&config.db_path).expect("can't connect to database file");
-    let game = Game::load(connection, words.clone()).expect("could not start game");
+    // end the sessions that run out of time, and start the scheduled ones
+    tokio::spawn(game.clone().expire_sessions(config.auto_hints.clone(), notices_tx.clone()));
+    if let Some(length) = config.season_length {
+        tokio::spawn(game.clone().roll_seasons(length, notices_tx.clone()));
+    }
+    tokio::spawn(game.clone().run_tournaments(notices_tx.clone()));
+    if let (DbBackend::Sqlite, Some(hours)) = (config.db_backend, config.sqlite.backup_interval) {
+        tokio::spawn(game.clone().back_up_periodically(Duration::from_secs(hours * 3600)));
+    }
+    if let Some(days) = config.guess_retention {
+        tokio::spawn(game.clone().archive_guesses_periodically(days));
+    }
+    if let Some(schedule) = schedule {
+        tokio::spawn(game.clone().start_scheduled(schedule, config.game_duration, notices_tx));
+    }
 
     // spawn the tasks: IRC bot & web server
-    let irc_task = tokio::spawn(irc_handler(words.clone(), game.clone()));
-    //let server_task = tokio::spawn(launch_server(pool.clone()));
+    let hinter = config.llm_hints.clone().map(|config| Arc::new(LlmHinter::new(config)));
+    let irc_task = tokio::spawn(irc_handler(
+        words.clone(),
+        game.clone(),
+        notices_rx,
+        config.game_duration,
+        config.giveup_quorum,
+        hinter,
+        config.announce_model_ready,
+    ));
+    let http_address = config.http_address.parse().expect("invalid `http_address`");
+    let rpc_settings = config.admin_token.clone().map(|admin_token| RpcSettings {
+        admin_token,
+        game_duration: config.game_duration,
+    });
+    let model_api_settings = config.model_api_token.clone().map(|token| ModelApiSettings {
+        token,
+        words: words.clone(),
+    });
+    let server_task = tokio::spawn(launch_server(http_address, game.clone(), rpc_settings, model_api_settings));
 
-    let res = try_join!(irc_task);
-    res.unwrap();
+    // stop if any of the tasks fails, including the model loading
+    try_join!(
+        async { irc_task.await? },
+        async { Ok::<_, Error>(server_task.await?) },
+        async {
+            try_join_all(load_tasks.into_iter().map(|load_task| async { load_task.await? })).await?;
+            Ok::<_, Error>(())
+        },
+    )?;
     Ok(())
 }
 
@@ -0,0 +1,253 @@
+//! Word vectors as a flat row-major matrix, in memory or memory-mapped from a file
+//!
+//! Flat vector files are made of (native byte order):
+//! - the magic bytes `WBFLAT02`
+//! - the number of words, the vector size, the size in bytes of the word list and the fingerprint of
+//!   the model the file was built from, as `u64`s
+//! - the word list: words separated by `\n`, padded with zeros to a multiple of 8 bytes
+//! - the L2 norm of each vector, as `f32`s
+//! - the vectors, as `f32`s
+use crate::words::dot;
+use anyhow::{anyhow, bail, Result};
+use half::f16;
+use memmap2::Mmap;
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+const FLAT_FILE_MAGIC: &[u8; 8] = b"WBFLAT02";
+const FLAT_FILE_HEADER_SIZE: usize = 40;
+
+enum MatrixData {
+    Owned(Vec<f32>),
+    /// `len` floats starting at `offset` bytes in the mapped file.
+    Mapped {
+        mmap: Mmap,
+        offset: usize,
+        len: usize,
+    },
+    /// Half-precision floats, converted on the fly.
+    Half(Vec<f16>),
+    /// 8-bit integers, with a scale factor per row.
+    Int8 {
+        data: Vec<i8>,
+        scales: Vec<f32>,
+    },
+}
+
+/// Quantizes a vector to 8-bit integers. Returns the integers and the scale factor to apply to them.
+fn quantize(v: &[f32]) -> (Vec<i8>, f32) {
+    let max = v.iter().fold(0.0f32, |max, x| max.max(x.abs()));
+    let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+    (v.iter().map(|x| (x / scale).round() as i8).collect(), scale)
+}
+
+/// A vector prepared for dot products with the rows of a matrix.
+pub struct Query<'a> {
+    vector: &'a [f32],
+    /// Quantized vector and its scale factor, for `Int8` matrices.
+    quantized: Option<(Vec<i8>, f32)>,
+}
+
+/// Vectors of the vocabulary, one per row.
+pub struct Matrix {
+    dim: usize,
+    data: MatrixData,
+}
+
+impl Matrix {
+    /// Builds a matrix from its rows of `dim` elements, stored one after the other.
+    pub fn from_data(dim: usize, data: Vec<f32>) -> Matrix {
+        debug_assert_eq!(data.len() % dim.max(1), 0);
+        Matrix {
+            dim,
+            data: MatrixData::Owned(data),
+        }
+    }
+
+    /// Size of the vectors.
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// The vectors, if they are stored as `f32`.
+    fn as_f32(&self) -> Option<&[f32]> {
+        match self.data {
+            MatrixData::Owned(ref data) => Some(data),
+            MatrixData::Mapped { ref mmap, offset, len } => Some(bytemuck::cast_slice(&mmap[offset..offset + len * 4])),
+            MatrixData::Half(_) | MatrixData::Int8 { .. } => None,
+        }
+    }
+
+    /// Returns the `i`-th vector.
+    pub fn row(&self, i: usize) -> Cow<'_, [f32]> {
+        let range = i * self.dim..(i + 1) * self.dim;
+        match self.data {
+            MatrixData::Half(ref data) => Cow::Owned(data[range].iter().map(|x| x.to_f32()).collect()),
+            MatrixData::Int8 { ref data, ref scales } => {
+                Cow::Owned(data[range].iter().map(|&x| x as f32 * scales[i]).collect())
+            }
+            _ => Cow::Borrowed(&self.as_f32().unwrap()[range]),
+        }
+    }
+
+    /// Prepares a vector for dot products with the rows of this matrix.
+    pub fn query<'a>(&self, v: &'a [f32]) -> Query<'a> {
+        let quantized = match self.data {
+            MatrixData::Int8 { .. } => Some(quantize(v)),
+            _ => None,
+        };
+        Query { vector: v, quantized }
+    }
+
+    /// Dot product of the `i`-th vector with a query vector.
+    pub fn dot(&self, i: usize, query: &Query) -> f32 {
+        let range = i * self.dim..(i + 1) * self.dim;
+        match self.data {
+            MatrixData::Half(ref data) => data[range].iter().zip(query.vector).map(|(a, b)| a.to_f32() * b).sum(),
+            MatrixData::Int8 { ref data, ref scales } => {
+                let (ref v, scale) = *query.quantized.as_ref().expect("query not prepared for this matrix");
+                let dot: i32 = data[range].iter().zip(v).map(|(&a, &b)| a as i32 * b as i32).sum();
+                dot as f32 * scales[i] * scale
+            }
+            _ => dot(&self.as_f32().unwrap()[range], query.vector),
+        }
+    }
+
+    /// Copies the vectors of a memory-mapped matrix into memory.
+    fn into_owned(self) -> Matrix {
+        match self.data {
+            MatrixData::Mapped { .. } => Matrix {
+                dim: self.dim,
+                data: MatrixData::Owned(self.as_f32().unwrap().to_vec()),
+            },
+            _ => self,
+        }
+    }
+
+    /// Quantizes the vectors to 8-bit integers, which divides the memory they use by four.
+    pub fn into_int8(self) -> Matrix {
+        match self.as_f32() {
+            Some(data) => {
+                let mut quantized = Vec::with_capacity(data.len());
+                let mut scales = Vec::with_capacity(data.len() / self.dim.max(1));
+                for row in data.chunks_exact(self.dim.max(1)) {
+                    let (row, scale) = quantize(row);
+                    quantized.extend(row);
+                    scales.push(scale);
+                }
+                Matrix {
+                    dim: self.dim,
+                    data: MatrixData::Int8 {
+                        data: quantized,
+                        scales,
+                    },
+                }
+            }
+            None => self,
+        }
+    }
+
+    /// Converts the vectors to half precision, which halves the memory they use.
+    pub fn into_half(self) -> Matrix {
+        match self.as_f32() {
+            Some(data) => Matrix {
+                dim: self.dim,
+                data: MatrixData::Half(data.iter().map(|&x| f16::from_f32(x)).collect()),
+            },
+            None => self,
+        }
+    }
+}
+
+/// Contents of a flat vector file.
+pub struct FlatFile {
+    pub words: Vec<String>,
+    pub vectors: Matrix,
+    /// L2 norm of each vector.
+    pub norms: Vec<f32>,
+}
+
+/// Writes the words, their vectors and the norms of the vectors to a flat vector file.
+pub fn write_flat_file(path: &str, fingerprint: u64, words: &[String], matrix: &Matrix, norms: &[f32]) -> Result<()> {
+    let word_list = words.join("\n");
+    let padding = (8 - word_list.len() % 8) % 8;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(FLAT_FILE_MAGIC)?;
+    for n in [
+        words.len() as u64,
+        matrix.dim() as u64,
+        word_list.len() as u64,
+        fingerprint,
+    ] {
+        writer.write_all(&n.to_ne_bytes())?;
+    }
+    writer.write_all(word_list.as_bytes())?;
+    writer.write_all(&[0; 8][..padding])?;
+    writer.write_all(bytemuck::cast_slice(norms))?;
+    for i in 0..words.len() {
+        writer.write_all(bytemuck::cast_slice(&matrix.row(i)))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a flat vector file. Returns `None` if the file doesn't exist or was built from another model.
+///
+/// If `mmap` is set, the vectors are memory-mapped instead of read into memory.
+pub fn read_flat_file(path: &str, fingerprint: u64, mmap: bool) -> Result<Option<FlatFile>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    // SAFETY: the file must not be modified while it's mapped, like the model files
+    let map = unsafe { Mmap::map(&file)? };
+
+    let header = |i: usize| -> Result<u64> {
+        let bytes = map
+            .get(8 + i * 8..16 + i * 8)
+            .ok_or_else(|| anyhow!("truncated header"))?;
+        Ok(u64::from_ne_bytes(bytes.try_into().unwrap()))
+    };
+    if map.get(..8) != Some(&FLAT_FILE_MAGIC[..]) {
+        // not a flat file, or an older version
+        return Ok(None);
+    }
+    if header(3)? != fingerprint {
+        return Ok(None);
+    }
+    let (count, dim, word_list_len) = (header(0)? as usize, header(1)? as usize, header(2)? as usize);
+
+    let words_start = FLAT_FILE_HEADER_SIZE;
+    let norms_start = words_start + word_list_len + (8 - word_list_len % 8) % 8;
+    let matrix_start = norms_start + count * 4;
+    if map.len() != matrix_start + count * dim * 4 {
+        bail!("invalid size for flat vector file `{}`", path);
+    }
+
+    let word_list = std::str::from_utf8(&map[words_start..words_start + word_list_len])?;
+    let words: Vec<String> = if count == 0 {
+        Vec::new()
+    } else {
+        word_list.split('\n').map(str::to_string).collect()
+    };
+    if words.len() != count {
+        bail!("invalid word list in flat vector file `{}`", path);
+    }
+    let norms = bytemuck::cast_slice(&map[norms_start..matrix_start]).to_vec();
+
+    let vectors = Matrix {
+        dim,
+        data: MatrixData::Mapped {
+            mmap: map,
+            offset: matrix_start,
+            len: count * dim,
+        },
+    };
+    let vectors = if mmap { vectors } else { vectors.into_owned() };
+    Ok(Some(FlatFile { words, vectors, norms }))
+}
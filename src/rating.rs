@@ -0,0 +1,199 @@
+//! Elo-style player ratings
+//!
+//! Each session is rated as a round of Elo games between every pair of players who took part in it, ranked by their
+//! placement: the players who found the word in the order they found it, then the others, tied. A player who found
+//! the word beats those who didn't, by more if they needed fewer guesses than the average winner of the previous
+//! sessions. Each pair exchanges the same amount of points, so ratings are only moved between the players.
+//!
+//! Ratings only depend on the history of the sessions, and can be recomputed from it with
+//! `wordlebot recompute-ratings`.
+use crate::storage::Storage;
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Rating of a player who hasn't finished a session yet.
+pub const INITIAL_RATING: f64 = 1500.0;
+
+/// Maximum rating change of a player in a session, shared between the games against each other player.
+const K: f64 = 32.0;
+
+/// Expected score (between 0 and 1) of a player rated `rating` against a player rated `opponent`.
+fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// Score (between 0 and 1) of a player against another. `place` is the place of a player among the winners, and
+/// `performance` how fast they found the word compared to the average winner, between 0 and 1 (0.5 on average).
+fn score(place: Option<usize>, performance: f64, other_place: Option<usize>) -> f64 {
+    match (place, other_place) {
+        (Some(place), Some(other_place)) if place < other_place => 1.0,
+        (Some(_), Some(_)) => 0.0,
+        (Some(_), None) => (1.0 + performance) / 2.0,
+        (None, Some(_)) => 1.0 - score(other_place, performance, place),
+        (None, None) => 0.5,
+    }
+}
+
+/// Returns the new ratings of the players of a session.
+///
+/// `guess_counts` is the number of guesses of each player of the session, `winner_ids` the players who found the
+/// word in the order they found it, and `average_guesses` the average number of guesses the winners of the previous
+/// sessions needed, if there were any.
+pub fn rate_session(
+    ratings: &HashMap<i64, f64>,
+    guess_counts: &HashMap<i64, usize>,
+    winner_ids: &[i64],
+    average_guesses: Option<f64>,
+) -> Vec<(i64, f64)> {
+    let rating = |player_id: i64| ratings.get(&player_id).copied().unwrap_or(INITIAL_RATING);
+    let place = |player_id: i64| winner_ids.iter().position(|&id| id == player_id);
+    let performance = |player_id: i64| {
+        let guesses = guess_counts.get(&player_id).copied().unwrap_or_default().max(1) as f64;
+        let average = average_guesses.unwrap_or(guesses);
+        average / (average + guesses)
+    };
+    // the games of a player are worth `K` in total, whatever the number of players
+    let k = K / guess_counts.len().saturating_sub(1).max(1) as f64;
+    guess_counts
+        .keys()
+        .map(|&player_id| {
+            let change: f64 = guess_counts
+                .keys()
+                .filter(|&&other_id| other_id != player_id)
+                .map(|&other_id| {
+                    // the performance only counts in the games between a winner and another player
+                    let performance = match place(player_id) {
+                        Some(_) => performance(player_id),
+                        None => performance(other_id),
+                    };
+                    let score = score(place(player_id), performance, place(other_id));
+                    k * (score - expected_score(rating(player_id), rating(other_id)))
+                })
+                .sum();
+            (player_id, rating(player_id) + change)
+        })
+        .collect()
+}
+
+/// Returns the number of guesses of each player of a session.
+pub async fn guess_counts(storage: &mut dyn Storage, session_id: i64) -> Result<HashMap<i64, usize>> {
+    Ok(storage.guess_counts(session_id).await?.into_iter().collect())
+}
+
+/// Recomputes the ratings of all players by replaying the ended sessions in order. Returns the number of sessions.
+pub async fn recompute_ratings(storage: &mut dyn Storage) -> Result<usize> {
+    let sessions = storage.ended_sessions().await?;
+    let mut ratings = HashMap::new();
+    let (mut wins, mut winning_guesses) = (0, 0);
+    for &(session_id, winner_id) in &sessions {
+        let counts = guess_counts(storage, session_id).await?;
+        let average_guesses = (wins > 0).then(|| winning_guesses as f64 / wins as f64);
+        // the sessions ended before several players could find the word only have a winner
+        let mut winner_ids: Vec<i64> = storage
+            .session_winners(session_id)
+            .await?
+            .into_iter()
+            .map(|winner| winner.player_id)
+            .collect();
+        if winner_ids.is_empty() {
+            winner_ids.extend(winner_id);
+        }
+        ratings.extend(rate_session(&ratings, &counts, &winner_ids, average_guesses));
+        if let Some(guesses) = winner_id.and_then(|id| counts.get(&id)) {
+            wins += 1;
+            winning_guesses += guesses;
+        }
+    }
+    storage.reset_ratings(&ratings.into_iter().collect::<Vec<_>>()).await?;
+    Ok(sessions.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rating changes of the players of a session.
+    fn changes(
+        ratings: &[(i64, f64)],
+        guess_counts: &[(i64, usize)],
+        winner_ids: &[i64],
+        average_guesses: Option<f64>,
+    ) -> HashMap<i64, f64> {
+        let ratings: HashMap<i64, f64> = ratings.iter().copied().collect();
+        let guess_counts: HashMap<i64, usize> = guess_counts.iter().copied().collect();
+        rate_session(&ratings, &guess_counts, winner_ids, average_guesses)
+            .into_iter()
+            .map(|(player_id, rating)| {
+                let before = ratings.get(&player_id).copied().unwrap_or(INITIAL_RATING);
+                (player_id, rating - before)
+            })
+            .collect()
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn symmetric() {
+        // the winner gains what the other player loses
+        let won = changes(&[(1, 1600.0), (2, 1450.0)], &[(1, 12), (2, 30)], &[1], Some(20.0));
+        assert!(won[&1] > 0.0);
+        assert_close(won[&1], -won[&2]);
+
+        // swapping the players swaps the changes
+        let swapped = changes(&[(2, 1600.0), (1, 1450.0)], &[(2, 12), (1, 30)], &[2], Some(20.0));
+        assert_close(won[&1], swapped[&2]);
+        assert_close(won[&2], swapped[&1]);
+
+        // nobody found the word: a draw
+        let draw = changes(&[], &[(1, 10), (2, 10)], &[], Some(20.0));
+        assert_close(draw[&1], 0.0);
+        assert_close(draw[&2], 0.0);
+    }
+
+    #[test]
+    fn conserves_points() {
+        let ratings = [(1, 1500.0), (2, 1720.0), (3, 1310.0), (4, 1555.0), (5, 1400.0)];
+        let guess_counts = [(1, 8), (2, 25), (3, 3), (4, 40), (5, 17), (6, 2)];
+        for winner_ids in [&[][..], &[3], &[2, 6], &[6, 1, 4]] {
+            for average_guesses in [None, Some(5.0), Some(30.0)] {
+                let changes = changes(&ratings, &guess_counts, winner_ids, average_guesses);
+                assert_eq!(changes.len(), guess_counts.len());
+                assert_close(changes.values().sum(), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn k_factor() {
+        // equal ratings, the winner found the word as fast as the average winner: score 0.75 against the others
+        let duel = changes(&[], &[(1, 20), (2, 20)], &[1], Some(20.0));
+        assert_close(duel[&1], K * 0.25);
+        assert_close(duel[&2], -K * 0.25);
+
+        // with more players, the games of each player are worth `K` in total
+        let session = changes(&[], &[(1, 20), (2, 20), (3, 5), (4, 50)], &[1], Some(20.0));
+        assert_close(session[&1], K * 0.25);
+        for player_id in [2, 3, 4] {
+            assert_close(session[&player_id], -K * 0.25 / 3.0);
+        }
+
+        // faster than the average winner: a bigger win
+        let fast = changes(&[], &[(1, 5), (2, 20)], &[1], Some(20.0));
+        let slow = changes(&[], &[(1, 80), (2, 20)], &[1], Some(20.0));
+        assert!(fast[&1] > duel[&1] && duel[&1] > slow[&1] && slow[&1] > 0.0);
+        // without previous winners, the winner performs as the average winner
+        assert_close(changes(&[], &[(1, 5), (2, 20)], &[1], None)[&1], duel[&1]);
+    }
+
+    #[test]
+    fn placement() {
+        let changes = changes(&[], &[(1, 20), (2, 20), (3, 20), (4, 20)], &[3, 1], Some(20.0));
+        // the first winner beats everybody, the second winner everybody but the first
+        assert_close(changes[&3], K / 3.0 * (0.5 + 0.25 + 0.25));
+        assert_close(changes[&1], K / 3.0 * (-0.5 + 0.25 + 0.25));
+        assert!(changes[&3] > changes[&1] && changes[&1] > changes[&2]);
+        assert_close(changes[&2], changes[&4]);
+    }
+}
@@ -0,0 +1,389 @@
+//! Word model shared between bot instances over HTTP
+//!
+//! An instance with `model_api_token` set serves its main word model on `POST /model`. Other instances
+//! configured with `[remote_model]` query it instead of loading a model themselves: only the word list is kept
+//! locally.
+//!
+//! Requests are JSON objects `{"method": ..., "params": {...}}` and must carry an
+//! `Authorization: Bearer <model_api_token>` header. Errors of the remote model are logged, and handled like
+//! unknown words where the interface can't report them.
+use crate::{
+    backend::EmbeddingBackend,
+    words::{
+        pick_by_difficulty, Difficulty, DifficultyLevel, GuessPolicy, Metric, NeighborTable, RankTable, SharedWords,
+        APPROXIMATE_RANK_TABLE_SIZE, DIFFICULTY_SAMPLE_SIZE,
+    },
+};
+use anyhow::{anyhow, Result};
+use axum::{
+    http::{header, HeaderMap, StatusCode},
+    Extension, Json,
+};
+use rand::{seq::SliceRandom, Rng, RngCore};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+/// `[remote_model]` section of the configuration.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RemoteModelConfig {
+    /// URL of the model API of the instance serving the model (e.g. `http://10.0.0.2:3000/model`).
+    pub url: String,
+    /// `model_api_token` of that instance.
+    pub token: String,
+}
+
+/// Settings of the model API.
+pub struct ModelApiSettings {
+    /// Bearer token that clients must present.
+    pub token: String,
+    /// Served word model.
+    pub words: SharedWords,
+}
+
+/// Request of the model API, one per method of `EmbeddingBackend` that needs the vectors.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum ModelRequest {
+    /// Default metric, words of the vocabulary and positions of the words that can be picked.
+    Info,
+    Vector { word: String },
+    GuessVector { guess: String },
+    ResolveGuess { guess: String },
+    IsRejectedStopWord { guess: String },
+    SpellSuggest { word: String, count: usize },
+    Neighbors { word: String, count: usize, metric: Metric },
+    NeighborTable { word: String, count: usize, metric: Metric },
+    Thesaurus { word: String, count: usize },
+    Analogy { positive: Vec<String>, negative: Vec<String>, count: usize },
+    Similarity { a: String, b: String },
+    /// Rank table, truncated to the `APPROXIMATE_RANK_TABLE_SIZE` nearest words.
+    RankTable { word: String, metric: Metric },
+    /// Rank of a similarity to the word at `index`, counting all the words of the vocabulary.
+    Rank { index: usize, metric: Metric, similarity: f32 },
+    Difficulties { words: Vec<String>, metric: Metric },
+    Frequency { word: String },
+}
+
+#[derive(Serialize, Deserialize)]
+struct ModelInfo {
+    metric: Metric,
+    words: Vec<String>,
+    pickable: Vec<usize>,
+    #[serde(default)]
+    guess_policy: GuessPolicy,
+}
+
+/// Answers a request with the served model.
+fn handle(words: &dyn EmbeddingBackend, request: ModelRequest) -> Result<Value> {
+    Ok(match request {
+        ModelRequest::Info => {
+            let pickable: HashSet<&str> = words.pickable_words().into_iter().collect();
+            let info = ModelInfo {
+                metric: words.metric(),
+                words: (0..words.len()).map(|i| words.word(i).to_string()).collect(),
+                pickable: (0..words.len()).filter(|&i| pickable.contains(words.word(i))).collect(),
+                guess_policy: words.guess_policy().clone(),
+            };
+            json!(info)
+        }
+        ModelRequest::Vector { word } => json!(words.vector(&word)),
+        ModelRequest::GuessVector { guess } => json!(words.guess_vector(&guess)?),
+        ModelRequest::ResolveGuess { guess } => json!(words.resolve_guess(&guess)),
+        ModelRequest::IsRejectedStopWord { guess } => json!(words.is_rejected_stop_word(&guess)),
+        ModelRequest::SpellSuggest { word, count } => json!(words.spell_suggest(&word, count)),
+        ModelRequest::Neighbors { word, count, metric } => json!(words.neighbors(&word, count, metric)),
+        ModelRequest::NeighborTable { word, count, metric } => json!(words.neighbor_table(&word, count, metric)),
+        ModelRequest::Thesaurus { word, count } => json!(words.thesaurus(&word, count)),
+        ModelRequest::Analogy {
+            positive,
+            negative,
+            count,
+        } => {
+            let positive: Vec<&str> = positive.iter().map(String::as_str).collect();
+            let negative: Vec<&str> = negative.iter().map(String::as_str).collect();
+            json!(words.analogy(&positive, &negative, count)?)
+        }
+        ModelRequest::Similarity { a, b } => json!(words.similarity(&a, &b)?),
+        ModelRequest::RankTable { word, metric } => {
+            let table = words.rank_table(&word, metric).map(|mut table| {
+                table.truncate(APPROXIMATE_RANK_TABLE_SIZE);
+                table
+            });
+            json!(table)
+        }
+        ModelRequest::Rank {
+            index,
+            metric,
+            similarity,
+        } => json!(words.rank(&RankTable::empty(index, metric), similarity)),
+        ModelRequest::Difficulties { words: sample, metric } => {
+            let difficulties: Vec<Option<Difficulty>> =
+                sample.iter().map(|word| words.difficulty(word, metric)).collect();
+            json!(difficulties)
+        }
+        ModelRequest::Frequency { word } => json!(words.frequency(&word)),
+    })
+}
+
+/// `POST /model`
+pub async fn model_handler(
+    Extension(settings): Extension<Option<Arc<ModelApiSettings>>>,
+    headers: HeaderMap,
+    Json(request): Json<ModelRequest>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let settings = settings.ok_or((StatusCode::NOT_FOUND, "model API disabled".to_string()))?;
+
+    let authorized = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        == Some(settings.token.as_str());
+    if !authorized {
+        return Err((StatusCode::UNAUTHORIZED, "invalid token".to_string()));
+    }
+
+    let words = settings
+        .words
+        .get()
+        .map_err(|loading| (StatusCode::SERVICE_UNAVAILABLE, loading.to_string()))?
+        .clone();
+    tokio::task::spawn_blocking(move || handle(words.as_ref(), request))
+        .await
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
+        .map(Json)
+        .map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()))
+}
+
+/// Word model served by another instance.
+pub struct RemoteWords {
+    config: RemoteModelConfig,
+    agent: ureq::Agent,
+    metric: Metric,
+    words: Vec<String>,
+    /// Position of each word in `words`.
+    index: HashMap<String, usize>,
+    /// Positions of the words that may be picked as the word to find.
+    pickable: Vec<usize>,
+    /// How guesses are cleaned up, as configured on the remote instance.
+    guess_policy: GuessPolicy,
+}
+
+impl RemoteWords {
+    /// Fetches the vocabulary of the remote model. This blocks until the remote instance replies.
+    pub fn connect(config: RemoteModelConfig) -> Result<RemoteWords> {
+        let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(60)).build();
+        let mut words = RemoteWords {
+            config,
+            agent,
+            metric: Metric::default(),
+            words: Vec::new(),
+            index: HashMap::new(),
+            pickable: Vec::new(),
+            guess_policy: GuessPolicy::default(),
+        };
+        let info: ModelInfo = words.call(&ModelRequest::Info)?;
+        words.metric = info.metric;
+        words.index = info.words.iter().enumerate().map(|(i, word)| (word.clone(), i)).collect();
+        words.words = info.words;
+        words.pickable = info.pickable;
+        words.guess_policy = info.guess_policy;
+        Ok(words)
+    }
+
+    /// Sends a request to the remote instance and waits for the result.
+    fn call<T: DeserializeOwned>(&self, request: &ModelRequest) -> Result<T> {
+        let response = self
+            .agent
+            .post(&self.config.url)
+            .set("Authorization", &format!("Bearer {}", self.config.token))
+            .send_json(request);
+        match response {
+            Ok(response) => Ok(response.into_json()?),
+            Err(ureq::Error::Status(_, response)) => Err(anyhow!(response.into_string()?)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Like `call`, but logs errors and returns `None` instead.
+    fn call_or_log<T: DeserializeOwned>(&self, request: &ModelRequest) -> Option<T> {
+        self.call(request)
+            .map_err(|err| error!("remote model `{}`: {}", self.config.url, err))
+            .ok()
+    }
+
+    fn candidates(&self, predicate: &dyn Fn(&str) -> bool) -> Vec<&str> {
+        self.pickable
+            .iter()
+            .map(|&i| self.words[i].as_str())
+            .filter(|word| predicate(word))
+            .collect()
+    }
+}
+
+impl EmbeddingBackend for RemoteWords {
+    fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    fn may_block(&self) -> bool {
+        true
+    }
+
+    fn word(&self, i: usize) -> &str {
+        &self.words[i]
+    }
+
+    fn vector(&self, word: &str) -> Option<Cow<'_, [f32]>> {
+        let vector: Option<Vec<f32>> = self.call_or_log(&ModelRequest::Vector { word: word.to_string() })?;
+        vector.map(Cow::Owned)
+    }
+
+    fn guess_vector(&self, guess: &str) -> Result<Option<Cow<'_, [f32]>>> {
+        let vector: Option<Vec<f32>> = self.call(&ModelRequest::GuessVector {
+            guess: guess.to_string(),
+        })?;
+        Ok(vector.map(Cow::Owned))
+    }
+
+    fn guess_policy(&self) -> &GuessPolicy {
+        &self.guess_policy
+    }
+
+    fn resolve_guess<'a>(&'a self, guess: &'a str) -> &'a str {
+        let resolved: Option<String> = self.call_or_log(&ModelRequest::ResolveGuess {
+            guess: guess.to_string(),
+        });
+        match resolved.and_then(|resolved| self.index.get(&resolved)) {
+            Some(&i) => &self.words[i],
+            None => guess,
+        }
+    }
+
+    fn is_rejected_stop_word(&self, guess: &str) -> bool {
+        self.call_or_log(&ModelRequest::IsRejectedStopWord {
+            guess: guess.to_string(),
+        })
+        .unwrap_or(false)
+    }
+
+    fn spell_suggest(&self, word: &str, count: usize) -> Vec<String> {
+        self.call_or_log(&ModelRequest::SpellSuggest {
+            word: word.to_string(),
+            count,
+        })
+        .unwrap_or_default()
+    }
+
+    fn neighbors(&self, word: &str, count: usize, metric: Metric) -> Option<Vec<(String, f32)>> {
+        self.call_or_log(&ModelRequest::Neighbors {
+            word: word.to_string(),
+            count,
+            metric,
+        })?
+    }
+
+    fn neighbor_table(&self, word: &str, count: usize, metric: Metric) -> Option<NeighborTable> {
+        self.call_or_log(&ModelRequest::NeighborTable {
+            word: word.to_string(),
+            count,
+            metric,
+        })?
+    }
+
+    fn thesaurus(&self, word: &str, count: usize) -> Option<Vec<(String, f32)>> {
+        self.call_or_log(&ModelRequest::Thesaurus {
+            word: word.to_string(),
+            count,
+        })?
+    }
+
+    fn analogy(&self, positive: &[&str], negative: &[&str], count: usize) -> Result<Vec<(String, f32)>> {
+        self.call(&ModelRequest::Analogy {
+            positive: positive.iter().map(|word| word.to_string()).collect(),
+            negative: negative.iter().map(|word| word.to_string()).collect(),
+            count,
+        })
+    }
+
+    fn similarity(&self, a: &str, b: &str) -> Result<f32> {
+        self.call(&ModelRequest::Similarity {
+            a: a.to_string(),
+            b: b.to_string(),
+        })
+    }
+
+    fn rank_table(&self, word: &str, metric: Metric) -> Option<RankTable> {
+        self.call_or_log(&ModelRequest::RankTable {
+            word: word.to_string(),
+            metric,
+        })?
+    }
+
+    fn rank(&self, table: &RankTable, similarity: f32) -> usize {
+        if let Some(rank) = table.known_rank(similarity) {
+            return rank;
+        }
+        self.call_or_log(&ModelRequest::Rank {
+            index: table.index(),
+            metric: table.metric(),
+            similarity,
+        })
+        // at worst, the guess is the furthest word
+        .unwrap_or(self.words.len())
+    }
+
+    fn difficulty(&self, word: &str, metric: Metric) -> Option<Difficulty> {
+        let mut difficulties: Vec<Option<Difficulty>> = self.call_or_log(&ModelRequest::Difficulties {
+            words: vec![word.to_string()],
+            metric,
+        })?;
+        difficulties.pop().flatten()
+    }
+
+    fn pick_word(&self, rng: &mut dyn RngCore) -> String {
+        self.pick_word_where(rng, &|_| true)
+            .unwrap_or_else(|| self.words[rng.gen_range(0..self.words.len())].clone())
+    }
+
+    fn pick_word_where(&self, rng: &mut dyn RngCore, predicate: &dyn Fn(&str) -> bool) -> Option<String> {
+        self.candidates(predicate).choose(rng).map(|word| word.to_string())
+    }
+
+    fn pick_word_of_difficulty(
+        &self,
+        rng: &mut dyn RngCore,
+        level: DifficultyLevel,
+        metric: Metric,
+        predicate: &dyn Fn(&str) -> bool,
+    ) -> Option<String> {
+        let candidates = self.candidates(predicate);
+        let sample: Vec<&str> = candidates
+            .choose_multiple(rng, DIFFICULTY_SAMPLE_SIZE)
+            .copied()
+            .collect();
+        let difficulties: Vec<Option<Difficulty>> = self.call_or_log(&ModelRequest::Difficulties {
+            words: sample.iter().map(|word| word.to_string()).collect(),
+            metric,
+        })?;
+        let difficulties: Vec<Difficulty> = difficulties.into_iter().collect::<Option<_>>()?;
+        pick_by_difficulty(rng, level, &sample, &difficulties)
+    }
+
+    fn pickable_words(&self) -> Vec<&str> {
+        self.candidates(&|_| true)
+    }
+
+    fn frequency(&self, word: &str) -> Option<u64> {
+        self.call_or_log(&ModelRequest::Frequency { word: word.to_string() })?
+    }
+}
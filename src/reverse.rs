@@ -0,0 +1,134 @@
+//! Reverse mode: the bot guesses a word picked by a player
+//!
+//! The player picks a word in private messages, then tells the bot the similarity of each of its guesses to the
+//! word. The first guesses are random words, to find a region of the embedding space to start from. Then the bot
+//! looks for the word among the neighbors of its best guess, picking the one whose similarities to all its previous
+//! guesses are the closest to the reported ones.
+//!
+//! The bot only uses the word of the player to know when it found it.
+use crate::backend::EmbeddingBackend;
+use anyhow::{anyhow, Result};
+use rand::RngCore;
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+/// Number of random guesses before the bot searches around its best guess.
+const OPENING_GUESSES: usize = 3;
+
+/// Number of neighbors of the best guess among which the next guess is picked.
+const CANDIDATES: usize = 200;
+
+/// The bot gives up after this number of guesses.
+const MAX_GUESSES: usize = 100;
+
+/// Reverse games are abandoned after this long without a report.
+const REVERSE_TIMEOUT: Duration = Duration::from_secs(24 * 3600);
+
+/// Next move of the bot in a reverse game.
+pub enum ReverseGuess {
+    /// The bot guesses `word`, and waits for its similarity to the word of the player. `number` counts the guesses
+    /// from 1.
+    Guess { word: String, number: usize },
+    /// The bot found the word of the player with this number of guesses. The game is over.
+    Found { word: String, guesses: usize },
+    /// The bot gave up after this number of guesses. The game is over.
+    GaveUp { guesses: usize },
+}
+
+/// A reverse game of a player, played in private messages. It is only kept in memory.
+pub struct ReverseGame {
+    /// Word picked by the player.
+    word: String,
+    /// Guesses of the bot whose similarity was reported, and the similarity.
+    reports: Vec<(String, f32)>,
+    /// Last guess of the bot, whose similarity wasn't reported yet.
+    pending: Option<String>,
+    /// When the last similarity was reported, or the game started.
+    last_activity: Instant,
+}
+
+impl ReverseGame {
+    pub fn new(word: String) -> ReverseGame {
+        ReverseGame {
+            word,
+            reports: Vec::new(),
+            pending: None,
+            last_activity: Instant::now(),
+        }
+    }
+
+    pub fn expired(&self) -> bool {
+        self.last_activity.elapsed() >= REVERSE_TIMEOUT
+    }
+
+    /// Records the similarity of the last guess of the bot to the word of the player.
+    pub fn report(&mut self, similarity: f32) -> Result<()> {
+        let guess = self
+            .pending
+            .take()
+            .ok_or_else(|| anyhow!("I haven't made a guess yet"))?;
+        self.reports.push((guess, similarity));
+        self.last_activity = Instant::now();
+        Ok(())
+    }
+
+    /// Picks the next guess of the bot. Scans the vocabulary.
+    pub fn guess(&mut self, words: &dyn EmbeddingBackend, rng: &mut dyn RngCore) -> ReverseGuess {
+        let number = self.reports.len() + 1;
+        if number > MAX_GUESSES {
+            return ReverseGuess::GaveUp {
+                guesses: self.reports.len(),
+            };
+        }
+        let word = self.search(words).unwrap_or_else(|| self.random_word(words, rng));
+        if word == self.word {
+            return ReverseGuess::Found { word, guesses: number };
+        }
+        self.pending = Some(word.clone());
+        ReverseGuess::Guess { word, number }
+    }
+
+    /// Returns the unguessed neighbor of the best guess whose similarities to the previous guesses are the closest
+    /// to the reported ones, if the opening is over.
+    fn search(&self, words: &dyn EmbeddingBackend) -> Option<String> {
+        if self.reports.len() < OPENING_GUESSES {
+            return None;
+        }
+        let metric = words.metric();
+        let (best, _) = self.reports.iter().max_by(|a, b| a.1.total_cmp(&b.1))?;
+        let reports: Vec<_> = self
+            .reports
+            .iter()
+            .filter_map(|(guess, similarity)| Some((words.vector(guess)?, *similarity)))
+            .collect();
+        let guessed: HashSet<&str> = self.reports.iter().map(|(guess, _)| guess.as_str()).collect();
+        words
+            .neighbors(best, CANDIDATES, metric)?
+            .into_iter()
+            .filter(|(candidate, _)| !guessed.contains(candidate.as_str()))
+            .filter_map(|(candidate, _)| {
+                let vector = words.vector(&candidate)?;
+                let error: f32 = reports
+                    .iter()
+                    .map(|(guess, similarity)| (metric.similarity(&vector, guess) - similarity).powi(2))
+                    .sum();
+                Some((candidate, error))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(candidate, _)| candidate)
+    }
+
+    /// Returns a random word, avoiding the previous guesses if possible.
+    fn random_word(&self, words: &dyn EmbeddingBackend, rng: &mut dyn RngCore) -> String {
+        let mut word = words.pick_word(rng);
+        for _ in 1..10 {
+            if !self.reports.iter().any(|(guess, _)| *guess == word) {
+                break;
+            }
+            word = words.pick_word(rng);
+        }
+        word
+    }
+}
@@ -0,0 +1,260 @@
+//! JSON-RPC 2.0 admin interface
+//!
+//! Served on `POST /rpc` by the web server, and only enabled if an `admin_token` is configured.
+//! Requests must carry an `Authorization: Bearer <admin_token>` header.
+//!
+//! Methods:
+//! - `session.current`: returns the session in progress (including the word), or `null`
+//! - `session.start {duration?, metric?, mode?, strict?, budget?, word?, theme?}`: ends the current session and starts
+//!   a new one; `duration` is in seconds, `metric` is one of `cosine`, `dot` or `euclidean`, `mode` is `semantic`
+//!   (default), `wordle`, `rank` or `blitz` (whose sessions last 10 minutes whatever `duration`), `strict` enables
+//!   hard mode (each guess of a player must be closer than their previous ones), `budget` enables cooperative mode
+//!   with this number of guesses shared by all the players, `word` is the word to find instead of a random one,
+//!   `theme` restricts the word to find to a theme (e.g. `animals`)
+//! - `session.end`: ends the current session with no winner
+//! - `players.list`: returns all players
+//! - `players.leaderboard`: returns the score, wins and number of games of all players, the highest score first
+//! - `teams.leaderboard`: returns the score, wins and number of members of all teams, the highest score first
+//! - `settings.get`: returns the game settings
+use crate::game::{Game, SessionOptions};
+use axum::{
+    http::{header, HeaderMap, StatusCode},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+
+/// Settings of the admin interface.
+pub struct RpcSettings {
+    /// Bearer token that clients must present.
+    pub admin_token: String,
+    /// Default game duration, for `session.start`.
+    pub game_duration: Duration,
+}
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    /// Absent for notifications.
+    id: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct ErrorObject {
+    code: i64,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorObject>,
+    id: Value,
+}
+
+impl Response {
+    fn result(id: Value, result: Value) -> Response {
+        Response {
+            jsonrpc: "2.0",
+            result: Some(result),
+            error: None,
+            id,
+        }
+    }
+
+    fn error(id: Value, code: i64, message: impl Into<String>) -> Response {
+        Response {
+            jsonrpc: "2.0",
+            result: None,
+            error: Some(ErrorObject {
+                code,
+                message: message.into(),
+            }),
+            id,
+        }
+    }
+}
+
+/// Calls a method. Errors are returned as (code, message).
+async fn call(game: &Game, settings: &RpcSettings, method: &str, params: Value) -> Result<Value, (i64, String)> {
+    let server_error = |err: anyhow::Error| (SERVER_ERROR, err.to_string());
+
+    match method {
+        "session.current" => Ok(json!(game.current_session().await)),
+        "session.start" => {
+            let duration = match params.get("duration") {
+                None | Some(Value::Null) => settings.game_duration,
+                Some(d) => Duration::from_secs(
+                    d.as_u64()
+                        .ok_or((INVALID_PARAMS, "`duration` must be a number of seconds".to_string()))?,
+                ),
+            };
+            let metric = match params.get("metric") {
+                None | Some(Value::Null) => None,
+                Some(m) => Some(
+                    m.as_str()
+                        .and_then(|m| m.parse().ok())
+                        .ok_or((INVALID_PARAMS, "invalid `metric`".to_string()))?,
+                ),
+            };
+            let mode = match params.get("mode") {
+                None | Some(Value::Null) => Default::default(),
+                Some(m) => m
+                    .as_str()
+                    .and_then(|m| m.parse().ok())
+                    .ok_or((INVALID_PARAMS, "invalid `mode`".to_string()))?,
+            };
+            let difficulty = match params.get("difficulty") {
+                None | Some(Value::Null) => None,
+                Some(d) => Some(
+                    d.as_str()
+                        .and_then(|d| d.parse().ok())
+                        .ok_or((INVALID_PARAMS, "invalid `difficulty`".to_string()))?,
+                ),
+            };
+            let language = match params.get("language") {
+                None | Some(Value::Null) => None,
+                Some(l) => Some(
+                    l.as_str()
+                        .ok_or((INVALID_PARAMS, "invalid `language`".to_string()))?
+                        .to_string(),
+                ),
+            };
+            let strict = match params.get("strict") {
+                None | Some(Value::Null) => false,
+                Some(s) => s
+                    .as_bool()
+                    .ok_or((INVALID_PARAMS, "`strict` must be a boolean".to_string()))?,
+            };
+            let budget = match params.get("budget") {
+                None | Some(Value::Null) => None,
+                Some(b) => Some(
+                    b.as_u64()
+                        .filter(|&b| b > 0)
+                        .ok_or((INVALID_PARAMS, "`budget` must be a positive number of guesses".to_string()))?
+                        as usize,
+                ),
+            };
+            let theme = match params.get("theme") {
+                None | Some(Value::Null) => None,
+                Some(t) => Some(
+                    t.as_str()
+                        .ok_or((INVALID_PARAMS, "invalid `theme`".to_string()))?
+                        .to_string(),
+                ),
+            };
+            let word = match params.get("word") {
+                None | Some(Value::Null) => None,
+                Some(w) => Some(
+                    w.as_str()
+                        .ok_or((INVALID_PARAMS, "invalid `word`".to_string()))?
+                        .to_string(),
+                ),
+            };
+            game.start_game(
+                duration,
+                SessionOptions {
+                    mode,
+                    metric,
+                    difficulty,
+                    language,
+                    strict,
+                    budget,
+                    word,
+                    theme,
+                },
+            )
+                .await
+                .map_err(server_error)?;
+            Ok(json!(game.current_session().await))
+        }
+        "session.end" => {
+            game.end_game().await.map_err(server_error)?;
+            Ok(Value::Null)
+        }
+        "players.list" => Ok(json!(game.fetch_players().await.map_err(server_error)?)),
+        "players.leaderboard" => Ok(json!(game.leaderboard().await.map_err(server_error)?)),
+        "teams.leaderboard" => Ok(json!(game.team_leaderboard().await.map_err(server_error)?)),
+        "settings.get" => Ok(json!({
+            "game_duration": settings.game_duration.as_secs(),
+        })),
+        _ => Err((METHOD_NOT_FOUND, format!("method not found: `{}`", method))),
+    }
+}
+
+/// Handles a single request object. Returns `None` for notifications.
+async fn handle_request(game: &Game, settings: &RpcSettings, request: Value) -> Option<Response> {
+    let request: Request = match serde_json::from_value(request) {
+        Ok(request) => request,
+        Err(err) => return Some(Response::error(Value::Null, INVALID_REQUEST, err.to_string())),
+    };
+    if request.jsonrpc != "2.0" {
+        return Some(Response::error(
+            request.id.unwrap_or(Value::Null),
+            INVALID_REQUEST,
+            "unsupported JSON-RPC version",
+        ));
+    }
+
+    let result = call(game, settings, &request.method, request.params).await;
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => Response::result(id, result),
+        Err((code, message)) => Response::error(id, code, message),
+    })
+}
+
+/// `POST /rpc`
+pub async fn rpc_handler(
+    Extension(game): Extension<Game>,
+    Extension(settings): Extension<Option<Arc<RpcSettings>>>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Json<Value>, StatusCode> {
+    let settings = settings.ok_or(StatusCode::NOT_FOUND)?;
+
+    let authorized = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        == Some(settings.admin_token.as_str());
+    if !authorized {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let request: Value = match serde_json::from_str(&body) {
+        Ok(request) => request,
+        Err(err) => return Ok(Json(json!(Response::error(Value::Null, PARSE_ERROR, err.to_string())))),
+    };
+
+    match request {
+        Value::Array(batch) => {
+            if batch.is_empty() {
+                return Ok(Json(json!(Response::error(
+                    Value::Null,
+                    INVALID_REQUEST,
+                    "empty batch"
+                ))));
+            }
+            let mut responses = Vec::new();
+            for request in batch {
+                responses.extend(handle_request(&game, &settings, request).await);
+            }
+            Ok(Json(json!(responses)))
+        }
+        request => Ok(Json(json!(handle_request(&game, &settings, request).await))),
+    }
+}
@@ -1,13 +1,19 @@
 //! Web server to display game state
-use crate::game::{fetch_players, Player};
+use crate::{
+    game::{Game, GuessRecord, LeaderboardEntry, PlayerProfile, PlayerStats, SessionTranscript, TeamStats},
+    remote::{model_handler, ModelApiSettings},
+    rpc::{rpc_handler, RpcSettings},
+};
 use askama::Template;
 use axum::{
-    http::StatusCode,
-    response::{Html, IntoResponse, Response},
-    routing::get,
-    Extension, Router,
+    extract::Path,
+    http::{header, StatusCode},
+    response::{Html, IntoResponse},
+    routing::{get, post},
+    Extension, Json, Router,
 };
-use std::{error::Error, fmt::Display, path::Path, time::Duration};
+use chrono::{TimeZone, Utc};
+use std::{fmt::Display, net::SocketAddr, sync::Arc};
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Utilities
@@ -31,7 +37,7 @@ impl<T, E: Display> IntoHttpError<T> for Result<T, E> {
             Ok(val) => Ok(val),
             Err(err) => {
                 error!("http_error: {}", err);
-                Err((status_code, format!("{}:{}", message, err.to_string())))
+                Err((status_code, format!("{}:{}", message, err)))
             }
         }
     }
@@ -54,37 +60,167 @@ impl<T> IntoHttpError<T> for anyhow::Result<T> {
 // Templates
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+/// Number of guesses shown on the game page.
+const RECENT_GUESSES: usize = 20;
+
 #[derive(Template)]
 #[template(path = "game.html")]
 struct GameTemplate {
-    players: Vec<Player>,
+    /// Players, the highest score first.
+    players: Vec<PlayerStats>,
+    /// Whether there's a season in progress, whose scores are shown.
+    seasons: bool,
+    /// Teams, the highest score first.
+    teams: Vec<TeamStats>,
+    /// Best guesses of the current or last session.
+    leaderboard: Vec<LeaderboardEntry>,
+    /// Last guesses of the current or last session, the most recent first.
+    guesses: Vec<GuessRecord>,
+    /// Whether the word of the current session was found and the other players keep looking for it in private, in
+    /// which case the guesses are not shown.
+    late_solving: bool,
+}
+
+#[derive(Template)]
+#[template(path = "player.html")]
+struct PlayerTemplate {
+    profile: PlayerProfile,
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Server
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-struct State {}
-
-async fn root(Extension(pool): Extension<SqlitePool>) -> Result<Html<String>, (StatusCode, String)> {
-    let players = fetch_players(&pool)
+async fn root(Extension(game): Extension<Game>) -> Result<Html<String>, (StatusCode, String)> {
+    let players = game
+        .leaderboard()
         .await
         .http_internal_error("could not fetch players")?;
-    let template = GameTemplate { players };
+    // there may be no session yet, and the guesses of the late solvers are not revealed
+    let late_solving = game.late_solving().await;
+    let leaderboard = if late_solving {
+        Vec::new()
+    } else {
+        game.session_leaderboard().await.unwrap_or_default()
+    };
+    let guesses = match game.recent_session_id().await {
+        Some(session_id) if !late_solving => game
+            .recent_guesses(session_id, RECENT_GUESSES)
+            .await
+            .http_internal_error("could not fetch guesses")?,
+        _ => Vec::new(),
+    };
+    let seasons = players.iter().any(|stats| stats.season_score.is_some());
+    let teams = game
+        .team_leaderboard()
+        .await
+        .http_internal_error("could not fetch teams")?;
+    let template = GameTemplate {
+        players,
+        seasons,
+        teams,
+        leaderboard,
+        guesses,
+        late_solving,
+    };
     let html = template.render().http_internal_error("failed to render template")?;
     Ok(Html(html))
 }
 
-pub async fn launch_server(pool: SqlitePool) {
+/// `GET /players/:nick`: profile page of a player.
+async fn player(
+    Extension(game): Extension<Game>,
+    Path(nick): Path<String>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let profile = game
+        .player_stats(nick)
+        .await
+        .http_internal_error("could not fetch player")?
+        .ok_or((StatusCode::NOT_FOUND, "unknown player".to_string()))?;
+    let template = PlayerTemplate { profile };
+    let html = template.render().http_internal_error("failed to render template")?;
+    Ok(Html(html))
+}
+
+/// `GET /players/:nick/share`: share text of the last session won by a player, as plain text.
+async fn share(
+    Extension(game): Extension<Game>,
+    Path(nick): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let share = game
+        .share(nick)
+        .await
+        .http_internal_error("could not fetch share text")?
+        .ok_or((StatusCode::NOT_FOUND, "no game won".to_string()))?;
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], share))
+}
+
+/// `GET /sessions/:id`: transcript of an ended session, as JSON.
+async fn session(
+    Extension(game): Extension<Game>,
+    Path(session_id): Path<i64>,
+) -> Result<Json<SessionTranscript>, (StatusCode, String)> {
+    let transcript = game
+        .export_session(session_id)
+        .await
+        .http_internal_error("could not fetch session")?
+        .ok_or((StatusCode::NOT_FOUND, "unknown or unfinished session".to_string()))?;
+    Ok(Json(transcript))
+}
+
+/// Formats a UNIX timestamp as an iCalendar UTC date-time.
+fn ical_date(timestamp: i64) -> String {
+    Utc.timestamp_opt(timestamp, 0)
+        .unwrap()
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// `GET /calendar.ics`: iCalendar feed of game sessions.
+async fn calendar(Extension(game): Extension<Game>) -> impl IntoResponse {
+    let mut ics = String::new();
+    ics.push_str("BEGIN:VCALENDAR\r\n");
+    ics.push_str("VERSION:2.0\r\n");
+    ics.push_str("PRODID:-//wordlebot//game calendar//EN\r\n");
+    ics.push_str("X-WR-CALNAME:Cabotin\r\n");
+
+    let now = ical_date(Utc::now().timestamp());
+    if let Some(session) = game.current_session().await {
+        ics.push_str("BEGIN:VEVENT\r\n");
+        ics.push_str(&format!("UID:session-{}@wordlebot\r\n", session.id));
+        ics.push_str(&format!("DTSTAMP:{}\r\n", now));
+        ics.push_str(&format!("DTSTART:{}\r\n", ical_date(session.start_date)));
+        ics.push_str(&format!("DTEND:{}\r\n", ical_date(session.planned_end_date)));
+        ics.push_str(&format!("SUMMARY:Cabotin game #{}\r\n", session.id));
+        ics.push_str("END:VEVENT\r\n");
+    }
+
+    ics.push_str("END:VCALENDAR\r\n");
+    ([(header::CONTENT_TYPE, "text/calendar; charset=utf-8")], ics)
+}
+
+/// Launches the web server. The JSON-RPC admin interface is enabled only if `rpc_settings` is set, and the
+/// model API only if `model_api_settings` is set.
+pub async fn launch_server(
+    addr: SocketAddr,
+    game: Game,
+    rpc_settings: Option<RpcSettings>,
+    model_api_settings: Option<ModelApiSettings>,
+) {
     // build our application with a route
     let app = Router::new()
         // `GET /` goes to `root`
         .route("/", get(root))
-        .layer(Extension(pool));
+        .route("/players/:nick", get(player))
+        .route("/players/:nick/share", get(share))
+        .route("/sessions/:id", get(session))
+        .route("/calendar.ics", get(calendar))
+        .route("/rpc", post(rpc_handler))
+        .route("/model", post(model_handler))
+        .layer(Extension(game))
+        .layer(Extension(rpc_settings.map(Arc::new)))
+        .layer(Extension(model_api_settings.map(Arc::new)));
 
-    // run it with hyper on localhost:3000
-    axum::Server::bind(&"0.0.0.0:3000".parse().unwrap())
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    // run it with hyper
+    axum::Server::bind(&addr).serve(app.into_make_service()).await.unwrap();
 }
@@ -0,0 +1,199 @@
+//! Game simulation harness (`wordlebot simulate`)
+//!
+//! Runs synthetic players against the real game logic (with an in-memory database) and reports
+//! how fast sessions converge. Useful to tune scoring, hints and difficulty.
+use crate::{
+    backend::EmbeddingBackend,
+    game::{Game, Outcome, ScoringConfig, TargetSettings},
+    storage::{SqliteConfig, SqliteStorage},
+    words::SharedWords,
+};
+use anyhow::{anyhow, Result};
+use rand::Rng;
+use std::{collections::HashSet, time::Duration};
+
+/// Number of neighbors considered by greedy players when choosing their next guess.
+const GREEDY_NEIGHBORS: usize = 50;
+
+/// Simulation parameters.
+pub struct SimulationOptions {
+    pub sessions: usize,
+    pub greedy_players: usize,
+    pub random_players: usize,
+    /// Sessions are abandoned after this number of guesses.
+    pub max_guesses: usize,
+}
+
+impl SimulationOptions {
+    /// Parses `--sessions N --greedy N --random N --max-guesses N`.
+    pub fn parse(args: &[String]) -> Result<SimulationOptions> {
+        let mut options = SimulationOptions {
+            sessions: 100,
+            greedy_players: 3,
+            random_players: 3,
+            max_guesses: 1000,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value: usize = args
+                .next()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| anyhow!("expected a number after `{}`", arg))?;
+            match arg.as_str() {
+                "--sessions" => options.sessions = value,
+                "--greedy" => options.greedy_players = value,
+                "--random" => options.random_players = value,
+                "--max-guesses" => options.max_guesses = value,
+                _ => return Err(anyhow!("unknown option `{}`", arg)),
+            }
+        }
+        if options.greedy_players + options.random_players == 0 {
+            return Err(anyhow!("there must be at least one player"));
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Strategy {
+    /// Guesses random words.
+    Random,
+    /// Guesses the closest unguessed neighbor of its best guess so far.
+    Greedy,
+}
+
+struct SimulatedPlayer {
+    nick: String,
+    strategy: Strategy,
+    /// Best guess so far and its similarity.
+    best: Option<(String, f32)>,
+}
+
+impl SimulatedPlayer {
+    fn next_guess(&self, words: &dyn EmbeddingBackend, guessed: &HashSet<String>) -> String {
+        if self.strategy == Strategy::Greedy {
+            if let Some((ref best, _)) = self.best {
+                let next = words
+                    .neighbors(best, GREEDY_NEIGHBORS, words.metric())
+                    .into_iter()
+                    .flatten()
+                    .map(|(word, _)| word)
+                    .find(|word| !guessed.contains(word));
+                if let Some(next) = next {
+                    return next;
+                }
+            }
+        }
+        // random guess, avoiding already guessed words if possible
+        let mut rng = rand::thread_rng();
+        let mut word = String::new();
+        for _ in 0..10 {
+            word = words.word(rng.gen_range(0..words.len())).to_string();
+            if !guessed.contains(&word) {
+                break;
+            }
+        }
+        word
+    }
+}
+
+/// Runs the simulation and prints a report on stdout.
+pub async fn simulate(shared_words: SharedWords, options: SimulationOptions) -> Result<()> {
+    let words = shared_words.get()?.clone();
+    let storage = Box::new(SqliteStorage::open(":memory:", &SqliteConfig::default()).await?);
+    let game = Game::load(
+        storage,
+        shared_words.into(),
+        TargetSettings::default(),
+        None,
+        ScoringConfig::default(),
+        None,
+    )
+    .await?;
+
+    let mut players: Vec<SimulatedPlayer> = (0..options.greedy_players)
+        .map(|i| (format!("greedy{}", i), Strategy::Greedy))
+        .chain((0..options.random_players).map(|i| (format!("random{}", i), Strategy::Random)))
+        .map(|(nick, strategy)| SimulatedPlayer {
+            nick,
+            strategy,
+            best: None,
+        })
+        .collect();
+
+    // number of guesses needed to win each solved session
+    let mut solved = Vec::new();
+    let mut greedy_wins = 0;
+    let mut random_wins = 0;
+    let mut unknown_words = 0;
+
+    for _ in 0..options.sessions {
+        game.start_game(Duration::from_secs(3600), Default::default()).await?;
+        let mut guessed = HashSet::new();
+        for player in players.iter_mut() {
+            player.best = None;
+        }
+
+        'session: for guess_count in 1..=options.max_guesses {
+            let player = &mut players[(guess_count - 1) % (options.greedy_players + options.random_players)];
+            let guess = player.next_guess(words.as_ref(), &guessed);
+            guessed.insert(guess.clone());
+            match game.process_guess(player.nick.clone(), guess.clone()).await? {
+                Outcome::Win { .. } => {
+                    solved.push(guess_count);
+                    match player.strategy {
+                        Strategy::Greedy => greedy_wins += 1,
+                        Strategy::Random => random_wins += 1,
+                    }
+                    break 'session;
+                }
+                Outcome::Miss { distance, .. } => {
+                    if player.best.as_ref().is_none_or(|(_, best)| distance > *best) {
+                        player.best = Some((guess, distance));
+                    }
+                }
+                Outcome::UnknownWord { .. } | Outcome::StopWord => unknown_words += 1,
+                // simulated sessions are semantic
+                Outcome::RankMiss { .. } | Outcome::WordleMiss { .. } | Outcome::WrongLength { .. } => {
+                    unreachable!("Wordle outcome in a semantic session")
+                }
+                // random players may repeat a guess when they can't find a new word
+                Outcome::AlreadyGuessed { .. } => {}
+                Outcome::NotCloser { .. } => unreachable!("simulated sessions are not in hard mode"),
+                Outcome::OutOfGuesses { .. } => unreachable!("simulated sessions are not cooperative"),
+                Outcome::AlreadyFound => unreachable!("simulated sessions end with the first winner"),
+            }
+        }
+
+        if game.current_session().await.is_some() {
+            game.end_game().await?;
+        }
+    }
+
+    solved.sort_unstable();
+    println!(
+        "players: {} greedy, {} random",
+        options.greedy_players, options.random_players
+    );
+    println!(
+        "sessions solved: {}/{} (max {} guesses per session)",
+        solved.len(),
+        options.sessions,
+        options.max_guesses
+    );
+    if !solved.is_empty() {
+        let mean = solved.iter().sum::<usize>() as f64 / solved.len() as f64;
+        println!(
+            "guesses to solve: mean {:.1}, median {}, min {}, max {}",
+            mean,
+            solved[solved.len() / 2],
+            solved[0],
+            solved[solved.len() - 1]
+        );
+        println!("wins: {} greedy, {} random", greedy_wins, random_wins);
+    }
+    if unknown_words > 0 {
+        println!("unknown words: {}", unknown_words);
+    }
+    Ok(())
+}
@@ -0,0 +1,252 @@
+//! Persistence layer
+//!
+//! The game state is stored through the [`Storage`] trait. SQLite is the default backend;
+//! PostgreSQL can be used for larger deployments.
+mod postgres;
+mod sqlite;
+
+pub use self::{
+    postgres::PostgresStorage,
+    sqlite::{SqliteConfig, SqliteStorage},
+};
+pub(crate) use sqlite::{restore_backup, setup_schema};
+
+use crate::{
+    achievements::{Achievement, UnlockedAchievement},
+    game::{
+        GuessRecord, LeaderboardEntry, Player, PlayerProfile, PlayerStats, Season, Session, SessionTranscript,
+        SessionWinner, TeamStats, Tournament,
+    },
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+
+/// Which database backend to use.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DbBackend {
+    #[default]
+    Sqlite,
+    Postgres,
+}
+
+/// Outcome of a session, recorded when it ends.
+#[derive(Clone, Debug)]
+pub struct SessionResult {
+    pub end_date: i64,
+    pub winner_id: Option<i64>,
+    /// Text the winner can share.
+    pub share: Option<String>,
+    /// Points won by the players (`(player ID, points)`).
+    pub points: Vec<(i64, i64)>,
+    /// New rating of the players of the session (`(player ID, rating)`).
+    pub ratings: Vec<(i64, f64)>,
+    /// Points won by the players registered to the tournament in progress for their place in the session
+    /// (`(player ID, points)`).
+    pub tournament_points: Vec<(i64, i64)>,
+}
+
+/// Operations on the persistent game state.
+///
+/// Dates are UNIX timestamps in seconds.
+#[async_trait]
+pub trait Storage: Send {
+    /// Returns the session in progress, if there is one.
+    async fn current_session(&mut self) -> Result<Option<Session>>;
+
+    /// Returns the ID of the player with the specified nick or alias, creating the player if necessary.
+    async fn player_id(&mut self, nick: &str) -> Result<i64>;
+
+    /// Returns the ID of the player with the specified nick or alias, if there's one.
+    async fn find_player(&mut self, nick: &str) -> Result<Option<i64>>;
+
+    /// Makes `alias` another nick of a player, replacing the player it was an alias of if any.
+    async fn add_alias(&mut self, alias: &str, player_id: i64) -> Result<()>;
+
+    /// Merges a player into another one, in a single transaction: their guesses, wins, points, achievements and
+    /// aliases go to the other player, who keeps their own team and rating if they have one. The merged player is
+    /// deleted, and their nick becomes an alias of the other player.
+    async fn merge_players(&mut self, from_id: i64, into_id: i64) -> Result<()>;
+
+    /// Anonymizes a player, in a single transaction: the player is renamed `nick`, and their aliases, achievements,
+    /// team membership and rating are deleted. Their guesses, wins and points stay, so the results of the other
+    /// players don't change. Returns the nicks the player was known by.
+    async fn forget_player(&mut self, player_id: i64, nick: &str) -> Result<Vec<String>>;
+
+    /// Records a guess made at `date`, with the time elapsed since the start of the session. `team_id` is the team
+    /// the guess is credited to, if the player is in one.
+    async fn insert_guess(
+        &mut self,
+        session_id: i64,
+        player_id: i64,
+        team_id: Option<i64>,
+        guess: &str,
+        cosine: f32,
+        date: i64,
+    ) -> Result<()>;
+
+    /// Creates a new session and makes it the current one, in a single transaction. Returns the ID of the new session.
+    ///
+    /// The `id` of `session` is ignored.
+    async fn start_session(&mut self, session: &Session) -> Result<i64>;
+
+    /// Returns the number of guesses of a player in a session.
+    async fn guess_count(&mut self, session_id: i64, player_id: i64) -> Result<usize>;
+
+    /// Returns the best guess of each player of a session, the closest first. Ranks and heats are not filled.
+    async fn best_guesses(&mut self, session_id: i64) -> Result<Vec<LeaderboardEntry>>;
+
+    /// Returns the player ID and the guess of all the guesses of a session.
+    async fn session_guesses(&mut self, session_id: i64) -> Result<Vec<(i64, String)>>;
+
+    /// Records that a player found the word of a session, at `place` (from 0) among the players who found it, and
+    /// the points they won for it.
+    async fn add_winner(
+        &mut self,
+        session_id: i64,
+        player_id: i64,
+        place: usize,
+        points: i64,
+        date: i64,
+    ) -> Result<()>;
+
+    /// Returns the players who found the word of a session, in the order they found it.
+    async fn session_winners(&mut self, session_id: i64) -> Result<Vec<SessionWinner>>;
+
+    /// Returns the number of guesses of each player of a session (`(player ID, guesses)`), including the archived
+    /// sessions.
+    async fn guess_counts(&mut self, session_id: i64) -> Result<Vec<(i64, usize)>>;
+
+    /// Rolls the guesses of the sessions that ended before `before` into the results of each player of the
+    /// sessions, and deletes them. Returns the number of deleted guesses.
+    async fn archive_guesses(&mut self, before: i64) -> Result<usize>;
+
+    /// Returns the guess, the nick of the player and the similarity of all the guesses of a session, in the order
+    /// they were made.
+    async fn guess_history(&mut self, session_id: i64) -> Result<Vec<(String, String, f32)>>;
+
+    /// Returns the session with the specified ID, its outcome and all its guesses in the order they were made, if
+    /// there's one.
+    async fn session_transcript(&mut self, session_id: i64) -> Result<Option<SessionTranscript>>;
+
+    /// Returns the last `limit` guesses of a session, the most recent first.
+    async fn recent_guesses(&mut self, session_id: i64, limit: usize) -> Result<Vec<GuessRecord>>;
+
+    /// Returns the share text of the last session won by a player, if there's one.
+    async fn last_share(&mut self, nick: &str) -> Result<Option<String>>;
+
+    /// Sets the number of hints given in a session.
+    async fn set_session_hints(&mut self, session_id: i64, hints: usize) -> Result<()>;
+
+    /// Marks a session as ended, adds the points won by the players to their all-time score, to their score of the
+    /// current season if there's one and to the score of the team their last guess of the session was credited to,
+    /// adds their tournament points to the tournament in progress, updates their ratings and clears the current
+    /// session, in a single transaction.
+    async fn end_session(&mut self, session_id: i64, result: &SessionResult) -> Result<()>;
+
+    /// Returns all known players.
+    async fn fetch_players(&mut self) -> Result<Vec<Player>>;
+
+    /// Returns the results of all known players, the highest season score first if there's a current season,
+    /// then the highest all-time score.
+    async fn player_stats(&mut self) -> Result<Vec<PlayerStats>>;
+
+    /// Returns the detailed results of the player with the specified nick, if there's one.
+    async fn player_profile(&mut self, nick: &str) -> Result<Option<PlayerProfile>>;
+
+    /// Returns the season in progress, if there is one.
+    async fn current_season(&mut self) -> Result<Option<Season>>;
+
+    /// Creates a new season and makes it the current one. Returns the ID of the new season.
+    async fn start_season(&mut self, start_date: i64) -> Result<i64>;
+
+    /// Returns the players who scored points during a season, with their season score in place of their
+    /// all-time score, the best first.
+    async fn season_scores(&mut self, season_id: i64) -> Result<Vec<Player>>;
+
+    /// Marks a season as ended.
+    async fn end_season(&mut self, season_id: i64, end_date: i64, winner_id: Option<i64>) -> Result<()>;
+
+    /// Returns the tournament in progress, if there is one. It may be past its end date.
+    async fn current_tournament(&mut self) -> Result<Option<Tournament>>;
+
+    /// Creates a new tournament and makes it the current one. Returns the ID of the new tournament.
+    async fn start_tournament(&mut self, name: &str, start_date: i64, end_date: i64) -> Result<i64>;
+
+    /// Registers a player to a tournament. Returns `false` if they were already registered.
+    async fn join_tournament(&mut self, tournament_id: i64, player_id: i64) -> Result<bool>;
+
+    /// Returns the players registered to a tournament, with their tournament points in place of their all-time
+    /// score, the best first.
+    async fn tournament_standings(&mut self, tournament_id: i64) -> Result<Vec<Player>>;
+
+    /// Marks a tournament as ended.
+    async fn end_tournament(&mut self, tournament_id: i64, winner_id: Option<i64>) -> Result<()>;
+
+    /// Returns the ID and the winner of all ended sessions, in the order they ended.
+    async fn ended_sessions(&mut self) -> Result<Vec<(i64, Option<i64>)>>;
+
+    /// Returns the average number of guesses the winners of the ended sessions needed, if there's one.
+    async fn average_guesses_to_win(&mut self) -> Result<Option<f64>>;
+
+    /// Returns the ID and the rating of all rated players.
+    async fn player_ratings(&mut self) -> Result<Vec<(i64, f64)>>;
+
+    /// Replaces the ratings of all players: the players missing from `ratings` become unrated.
+    async fn reset_ratings(&mut self, ratings: &[(i64, f64)]) -> Result<()>;
+
+    /// Returns the start dates of the sessions a player took part in.
+    async fn play_dates(&mut self, player_id: i64) -> Result<Vec<i64>>;
+
+    /// Records that a player unlocked an achievement. Returns `false` if they already had it.
+    async fn unlock_achievement(&mut self, player_id: i64, achievement: Achievement, date: i64) -> Result<bool>;
+
+    /// Returns the achievements of a player, the oldest first.
+    async fn player_achievements(&mut self, player_id: i64) -> Result<Vec<UnlockedAchievement>>;
+
+    /// Returns the ID of the team of a player, if they're in one.
+    async fn player_team(&mut self, player_id: i64) -> Result<Option<i64>>;
+
+    /// Makes a player join the team with the specified name, creating the team if necessary. Players are in one
+    /// team at most: they leave their previous team.
+    async fn join_team(&mut self, player_id: i64, team: &str) -> Result<()>;
+
+    /// Removes a player from their team. Returns `false` if they were not in a team.
+    async fn leave_team(&mut self, player_id: i64) -> Result<bool>;
+
+    /// Returns the player ID and the team ID of the players of a session whose last guess was credited to a team.
+    async fn session_teams(&mut self, session_id: i64) -> Result<Vec<(i64, i64)>>;
+
+    /// Returns the results of all teams, the highest score first.
+    async fn team_stats(&mut self) -> Result<Vec<TeamStats>>;
+
+    /// Returns the words of the last `count` sessions, the most recent first.
+    async fn recent_words(&mut self, count: usize) -> Result<Vec<String>>;
+
+    /// Returns the cached definition of a word, if there's one.
+    async fn definition(&mut self, word: &str) -> Result<Option<String>>;
+
+    /// Caches the definition of a word, replacing any previous one.
+    async fn insert_definition(&mut self, word: &str, definition: &str) -> Result<()>;
+
+    /// Writes a copy of the database to the backup directory, with the current date and time in its name, and
+    /// deletes the oldest copies. Returns the path of the copy.
+    async fn backup(&mut self) -> Result<String>;
+}
+
+/// Opens the storage backend selected in the configuration.
+pub async fn open(
+    backend: DbBackend,
+    db_path: &str,
+    sqlite: &SqliteConfig,
+    postgres_url: Option<&str>,
+) -> Result<Box<dyn Storage>> {
+    match backend {
+        DbBackend::Sqlite => Ok(Box::new(SqliteStorage::open(db_path, sqlite).await?)),
+        DbBackend::Postgres => {
+            let url = postgres_url.ok_or_else(|| anyhow!("`postgres_url` must be set to use the postgres backend"))?;
+            Ok(Box::new(PostgresStorage::connect(url).await?))
+        }
+    }
+}
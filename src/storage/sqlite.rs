@@ -0,0 +1,1780 @@
+//! SQLite storage backend
+use super::{SessionResult, Storage};
+use crate::{
+    achievements::{Achievement, UnlockedAchievement},
+    game::{
+        GameMode, GuessRecord, LeaderboardEntry, Player, PlayerProfile, PlayerStats, Season, Session,
+        SessionTranscript, SessionWinner, TeamStats, Tournament, TranscriptGuess,
+    },
+    words::{Difficulty, Metric},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use rusqlite::{params, DatabaseName, OpenFlags, OptionalExtension};
+use serde::Deserialize;
+use std::{fs, path::Path, time::Duration};
+
+fn default_journal_mode() -> String {
+    "wal".to_string()
+}
+
+fn default_busy_timeout() -> u64 {
+    5000
+}
+
+fn default_synchronous() -> String {
+    "normal".to_string()
+}
+
+fn default_read_connections() -> usize {
+    4
+}
+
+fn default_backup_keep() -> usize {
+    7
+}
+
+/// `[sqlite]` section of the configuration.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SqliteConfig {
+    /// Journal mode of the database (`PRAGMA journal_mode`). In `wal` mode, the readers don't wait for the writes.
+    #[serde(default = "default_journal_mode")]
+    pub journal_mode: String,
+    /// How long a query waits for the database to be unlocked, in milliseconds (`PRAGMA busy_timeout`).
+    #[serde(default = "default_busy_timeout")]
+    pub busy_timeout: u64,
+    /// When the writes are synced to disk (`PRAGMA synchronous`).
+    #[serde(default = "default_synchronous")]
+    pub synchronous: String,
+    /// Number of read-only connections, used by the queries that don't write. With 0, or for in-memory databases,
+    /// all the queries use the connection that writes.
+    #[serde(default = "default_read_connections")]
+    pub read_connections: usize,
+    /// Directory where the backups of the database are written, with the date and time in their file name. There
+    /// are no backups if unset.
+    #[serde(default)]
+    pub backup_directory: Option<String>,
+    /// Interval between two backups, in hours. Backups are only made with `!backup` if unset.
+    #[serde(default)]
+    pub backup_interval: Option<u64>,
+    /// Number of backups kept in `backup_directory`, the oldest ones are deleted. All of them are kept with 0.
+    #[serde(default = "default_backup_keep")]
+    pub backup_keep: usize,
+}
+
+impl Default for SqliteConfig {
+    fn default() -> SqliteConfig {
+        SqliteConfig {
+            journal_mode: default_journal_mode(),
+            busy_timeout: default_busy_timeout(),
+            synchronous: default_synchronous(),
+            read_connections: default_read_connections(),
+            backup_directory: None,
+            backup_interval: None,
+            backup_keep: default_backup_keep(),
+        }
+    }
+}
+
+/// Schema migrations, in the order they are applied. The version of a database is the number of migrations applied
+/// to it; new migrations must be appended to the list, and the existing ones never changed.
+const MIGRATIONS: &[fn(&rusqlite::Connection) -> Result<()>] =
+    &[initial_schema, guess_dates, aliases, session_stats, tournaments, session_themes, winners];
+
+/// Creates the database tables, or brings the schema of an existing database up to date.
+pub(crate) fn setup_schema(conn: &mut rusqlite::Connection) -> Result<()> {
+    conn.execute_batch("CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL);")?;
+    let tx = conn.transaction()?;
+    let version = tx
+        .query_row("SELECT version FROM schema_version", [], |row| row.get::<_, usize>(0))
+        .optional()?
+        .unwrap_or(0);
+    if version > MIGRATIONS.len() {
+        bail!(
+            "the database schema (version {}) is newer than this version of wordlebot supports ({})",
+            version,
+            MIGRATIONS.len()
+        );
+    }
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        migration(&tx).with_context(|| format!("schema migration {} failed", i + 1))?;
+    }
+    tx.execute("DELETE FROM schema_version", [])?;
+    tx.execute("INSERT INTO schema_version(version) VALUES (?1)", [MIGRATIONS.len()])?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Migration 1: the schema as it was before migrations were versioned.
+///
+/// Databases created before that are at version 0 whatever their schema, so this only creates the tables and the
+/// columns that are missing.
+fn initial_schema(conn: &rusqlite::Connection) -> Result<()> {
+    // players: ID -> nick, score (total score), rating (NULL until the player has finished a session)
+    // sessions (result of past sessions): ID -> start_date, end_date, planned_end_date, word, winner, is_current (whether the session is in progress)
+    // current_session:
+    // guesses (records all guesses made across all sessions): ID -> session ID, player ID, guess, cosine
+    // seasons: ID -> start_date, end_date (NULL for the current season), winner
+    // season_scores: season ID, player ID -> score (points won during the season)
+    // achievements: player ID, achievement -> date (when the achievement was unlocked)
+    // teams: ID -> name, score (total of the points won by the members for the team)
+    // team_members: player ID -> team ID (a player is in one team at most)
+    conn.execute_batch(
+        // language=SQLITE-SQL
+        r#"
+CREATE TABLE IF NOT EXISTS players
+         (id      INTEGER PRIMARY KEY,
+          nick    TEXT UNIQUE NOT NULL,
+          score   INTEGER);
+
+CREATE TABLE IF NOT EXISTS sessions
+         (id               INTEGER PRIMARY KEY,
+		  start_date       INTEGER,
+          end_date         INTEGER,
+		  planned_end_date INTEGER,
+		  word             TEXT NOT NULL,
+		  winner_id        INTEGER REFERENCES players(id) ON DELETE NO ACTION,
+		  playing          INTEGER);
+
+CREATE TABLE IF NOT EXISTS current_session
+         (id            INTEGER PRIMARY KEY DEFAULT 0,
+          session_id    INTEGER REFERENCES sessions(id) ON DELETE NO ACTION);
+
+INSERT OR IGNORE INTO current_session(id) VALUES (0);
+
+CREATE TABLE IF NOT EXISTS guesses
+         (id         INTEGER PRIMARY KEY,
+          session_id INTEGER REFERENCES sessions(id) ON DELETE NO ACTION,
+          player_id  INTEGER REFERENCES players(id) ON DELETE NO ACTION,
+          guess      TEXT NOT NULL,
+          cosine     NUMERIC);
+
+CREATE TABLE IF NOT EXISTS definitions
+         (word       TEXT PRIMARY KEY,
+          definition TEXT NOT NULL);
+
+CREATE TABLE IF NOT EXISTS seasons
+         (id         INTEGER PRIMARY KEY,
+          start_date INTEGER NOT NULL,
+          end_date   INTEGER,
+          winner_id  INTEGER REFERENCES players(id) ON DELETE NO ACTION);
+
+CREATE TABLE IF NOT EXISTS season_scores
+         (season_id INTEGER REFERENCES seasons(id) ON DELETE NO ACTION,
+          player_id INTEGER REFERENCES players(id) ON DELETE NO ACTION,
+          score     INTEGER NOT NULL,
+          PRIMARY KEY (season_id, player_id));
+
+CREATE TABLE IF NOT EXISTS achievements
+         (player_id   INTEGER REFERENCES players(id) ON DELETE NO ACTION,
+          achievement TEXT NOT NULL,
+          date        INTEGER NOT NULL,
+          PRIMARY KEY (player_id, achievement));
+
+CREATE TABLE IF NOT EXISTS teams
+         (id    INTEGER PRIMARY KEY,
+          name  TEXT UNIQUE NOT NULL,
+          score INTEGER);
+
+CREATE TABLE IF NOT EXISTS team_members
+         (player_id INTEGER PRIMARY KEY REFERENCES players(id) ON DELETE NO ACTION,
+          team_id   INTEGER REFERENCES teams(id) ON DELETE NO ACTION);
+          "#,
+    )?;
+
+    // columns added after the initial schema
+    add_column_if_missing(conn, "sessions", "metric", "TEXT")?;
+    add_column_if_missing(conn, "sessions", "mode", "TEXT")?;
+    add_column_if_missing(conn, "sessions", "density", "NUMERIC")?;
+    add_column_if_missing(conn, "sessions", "frequency", "INTEGER")?;
+    add_column_if_missing(conn, "sessions", "language", "TEXT")?;
+    add_column_if_missing(conn, "sessions", "strict", "INTEGER")?;
+    add_column_if_missing(conn, "sessions", "guess_budget", "INTEGER")?;
+    add_column_if_missing(conn, "sessions", "hints", "INTEGER")?;
+    add_column_if_missing(conn, "sessions", "share", "TEXT")?;
+    add_column_if_missing(conn, "guesses", "team_id", "INTEGER REFERENCES teams(id)")?;
+    add_column_if_missing(conn, "players", "rating", "REAL")?;
+
+    Ok(())
+}
+
+/// Migration 2: when the guesses were made, and how long after the start of their session (in seconds). The guesses
+/// made before have no date.
+fn guess_dates(conn: &rusqlite::Connection) -> Result<()> {
+    // language=SQLITE-SQL
+    conn.execute_batch(
+        r#"
+ALTER TABLE guesses ADD COLUMN timestamp INTEGER;
+ALTER TABLE guesses ADD COLUMN elapsed INTEGER;
+"#,
+    )?;
+    Ok(())
+}
+
+/// Migration 3: other nicks of the players (`nick_`, `nick|away`...), whose guesses go to the player.
+fn aliases(conn: &rusqlite::Connection) -> Result<()> {
+    // language=SQLITE-SQL
+    conn.execute_batch(
+        r#"
+CREATE TABLE aliases
+         (nick      TEXT PRIMARY KEY,
+          player_id INTEGER NOT NULL REFERENCES players(id) ON DELETE NO ACTION);
+"#,
+    )?;
+    Ok(())
+}
+
+/// Migration 4: results of the players of the sessions whose guesses were archived, and a view of the results of
+/// the players of all the sessions.
+fn session_stats(conn: &rusqlite::Connection) -> Result<()> {
+    // session_stats: session ID, player ID -> number of guesses, similarity of the best guess that wasn't the word,
+    //                team the last guess was credited to
+    // session_players: same as session_stats, for all the sessions
+    conn.execute_batch(
+        // language=SQLITE-SQL
+        r#"
+CREATE TABLE session_stats
+         (session_id INTEGER REFERENCES sessions(id) ON DELETE NO ACTION,
+          player_id  INTEGER REFERENCES players(id) ON DELETE NO ACTION,
+          guesses    INTEGER NOT NULL,
+          best_miss  NUMERIC,
+          team_id    INTEGER REFERENCES teams(id) ON DELETE NO ACTION,
+          PRIMARY KEY (session_id, player_id));
+
+CREATE VIEW session_players AS
+    SELECT g.session_id, g.player_id, COUNT(*) AS guesses,
+           MAX(CASE WHEN g.guess <> s.word THEN g.cosine END) AS best_miss,
+           (SELECT l.team_id FROM guesses l WHERE l.session_id = g.session_id AND l.player_id = g.player_id
+            ORDER BY l.id DESC LIMIT 1) AS team_id
+    FROM guesses g JOIN sessions s ON s.id = g.session_id
+    GROUP BY g.session_id, g.player_id
+    UNION ALL
+    SELECT session_id, player_id, guesses, best_miss, team_id FROM session_stats;
+"#,
+    )?;
+    Ok(())
+}
+
+/// Migration 5: tournaments, and the players registered to them.
+fn tournaments(conn: &rusqlite::Connection) -> Result<()> {
+    // tournaments: ID -> name, start_date, end_date (planned), ended, winner
+    // tournament_players: tournament ID, player ID -> points (won for their places in the sessions)
+    conn.execute_batch(
+        // language=SQLITE-SQL
+        r#"
+CREATE TABLE tournaments
+         (id         INTEGER PRIMARY KEY,
+          name       TEXT NOT NULL,
+          start_date INTEGER NOT NULL,
+          end_date   INTEGER NOT NULL,
+          ended      INTEGER NOT NULL DEFAULT 0,
+          winner_id  INTEGER REFERENCES players(id) ON DELETE NO ACTION);
+
+CREATE TABLE tournament_players
+         (tournament_id INTEGER REFERENCES tournaments(id) ON DELETE NO ACTION,
+          player_id     INTEGER REFERENCES players(id) ON DELETE NO ACTION,
+          points        INTEGER NOT NULL DEFAULT 0,
+          PRIMARY KEY (tournament_id, player_id));
+"#,
+    )?;
+    Ok(())
+}
+
+/// Migration 6: theme of the word to find of the themed sessions.
+fn session_themes(conn: &rusqlite::Connection) -> Result<()> {
+    // language=SQLITE-SQL
+    conn.execute_batch("ALTER TABLE sessions ADD COLUMN theme TEXT;")?;
+    Ok(())
+}
+
+/// Migration 7: players who found the word of a session, when several players can find it.
+fn winners(conn: &rusqlite::Connection) -> Result<()> {
+    // winners: session ID, player ID -> place (from 0), points (won for finding the word), date
+    conn.execute_batch(
+        // language=SQLITE-SQL
+        r#"
+CREATE TABLE winners
+         (session_id INTEGER REFERENCES sessions(id) ON DELETE NO ACTION,
+          player_id  INTEGER REFERENCES players(id) ON DELETE NO ACTION,
+          place      INTEGER NOT NULL,
+          points     INTEGER NOT NULL,
+          date       INTEGER NOT NULL,
+          PRIMARY KEY (session_id, player_id));
+"#,
+    )?;
+    Ok(())
+}
+
+/// Replaces the database at `db_path` with a backup, and brings its schema up to date. The bot must not be running.
+pub fn restore_backup(db_path: &str, backup_path: &str) -> Result<()> {
+    if !Path::new(backup_path).is_file() {
+        bail!("there's no backup at `{}`", backup_path);
+    }
+    let mut conn = rusqlite::Connection::open(db_path)?;
+    conn.restore(DatabaseName::Main, backup_path, None::<fn(rusqlite::backup::Progress)>)
+        .with_context(|| format!("could not restore `{}`", backup_path))?;
+    setup_schema(&mut conn)
+}
+
+/// Deletes the oldest backups of `directory` whose file name starts with `prefix`, so that `keep` of them remain.
+fn prune_backups(directory: &Path, prefix: &str, keep: usize) -> Result<()> {
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.starts_with(prefix) && name.ends_with(".db") {
+            backups.push(name);
+        }
+    }
+    // the dates in the names sort in chronological order
+    backups.sort();
+    for name in &backups[..backups.len().saturating_sub(keep)] {
+        fs::remove_file(directory.join(name))?;
+        info!("deleted the old backup `{}`", name);
+    }
+    Ok(())
+}
+
+/// Adds a column to an existing table, unless it's already there.
+fn add_column_if_missing(conn: &rusqlite::Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let exists = conn
+        .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name=?1", table))?
+        .exists([column])?;
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {};", table, column, decl))?;
+    }
+    Ok(())
+}
+
+/// Game state stored in a SQLite database file.
+///
+/// The queries run on the thread of the connection, so they don't block the async tasks that wait for them. The
+/// queries that only read use a pool of read-only connections in turn, so they don't wait for the thread of the
+/// connection that writes.
+pub struct SqliteStorage {
+    conn: tokio_rusqlite::Connection,
+    readers: Vec<tokio_rusqlite::Connection>,
+    /// Index of the reader used by the next read query.
+    next_reader: usize,
+    /// Path of the database file.
+    db_path: String,
+    /// Directory of the backups, and number of backups kept.
+    backups: Option<(String, usize)>,
+}
+
+impl SqliteStorage {
+    /// Opens (or creates) the database file at the specified path.
+    pub async fn open(db_path: &str, config: &SqliteConfig) -> Result<SqliteStorage> {
+        let conn = tokio_rusqlite::Connection::open(db_path).await?;
+        let pragmas = config.clone();
+        conn.call(move |conn| Ok(set_pragmas(conn, &pragmas, true)))
+            .await?
+            .context("can't set the pragmas of the database")?;
+        let mut storage = SqliteStorage {
+            conn,
+            readers: Vec::new(),
+            next_reader: 0,
+            db_path: db_path.to_string(),
+            backups: config
+                .backup_directory
+                .clone()
+                .map(|directory| (directory, config.backup_keep)),
+        };
+        storage.call(setup_schema).await?;
+        // each connection to an in-memory database has its own database
+        if db_path != ":memory:" {
+            for _ in 0..config.read_connections {
+                let flags =
+                    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+                let reader = tokio_rusqlite::Connection::open_with_flags(db_path, flags).await?;
+                let pragmas = config.clone();
+                reader
+                    .call(move |conn| Ok(set_pragmas(conn, &pragmas, false)))
+                    .await??;
+                storage.readers.push(reader);
+            }
+        }
+        Ok(storage)
+    }
+
+    /// Runs queries on the thread of the connection.
+    async fn call<T: Send + 'static>(
+        &self,
+        function: impl FnOnce(&mut rusqlite::Connection) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        // errors of the queries are passed through, the outer error is for a closed connection
+        self.conn.call(move |conn| Ok(function(conn))).await?
+    }
+
+    /// Runs queries that don't write on the thread of the next read-only connection, or of the connection that
+    /// writes if there's none.
+    async fn read<T: Send + 'static>(
+        &mut self,
+        function: impl FnOnce(&mut rusqlite::Connection) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        if self.readers.is_empty() {
+            return self.call(function).await;
+        }
+        let reader = &self.readers[self.next_reader];
+        self.next_reader = (self.next_reader + 1) % self.readers.len();
+        reader.call(move |conn| Ok(function(conn))).await?
+    }
+}
+
+/// Sets the pragmas of a connection. The journal mode is a property of the database file, so only the connection
+/// that writes sets it.
+fn set_pragmas(conn: &rusqlite::Connection, config: &SqliteConfig, writer: bool) -> Result<()> {
+    conn.busy_timeout(Duration::from_millis(config.busy_timeout))?;
+    if writer {
+        let mode: String =
+            conn.pragma_update_and_check(None, "journal_mode", &config.journal_mode, |row| row.get(0))?;
+        if !mode.eq_ignore_ascii_case(&config.journal_mode) {
+            warn!(
+                "the database uses the `{}` journal mode instead of `{}`",
+                mode, config.journal_mode
+            );
+        }
+    }
+    conn.pragma_update(None, "synchronous", &config.synchronous)?;
+    Ok(())
+}
+
+/// Synchronous queries, run on the thread of the connection.
+struct Queries<'a> {
+    conn: &'a mut rusqlite::Connection,
+}
+
+impl Queries<'_> {
+    fn current_session(&mut self) -> Result<Option<Session>> {
+        // language=SQLITE-SQL
+        let session_id: Option<i64> = self
+            .conn
+            .query_row("SELECT session_id FROM current_session WHERE id=0", [], |row| {
+                row.get(0)
+            })
+            .optional()?
+            .flatten();
+        session_id.map_or(Ok(None), |session_id| self.session(session_id))
+    }
+
+    fn session(&mut self, session_id: i64) -> Result<Option<Session>> {
+        // language=SQLITE-SQL
+        let session = self
+            .conn
+            .query_row(
+                r#"SELECT s.id, s.word, s.start_date, s.planned_end_date, s.metric, s.mode, s.density, s.frequency,
+                          s.language, IFNULL(s.strict, 0), s.guess_budget, IFNULL(s.hints, 0), s.theme
+                   FROM sessions s WHERE s.id=?1"#,
+                [session_id],
+                |row| {
+                    Ok((
+                        Session {
+                            id: row.get(0)?,
+                            word: row.get(1)?,
+                            start_date: row.get(2)?,
+                            planned_end_date: row.get(3)?,
+                            metric: Metric::default(),
+                            mode: GameMode::default(),
+                            difficulty: None,
+                            language: row.get(8)?,
+                            strict: row.get(9)?,
+                            budget: row.get::<_, Option<i64>>(10)?.map(|budget| budget as usize),
+                            hints: row.get::<_, i64>(11)? as usize,
+                            theme: row.get(12)?,
+                        },
+                        row.get::<_, Option<String>>(4)?,
+                        row.get::<_, Option<String>>(5)?,
+                        row.get::<_, Option<f64>>(6)?,
+                        row.get::<_, Option<i64>>(7)?,
+                    ))
+                },
+            )
+            .optional()?;
+        match session {
+            Some((mut session, metric, mode, density, frequency)) => {
+                if let Some(metric) = metric {
+                    session.metric = metric.parse()?;
+                }
+                if let Some(mode) = mode {
+                    session.mode = mode.parse()?;
+                }
+                session.difficulty = density.map(|density| Difficulty {
+                    density: density as f32,
+                    frequency: frequency.map(|f| f as u64),
+                });
+                Ok(Some(session))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn player_id(&mut self, nick: &str) -> Result<i64> {
+        if let Some(id) = self.find_player(nick)? {
+            Ok(id)
+        } else {
+            // unknown player, insert into players
+            // language=SQLITE-SQL
+            self.conn
+                .execute("INSERT INTO players(nick, score) VALUES (?1,?2);", params![nick, 0i64])?;
+            Ok(self.conn.last_insert_rowid())
+        }
+    }
+
+    fn find_player(&mut self, nick: &str) -> Result<Option<i64>> {
+        // language=SQLITE-SQL
+        let player_id = self.conn.query_row(
+            r#"SELECT IFNULL((SELECT player_id FROM aliases WHERE nick=?1),
+                             (SELECT id FROM players WHERE nick=?1))"#,
+            [nick],
+            |row| row.get(0),
+        )?;
+        Ok(player_id)
+    }
+
+    fn add_alias(&mut self, alias: &str, player_id: i64) -> Result<()> {
+        // language=SQLITE-SQL
+        self.conn.execute(
+            "INSERT OR REPLACE INTO aliases(nick, player_id) VALUES (?1,?2);",
+            params![alias, player_id],
+        )?;
+        Ok(())
+    }
+
+    fn merge_players(&mut self, from_id: i64, into_id: i64) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        let ids = params![from_id, into_id];
+        // language=SQLITE-SQL
+        tx.execute("UPDATE guesses SET player_id=?2 WHERE player_id=?1;", ids)?;
+        // language=SQLITE-SQL
+        tx.execute(
+            r#"INSERT INTO session_stats(session_id, player_id, guesses, best_miss, team_id)
+               SELECT session_id, ?2, guesses, best_miss, team_id FROM session_stats WHERE player_id=?1
+               ON CONFLICT(session_id, player_id) DO UPDATE SET guesses=guesses+excluded.guesses,
+                   best_miss=MAX(IFNULL(best_miss, excluded.best_miss), IFNULL(excluded.best_miss, best_miss));"#,
+            ids,
+        )?;
+        // language=SQLITE-SQL
+        tx.execute("UPDATE sessions SET winner_id=?2 WHERE winner_id=?1;", ids)?;
+        // language=SQLITE-SQL
+        tx.execute(
+            r#"INSERT OR IGNORE INTO winners(session_id, player_id, place, points, date)
+               SELECT session_id, ?2, place, points, date FROM winners WHERE player_id=?1;"#,
+            ids,
+        )?;
+        // language=SQLITE-SQL
+        tx.execute("UPDATE seasons SET winner_id=?2 WHERE winner_id=?1;", ids)?;
+        // language=SQLITE-SQL
+        tx.execute("UPDATE tournaments SET winner_id=?2 WHERE winner_id=?1;", ids)?;
+        // language=SQLITE-SQL
+        tx.execute(
+            r#"UPDATE players SET score=IFNULL(score,0)+(SELECT IFNULL(score,0) FROM players WHERE id=?1),
+                                  rating=IFNULL(rating, (SELECT rating FROM players WHERE id=?1))
+               WHERE id=?2;"#,
+            ids,
+        )?;
+        // language=SQLITE-SQL
+        tx.execute(
+            r#"INSERT INTO season_scores(season_id, player_id, score)
+               SELECT season_id, ?2, score FROM season_scores WHERE player_id=?1
+               ON CONFLICT(season_id, player_id) DO UPDATE SET score=score+excluded.score;"#,
+            ids,
+        )?;
+        // language=SQLITE-SQL
+        tx.execute(
+            r#"INSERT INTO tournament_players(tournament_id, player_id, points)
+               SELECT tournament_id, ?2, points FROM tournament_players WHERE player_id=?1
+               ON CONFLICT(tournament_id, player_id) DO UPDATE SET points=points+excluded.points;"#,
+            ids,
+        )?;
+        // language=SQLITE-SQL
+        tx.execute(
+            r#"INSERT INTO achievements(player_id, achievement, date)
+               SELECT ?2, achievement, date FROM achievements WHERE player_id=?1
+               ON CONFLICT(player_id, achievement) DO UPDATE SET date=MIN(date, excluded.date);"#,
+            ids,
+        )?;
+        // language=SQLITE-SQL
+        tx.execute(
+            r#"INSERT OR IGNORE INTO team_members(player_id, team_id)
+               SELECT ?2, team_id FROM team_members WHERE player_id=?1;"#,
+            ids,
+        )?;
+        // language=SQLITE-SQL
+        tx.execute("UPDATE aliases SET player_id=?2 WHERE player_id=?1;", ids)?;
+        // language=SQLITE-SQL
+        tx.execute(
+            "INSERT OR REPLACE INTO aliases(nick, player_id) SELECT nick, ?2 FROM players WHERE id=?1;",
+            ids,
+        )?;
+        for table in [
+            "session_stats",
+            "winners",
+            "season_scores",
+            "tournament_players",
+            "achievements",
+            "team_members",
+        ] {
+            // language=SQLITE-SQL
+            tx.execute(&format!("DELETE FROM {} WHERE player_id=?1;", table), [from_id])?;
+        }
+        // language=SQLITE-SQL
+        tx.execute("DELETE FROM players WHERE id=?1;", [from_id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn forget_player(&mut self, player_id: i64, nick: &str) -> Result<Vec<String>> {
+        let tx = self.conn.transaction()?;
+        // language=SQLITE-SQL
+        let nicks = tx
+            .prepare("SELECT nick FROM players WHERE id=?1 UNION SELECT nick FROM aliases WHERE player_id=?1")?
+            .query_map([player_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        for table in ["aliases", "achievements", "team_members"] {
+            // language=SQLITE-SQL
+            tx.execute(&format!("DELETE FROM {} WHERE player_id=?1;", table), [player_id])?;
+        }
+        // language=SQLITE-SQL
+        tx.execute(
+            "UPDATE players SET nick=?1, rating=NULL WHERE id=?2;",
+            params![nick, player_id],
+        )?;
+        tx.commit()?;
+        Ok(nicks)
+    }
+
+    fn insert_guess(
+        &mut self,
+        session_id: i64,
+        player_id: i64,
+        team_id: Option<i64>,
+        guess: &str,
+        cosine: f32,
+        date: i64,
+    ) -> Result<()> {
+        // language=SQLITE-SQL
+        self.conn.execute(
+            r#"INSERT INTO guesses(session_id, player_id, team_id, guess, cosine, timestamp, elapsed)
+               SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?6 - start_date FROM sessions WHERE id=?1"#,
+            params![session_id, player_id, team_id, guess, cosine, date],
+        )?;
+        Ok(())
+    }
+
+    fn start_session(&mut self, session: &Session) -> Result<i64> {
+        let tx = self.conn.transaction()?;
+        // language=SQLITE-SQL
+        tx.execute(
+            "INSERT INTO sessions(start_date, planned_end_date, word, metric, mode, density, frequency, language, strict, guess_budget, theme) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11);",
+            params![
+                session.start_date,
+                session.planned_end_date,
+                session.word,
+                session.metric.as_str(),
+                session.mode.as_str(),
+                session.difficulty.map(|difficulty| difficulty.density),
+                session.difficulty.and_then(|difficulty| difficulty.frequency).map(|f| f as i64),
+                session.language,
+                session.strict,
+                session.budget.map(|budget| budget as i64),
+                session.theme
+            ],
+        )?;
+        let session_id = tx.last_insert_rowid();
+        // language=SQLITE-SQL
+        tx.execute("UPDATE current_session SET session_id=?1 WHERE id=0;", params![session_id])?;
+        tx.commit()?;
+        Ok(session_id)
+    }
+
+    fn guess_count(&mut self, session_id: i64, player_id: i64) -> Result<usize> {
+        // language=SQLITE-SQL
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM guesses WHERE session_id=?1 AND player_id=?2",
+            params![session_id, player_id],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    fn best_guesses(&mut self, session_id: i64) -> Result<Vec<LeaderboardEntry>> {
+        // the other columns are the ones of the row with the maximum similarity
+        // language=SQLITE-SQL
+        let mut stmt = self.conn.prepare(
+            r#"SELECT p.nick, g.guess, MAX(g.cosine) AS best
+               FROM guesses g JOIN players p ON p.id = g.player_id
+               WHERE g.session_id=?1 GROUP BY g.player_id ORDER BY best DESC"#,
+        )?;
+        let entries = stmt
+            .query_map([session_id], |row| {
+                Ok(LeaderboardEntry {
+                    nick: row.get(0)?,
+                    guess: row.get(1)?,
+                    similarity: row.get::<_, f64>(2)? as f32,
+                    rank: None,
+                    heat: None,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    fn session_guesses(&mut self, session_id: i64) -> Result<Vec<(i64, String)>> {
+        // language=SQLITE-SQL
+        let mut stmt = self
+            .conn
+            .prepare("SELECT player_id, guess FROM guesses WHERE session_id=?1 ORDER BY id")?;
+        let guesses = stmt
+            .query_map([session_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(guesses)
+    }
+
+    fn add_winner(&mut self, session_id: i64, player_id: i64, place: usize, points: i64, date: i64) -> Result<()> {
+        // language=SQLITE-SQL
+        self.conn.execute(
+            "INSERT INTO winners(session_id, player_id, place, points, date) VALUES (?1, ?2, ?3, ?4, ?5);",
+            params![session_id, player_id, place as i64, points, date],
+        )?;
+        Ok(())
+    }
+
+    fn session_winners(&mut self, session_id: i64) -> Result<Vec<SessionWinner>> {
+        // language=SQLITE-SQL
+        let mut stmt = self.conn.prepare(
+            r#"SELECT w.player_id, p.nick, w.points FROM winners w JOIN players p ON p.id = w.player_id
+               WHERE w.session_id=?1 ORDER BY w.place"#,
+        )?;
+        let winners = stmt
+            .query_map([session_id], |row| {
+                Ok(SessionWinner {
+                    player_id: row.get(0)?,
+                    nick: row.get(1)?,
+                    points: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(winners)
+    }
+
+    fn guess_counts(&mut self, session_id: i64) -> Result<Vec<(i64, usize)>> {
+        // language=SQLITE-SQL
+        let mut stmt = self
+            .conn
+            .prepare("SELECT player_id, guesses FROM session_players WHERE session_id=?1")?;
+        let counts = stmt
+            .query_map([session_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(counts)
+    }
+
+    fn archive_guesses(&mut self, before: i64) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        // the results of a session may already be partly archived (e.g. after an import): they're added up
+        // language=SQLITE-SQL
+        tx.execute(
+            r#"INSERT INTO session_stats(session_id, player_id, guesses, best_miss, team_id)
+               SELECT g.session_id, g.player_id, COUNT(*), MAX(CASE WHEN g.guess <> s.word THEN g.cosine END),
+                      (SELECT l.team_id FROM guesses l WHERE l.session_id = g.session_id AND l.player_id = g.player_id
+                       ORDER BY l.id DESC LIMIT 1)
+               FROM guesses g JOIN sessions s ON s.id = g.session_id
+               WHERE s.end_date < ?1
+               GROUP BY g.session_id, g.player_id
+               ON CONFLICT(session_id, player_id) DO UPDATE SET guesses=guesses+excluded.guesses,
+                   best_miss=MAX(IFNULL(best_miss, excluded.best_miss), IFNULL(excluded.best_miss, best_miss)),
+                   team_id=IFNULL(excluded.team_id, team_id);"#,
+            [before],
+        )?;
+        // language=SQLITE-SQL
+        let deleted = tx.execute(
+            "DELETE FROM guesses WHERE session_id IN (SELECT id FROM sessions WHERE end_date < ?1);",
+            [before],
+        )?;
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    fn guess_history(&mut self, session_id: i64) -> Result<Vec<(String, String, f32)>> {
+        // language=SQLITE-SQL
+        let mut stmt = self.conn.prepare(
+            r#"SELECT g.guess, p.nick, g.cosine FROM guesses g JOIN players p ON p.id = g.player_id
+               WHERE g.session_id=?1 ORDER BY g.id"#,
+        )?;
+        let guesses = stmt
+            .query_map([session_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get::<_, f64>(2)? as f32))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(guesses)
+    }
+
+    fn session_transcript(&mut self, session_id: i64) -> Result<Option<SessionTranscript>> {
+        let Some(session) = self.session(session_id)? else {
+            return Ok(None);
+        };
+        // language=SQLITE-SQL
+        let (end_date, winner) = self.conn.query_row(
+            "SELECT s.end_date, p.nick FROM sessions s LEFT JOIN players p ON p.id = s.winner_id WHERE s.id=?1",
+            [session_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        // language=SQLITE-SQL
+        let mut stmt = self.conn.prepare(
+            r#"SELECT p.nick, g.guess, g.cosine, g.timestamp, g.elapsed
+               FROM guesses g JOIN players p ON p.id = g.player_id
+               WHERE g.session_id=?1 ORDER BY g.id"#,
+        )?;
+        let guesses = stmt
+            .query_map([session_id], |row| {
+                Ok(TranscriptGuess {
+                    nick: row.get(0)?,
+                    guess: row.get(1)?,
+                    similarity: row.get::<_, f64>(2)? as f32,
+                    timestamp: row.get(3)?,
+                    elapsed: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(SessionTranscript {
+            session,
+            end_date,
+            winner,
+            guesses,
+        }))
+    }
+
+    fn recent_guesses(&mut self, session_id: i64, limit: usize) -> Result<Vec<GuessRecord>> {
+        // language=SQLITE-SQL
+        let mut stmt = self.conn.prepare(
+            r#"SELECT p.nick, g.guess, g.cosine FROM guesses g JOIN players p ON p.id = g.player_id
+               WHERE g.session_id=?1 ORDER BY g.id DESC LIMIT ?2"#,
+        )?;
+        let guesses = stmt
+            .query_map(params![session_id, limit as i64], |row| {
+                Ok(GuessRecord {
+                    nick: row.get(0)?,
+                    guess: row.get(1)?,
+                    distance: row.get::<_, f64>(2)? as f32,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(guesses)
+    }
+
+    fn last_share(&mut self, nick: &str) -> Result<Option<String>> {
+        // language=SQLITE-SQL
+        let share = self
+            .conn
+            .query_row(
+                r#"SELECT s.share FROM sessions s JOIN players p ON p.id = s.winner_id
+                   WHERE p.id = IFNULL((SELECT player_id FROM aliases WHERE nick=?1),
+                                       (SELECT id FROM players WHERE nick=?1))
+                     AND s.share IS NOT NULL ORDER BY s.end_date DESC, s.id DESC LIMIT 1"#,
+                [nick],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(share)
+    }
+
+    fn set_session_hints(&mut self, session_id: i64, hints: usize) -> Result<()> {
+        // language=SQLITE-SQL
+        self.conn.execute(
+            "UPDATE sessions SET hints=?1 WHERE id=?2;",
+            params![hints as i64, session_id],
+        )?;
+        Ok(())
+    }
+
+    fn end_session(&mut self, session_id: i64, result: &SessionResult) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        // language=SQLITE-SQL
+        tx.execute(
+            "UPDATE sessions SET end_date=?1, winner_id=?2, share=?3 WHERE id=?4;",
+            params![result.end_date, result.winner_id, result.share, session_id],
+        )?;
+        for &(player_id, points) in &result.points {
+            // language=SQLITE-SQL
+            tx.execute(
+                "UPDATE players SET score=IFNULL(score,0)+?1 WHERE id=?2;",
+                params![points, player_id],
+            )?;
+            // language=SQLITE-SQL
+            tx.execute(
+                r#"INSERT INTO season_scores(season_id, player_id, score)
+                   SELECT id, ?1, ?2 FROM seasons WHERE end_date IS NULL
+                   ON CONFLICT(season_id, player_id) DO UPDATE SET score=score+excluded.score;"#,
+                params![player_id, points],
+            )?;
+            // language=SQLITE-SQL
+            tx.execute(
+                r#"UPDATE teams SET score=IFNULL(score,0)+?1
+                   WHERE id=(SELECT team_id FROM guesses WHERE session_id=?2 AND player_id=?3
+                             ORDER BY id DESC LIMIT 1);"#,
+                params![points, session_id, player_id],
+            )?;
+        }
+        for &(player_id, rating) in &result.ratings {
+            // language=SQLITE-SQL
+            tx.execute("UPDATE players SET rating=?1 WHERE id=?2;", params![rating, player_id])?;
+        }
+        for &(player_id, points) in &result.tournament_points {
+            // language=SQLITE-SQL
+            tx.execute(
+                r#"UPDATE tournament_players SET points=points+?1
+                   WHERE player_id=?2 AND tournament_id=(SELECT MAX(id) FROM tournaments WHERE ended=0);"#,
+                params![points, player_id],
+            )?;
+        }
+        // language=SQLITE-SQL
+        tx.execute("UPDATE current_session SET session_id=NULL WHERE id=0;", [])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn fetch_players(&mut self) -> Result<Vec<Player>> {
+        // language=SQLITE-SQL
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, nick, IFNULL(score,0) FROM players ORDER BY nick")?;
+        let players = stmt
+            .query_map([], |row| {
+                Ok(Player {
+                    id: row.get(0)?,
+                    nick: row.get(1)?,
+                    score: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(players)
+    }
+
+    fn player_stats(&mut self) -> Result<Vec<PlayerStats>> {
+        // language=SQLITE-SQL
+        let mut stmt = self.conn.prepare(
+            r#"SELECT p.id, p.nick, IFNULL(p.score,0) AS total,
+                      (SELECT COUNT(*) FROM sessions s WHERE s.winner_id = p.id),
+                      (SELECT COUNT(*) FROM session_players sp WHERE sp.player_id = p.id),
+                      (SELECT IFNULL(MAX(ss.score),0) FROM seasons se
+                       LEFT JOIN season_scores ss ON ss.season_id = se.id AND ss.player_id = p.id
+                       WHERE se.end_date IS NULL GROUP BY se.id) AS season
+               FROM players p ORDER BY season DESC, total DESC, p.nick"#,
+        )?;
+        let stats = stmt
+            .query_map([], |row| {
+                Ok(PlayerStats {
+                    player: Player {
+                        id: row.get(0)?,
+                        nick: row.get(1)?,
+                        score: row.get(2)?,
+                    },
+                    season_score: row.get(5)?,
+                    wins: row.get(3)?,
+                    games: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(stats)
+    }
+
+    fn player_profile(&mut self, nick: &str) -> Result<Option<PlayerProfile>> {
+        // language=SQLITE-SQL
+        let stats = self
+            .conn
+            .query_row(
+                r#"SELECT p.id, p.nick, IFNULL(p.score,0),
+                          (SELECT COUNT(*) FROM sessions s WHERE s.winner_id = p.id),
+                          (SELECT COUNT(*) FROM session_players sp WHERE sp.player_id = p.id),
+                          (SELECT IFNULL(MAX(ss.score),0) FROM seasons se
+                           LEFT JOIN season_scores ss ON ss.season_id = se.id AND ss.player_id = p.id
+                           WHERE se.end_date IS NULL GROUP BY se.id)
+                   FROM players p
+                   WHERE p.id = IFNULL((SELECT player_id FROM aliases WHERE nick=?1),
+                                       (SELECT id FROM players WHERE nick=?1))"#,
+                [nick],
+                |row| {
+                    Ok(PlayerStats {
+                        player: Player {
+                            id: row.get(0)?,
+                            nick: row.get(1)?,
+                            score: row.get(2)?,
+                        },
+                        season_score: row.get(5)?,
+                        wins: row.get(3)?,
+                        games: row.get(4)?,
+                    })
+                },
+            )
+            .optional()?;
+        let stats = match stats {
+            Some(stats) => stats,
+            None => return Ok(None),
+        };
+        let player_id = stats.player.id;
+
+        // language=SQLITE-SQL
+        let average_guesses_to_win: Option<f64> = self.conn.query_row(
+            r#"SELECT AVG(sp.guesses) FROM session_players sp JOIN sessions s ON s.id = sp.session_id
+               WHERE s.winner_id=?1 AND sp.player_id=?1"#,
+            [player_id],
+            |row| row.get(0),
+        )?;
+        // language=SQLITE-SQL
+        let best_similarity: Option<f64> = self.conn.query_row(
+            r#"SELECT MAX(sp.best_miss) FROM session_players sp JOIN sessions s ON s.id = sp.session_id
+               WHERE sp.player_id=?1 AND IFNULL(s.mode, 'semantic') <> 'wordle'"#,
+            [player_id],
+            |row| row.get(0),
+        )?;
+        // language=SQLITE-SQL
+        let mut stmt = self.conn.prepare(
+            r#"SELECT s.winner_id FROM sessions s
+               WHERE s.end_date IS NOT NULL
+                 AND s.id IN (SELECT session_id FROM session_players WHERE player_id=?1)
+               ORDER BY s.id DESC"#,
+        )?;
+        let winners = stmt
+            .query_map([player_id], |row| row.get::<_, Option<i64>>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let streak = winners.iter().take_while(|&&winner| winner == Some(player_id)).count();
+        drop(stmt);
+        // language=SQLITE-SQL
+        let rating = self
+            .conn
+            .query_row("SELECT rating FROM players WHERE id=?1", [player_id], |row| row.get(0))?;
+        let achievements = self.player_achievements(player_id)?;
+
+        Ok(Some(PlayerProfile {
+            stats,
+            average_guesses_to_win,
+            best_similarity: best_similarity.map(|similarity| similarity as f32),
+            streak,
+            rating,
+            achievements,
+        }))
+    }
+
+    fn current_season(&mut self) -> Result<Option<Season>> {
+        // language=SQLITE-SQL
+        let season = self
+            .conn
+            .query_row(
+                "SELECT id, start_date FROM seasons WHERE end_date IS NULL ORDER BY id DESC LIMIT 1",
+                [],
+                |row| {
+                    Ok(Season {
+                        id: row.get(0)?,
+                        start_date: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(season)
+    }
+
+    fn start_season(&mut self, start_date: i64) -> Result<i64> {
+        // language=SQLITE-SQL
+        self.conn
+            .execute("INSERT INTO seasons(start_date) VALUES (?1);", params![start_date])?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    fn season_scores(&mut self, season_id: i64) -> Result<Vec<Player>> {
+        // language=SQLITE-SQL
+        let mut stmt = self.conn.prepare(
+            r#"SELECT p.id, p.nick, ss.score FROM season_scores ss JOIN players p ON p.id = ss.player_id
+               WHERE ss.season_id=?1 AND ss.score > 0 ORDER BY ss.score DESC, p.nick"#,
+        )?;
+        let players = stmt
+            .query_map([season_id], |row| {
+                Ok(Player {
+                    id: row.get(0)?,
+                    nick: row.get(1)?,
+                    score: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(players)
+    }
+
+    fn end_season(&mut self, season_id: i64, end_date: i64, winner_id: Option<i64>) -> Result<()> {
+        // language=SQLITE-SQL
+        self.conn.execute(
+            "UPDATE seasons SET end_date=?1, winner_id=?2 WHERE id=?3;",
+            params![end_date, winner_id, season_id],
+        )?;
+        Ok(())
+    }
+
+    fn current_tournament(&mut self) -> Result<Option<Tournament>> {
+        // language=SQLITE-SQL
+        let tournament = self
+            .conn
+            .query_row(
+                "SELECT id, name, start_date, end_date FROM tournaments WHERE ended=0 ORDER BY id DESC LIMIT 1",
+                [],
+                |row| {
+                    Ok(Tournament {
+                        id: row.get(0)?,
+                        name: row.get(1)?,
+                        start_date: row.get(2)?,
+                        end_date: row.get(3)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(tournament)
+    }
+
+    fn start_tournament(&mut self, name: &str, start_date: i64, end_date: i64) -> Result<i64> {
+        // language=SQLITE-SQL
+        self.conn.execute(
+            "INSERT INTO tournaments(name, start_date, end_date) VALUES (?1, ?2, ?3);",
+            params![name, start_date, end_date],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    fn join_tournament(&mut self, tournament_id: i64, player_id: i64) -> Result<bool> {
+        // language=SQLITE-SQL
+        let inserted = self.conn.execute(
+            "INSERT OR IGNORE INTO tournament_players(tournament_id, player_id) VALUES (?1, ?2);",
+            params![tournament_id, player_id],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    fn tournament_standings(&mut self, tournament_id: i64) -> Result<Vec<Player>> {
+        // language=SQLITE-SQL
+        let mut stmt = self.conn.prepare(
+            r#"SELECT p.id, p.nick, tp.points FROM tournament_players tp JOIN players p ON p.id = tp.player_id
+               WHERE tp.tournament_id=?1 ORDER BY tp.points DESC, p.nick"#,
+        )?;
+        let players = stmt
+            .query_map([tournament_id], |row| {
+                Ok(Player {
+                    id: row.get(0)?,
+                    nick: row.get(1)?,
+                    score: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(players)
+    }
+
+    fn end_tournament(&mut self, tournament_id: i64, winner_id: Option<i64>) -> Result<()> {
+        // language=SQLITE-SQL
+        self.conn.execute(
+            "UPDATE tournaments SET ended=1, winner_id=?2 WHERE id=?1;",
+            params![tournament_id, winner_id],
+        )?;
+        Ok(())
+    }
+
+    fn ended_sessions(&mut self) -> Result<Vec<(i64, Option<i64>)>> {
+        // language=SQLITE-SQL
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, winner_id FROM sessions WHERE end_date IS NOT NULL ORDER BY end_date, id")?;
+        let sessions = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(sessions)
+    }
+
+    fn average_guesses_to_win(&mut self) -> Result<Option<f64>> {
+        // language=SQLITE-SQL
+        let average = self.conn.query_row(
+            r#"SELECT AVG(sp.guesses) FROM session_players sp JOIN sessions s ON s.id = sp.session_id
+               WHERE s.end_date IS NOT NULL AND sp.player_id = s.winner_id"#,
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(average)
+    }
+
+    fn player_ratings(&mut self) -> Result<Vec<(i64, f64)>> {
+        // language=SQLITE-SQL
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, rating FROM players WHERE rating IS NOT NULL")?;
+        let ratings = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ratings)
+    }
+
+    fn reset_ratings(&mut self, ratings: &[(i64, f64)]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        // language=SQLITE-SQL
+        tx.execute("UPDATE players SET rating=NULL;", [])?;
+        for &(player_id, rating) in ratings {
+            // language=SQLITE-SQL
+            tx.execute("UPDATE players SET rating=?1 WHERE id=?2;", params![rating, player_id])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn play_dates(&mut self, player_id: i64) -> Result<Vec<i64>> {
+        // language=SQLITE-SQL
+        let mut stmt = self.conn.prepare(
+            r#"SELECT s.start_date FROM sessions s
+               WHERE s.id IN (SELECT session_id FROM session_players WHERE player_id=?1)"#,
+        )?;
+        let dates = stmt
+            .query_map([player_id], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(dates)
+    }
+
+    fn unlock_achievement(&mut self, player_id: i64, achievement: Achievement, date: i64) -> Result<bool> {
+        // language=SQLITE-SQL
+        let inserted = self.conn.execute(
+            "INSERT INTO achievements(player_id, achievement, date) VALUES (?1, ?2, ?3) ON CONFLICT DO NOTHING;",
+            params![player_id, achievement.as_str(), date],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    fn player_achievements(&mut self, player_id: i64) -> Result<Vec<UnlockedAchievement>> {
+        // language=SQLITE-SQL
+        let mut stmt = self
+            .conn
+            .prepare("SELECT achievement, date FROM achievements WHERE player_id=?1 ORDER BY date, achievement")?;
+        let rows = stmt
+            .query_map([player_id], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(achievement, date)| {
+                Some(UnlockedAchievement {
+                    achievement: achievement.parse().ok()?,
+                    date,
+                })
+            })
+            .collect())
+    }
+
+    fn player_team(&mut self, player_id: i64) -> Result<Option<i64>> {
+        // language=SQLITE-SQL
+        let team_id = self
+            .conn
+            .query_row(
+                "SELECT team_id FROM team_members WHERE player_id=?1",
+                [player_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(team_id)
+    }
+
+    fn join_team(&mut self, player_id: i64, team: &str) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        // language=SQLITE-SQL
+        tx.execute(
+            "INSERT INTO teams(name, score) VALUES (?1, 0) ON CONFLICT DO NOTHING;",
+            [team],
+        )?;
+        // language=SQLITE-SQL
+        tx.execute(
+            r#"INSERT INTO team_members(player_id, team_id) SELECT ?1, id FROM teams WHERE name=?2
+               ON CONFLICT(player_id) DO UPDATE SET team_id=excluded.team_id;"#,
+            params![player_id, team],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn leave_team(&mut self, player_id: i64) -> Result<bool> {
+        // language=SQLITE-SQL
+        let deleted = self
+            .conn
+            .execute("DELETE FROM team_members WHERE player_id=?1;", [player_id])?;
+        Ok(deleted > 0)
+    }
+
+    fn session_teams(&mut self, session_id: i64) -> Result<Vec<(i64, i64)>> {
+        // language=SQLITE-SQL
+        let mut stmt = self.conn.prepare(
+            r#"SELECT g.player_id, g.team_id FROM guesses g
+               WHERE g.id IN (SELECT MAX(id) FROM guesses WHERE session_id=?1 GROUP BY player_id)
+                 AND g.team_id IS NOT NULL"#,
+        )?;
+        let teams = stmt
+            .query_map([session_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(teams)
+    }
+
+    fn team_stats(&mut self) -> Result<Vec<TeamStats>> {
+        // language=SQLITE-SQL
+        let mut stmt = self.conn.prepare(
+            r#"SELECT t.name, IFNULL(t.score,0) AS total,
+                      (SELECT COUNT(*) FROM team_members m WHERE m.team_id = t.id),
+                      (SELECT COUNT(*) FROM sessions s
+                       JOIN session_players sp ON sp.session_id = s.id AND sp.player_id = s.winner_id
+                       WHERE sp.team_id = t.id)
+               FROM teams t ORDER BY total DESC, t.name"#,
+        )?;
+        let teams = stmt
+            .query_map([], |row| {
+                Ok(TeamStats {
+                    name: row.get(0)?,
+                    score: row.get(1)?,
+                    members: row.get(2)?,
+                    wins: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(teams)
+    }
+
+    fn recent_words(&mut self, count: usize) -> Result<Vec<String>> {
+        // language=SQLITE-SQL
+        let mut stmt = self
+            .conn
+            .prepare("SELECT word FROM sessions ORDER BY id DESC LIMIT ?1")?;
+        let words = stmt
+            .query_map([count as i64], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(words)
+    }
+
+    fn definition(&mut self, word: &str) -> Result<Option<String>> {
+        // language=SQLITE-SQL
+        let definition = self
+            .conn
+            .query_row("SELECT definition FROM definitions WHERE word==?1", [word], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(definition)
+    }
+
+    fn insert_definition(&mut self, word: &str, definition: &str) -> Result<()> {
+        // language=SQLITE-SQL
+        self.conn.execute(
+            "INSERT OR REPLACE INTO definitions(word, definition) VALUES (?1,?2)",
+            params![word, definition],
+        )?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn current_session(&mut self) -> Result<Option<Session>> {
+        self.read(|conn| Queries { conn }.current_session()).await
+    }
+
+    async fn player_id(&mut self, nick: &str) -> Result<i64> {
+        let nick = nick.to_string();
+        self.call(move |conn| Queries { conn }.player_id(&nick)).await
+    }
+
+    async fn find_player(&mut self, nick: &str) -> Result<Option<i64>> {
+        let nick = nick.to_string();
+        self.read(move |conn| Queries { conn }.find_player(&nick)).await
+    }
+
+    async fn add_alias(&mut self, alias: &str, player_id: i64) -> Result<()> {
+        let alias = alias.to_string();
+        self.call(move |conn| Queries { conn }.add_alias(&alias, player_id))
+            .await
+    }
+
+    async fn merge_players(&mut self, from_id: i64, into_id: i64) -> Result<()> {
+        self.call(move |conn| Queries { conn }.merge_players(from_id, into_id))
+            .await
+    }
+
+    async fn forget_player(&mut self, player_id: i64, nick: &str) -> Result<Vec<String>> {
+        let nick = nick.to_string();
+        self.call(move |conn| Queries { conn }.forget_player(player_id, &nick))
+            .await
+    }
+
+    async fn insert_guess(
+        &mut self,
+        session_id: i64,
+        player_id: i64,
+        team_id: Option<i64>,
+        guess: &str,
+        cosine: f32,
+        date: i64,
+    ) -> Result<()> {
+        let guess = guess.to_string();
+        self.call(move |conn| Queries { conn }.insert_guess(session_id, player_id, team_id, &guess, cosine, date))
+            .await
+    }
+
+    async fn start_session(&mut self, session: &Session) -> Result<i64> {
+        let session = session.clone();
+        self.call(move |conn| Queries { conn }.start_session(&session)).await
+    }
+
+    async fn guess_count(&mut self, session_id: i64, player_id: i64) -> Result<usize> {
+        self.read(move |conn| Queries { conn }.guess_count(session_id, player_id))
+            .await
+    }
+
+    async fn best_guesses(&mut self, session_id: i64) -> Result<Vec<LeaderboardEntry>> {
+        self.read(move |conn| Queries { conn }.best_guesses(session_id)).await
+    }
+
+    async fn session_guesses(&mut self, session_id: i64) -> Result<Vec<(i64, String)>> {
+        self.read(move |conn| Queries { conn }.session_guesses(session_id))
+            .await
+    }
+
+    async fn add_winner(
+        &mut self,
+        session_id: i64,
+        player_id: i64,
+        place: usize,
+        points: i64,
+        date: i64,
+    ) -> Result<()> {
+        self.call(move |conn| Queries { conn }.add_winner(session_id, player_id, place, points, date))
+            .await
+    }
+
+    async fn session_winners(&mut self, session_id: i64) -> Result<Vec<SessionWinner>> {
+        self.read(move |conn| Queries { conn }.session_winners(session_id))
+            .await
+    }
+
+    async fn guess_counts(&mut self, session_id: i64) -> Result<Vec<(i64, usize)>> {
+        self.read(move |conn| Queries { conn }.guess_counts(session_id)).await
+    }
+
+    async fn archive_guesses(&mut self, before: i64) -> Result<usize> {
+        self.call(move |conn| Queries { conn }.archive_guesses(before)).await
+    }
+
+    async fn guess_history(&mut self, session_id: i64) -> Result<Vec<(String, String, f32)>> {
+        self.read(move |conn| Queries { conn }.guess_history(session_id)).await
+    }
+
+    async fn session_transcript(&mut self, session_id: i64) -> Result<Option<SessionTranscript>> {
+        self.read(move |conn| Queries { conn }.session_transcript(session_id))
+            .await
+    }
+
+    async fn recent_guesses(&mut self, session_id: i64, limit: usize) -> Result<Vec<GuessRecord>> {
+        self.read(move |conn| Queries { conn }.recent_guesses(session_id, limit))
+            .await
+    }
+
+    async fn last_share(&mut self, nick: &str) -> Result<Option<String>> {
+        let nick = nick.to_string();
+        self.read(move |conn| Queries { conn }.last_share(&nick)).await
+    }
+
+    async fn set_session_hints(&mut self, session_id: i64, hints: usize) -> Result<()> {
+        self.call(move |conn| Queries { conn }.set_session_hints(session_id, hints))
+            .await
+    }
+
+    async fn end_session(&mut self, session_id: i64, result: &SessionResult) -> Result<()> {
+        let result = result.clone();
+        self.call(move |conn| Queries { conn }.end_session(session_id, &result))
+            .await
+    }
+
+    async fn fetch_players(&mut self) -> Result<Vec<Player>> {
+        self.read(|conn| Queries { conn }.fetch_players()).await
+    }
+
+    async fn player_stats(&mut self) -> Result<Vec<PlayerStats>> {
+        self.read(|conn| Queries { conn }.player_stats()).await
+    }
+
+    async fn player_profile(&mut self, nick: &str) -> Result<Option<PlayerProfile>> {
+        let nick = nick.to_string();
+        self.read(move |conn| Queries { conn }.player_profile(&nick)).await
+    }
+
+    async fn current_season(&mut self) -> Result<Option<Season>> {
+        self.read(|conn| Queries { conn }.current_season()).await
+    }
+
+    async fn start_season(&mut self, start_date: i64) -> Result<i64> {
+        self.call(move |conn| Queries { conn }.start_season(start_date)).await
+    }
+
+    async fn season_scores(&mut self, season_id: i64) -> Result<Vec<Player>> {
+        self.read(move |conn| Queries { conn }.season_scores(season_id)).await
+    }
+
+    async fn end_season(&mut self, season_id: i64, end_date: i64, winner_id: Option<i64>) -> Result<()> {
+        self.call(move |conn| Queries { conn }.end_season(season_id, end_date, winner_id))
+            .await
+    }
+
+    async fn current_tournament(&mut self) -> Result<Option<Tournament>> {
+        self.read(|conn| Queries { conn }.current_tournament()).await
+    }
+
+    async fn start_tournament(&mut self, name: &str, start_date: i64, end_date: i64) -> Result<i64> {
+        let name = name.to_string();
+        self.call(move |conn| Queries { conn }.start_tournament(&name, start_date, end_date))
+            .await
+    }
+
+    async fn join_tournament(&mut self, tournament_id: i64, player_id: i64) -> Result<bool> {
+        self.call(move |conn| Queries { conn }.join_tournament(tournament_id, player_id))
+            .await
+    }
+
+    async fn tournament_standings(&mut self, tournament_id: i64) -> Result<Vec<Player>> {
+        self.read(move |conn| Queries { conn }.tournament_standings(tournament_id))
+            .await
+    }
+
+    async fn end_tournament(&mut self, tournament_id: i64, winner_id: Option<i64>) -> Result<()> {
+        self.call(move |conn| Queries { conn }.end_tournament(tournament_id, winner_id))
+            .await
+    }
+
+    async fn ended_sessions(&mut self) -> Result<Vec<(i64, Option<i64>)>> {
+        self.read(|conn| Queries { conn }.ended_sessions()).await
+    }
+
+    async fn average_guesses_to_win(&mut self) -> Result<Option<f64>> {
+        self.read(|conn| Queries { conn }.average_guesses_to_win()).await
+    }
+
+    async fn player_ratings(&mut self) -> Result<Vec<(i64, f64)>> {
+        self.read(|conn| Queries { conn }.player_ratings()).await
+    }
+
+    async fn reset_ratings(&mut self, ratings: &[(i64, f64)]) -> Result<()> {
+        let ratings = ratings.to_vec();
+        self.call(move |conn| Queries { conn }.reset_ratings(&ratings)).await
+    }
+
+    async fn play_dates(&mut self, player_id: i64) -> Result<Vec<i64>> {
+        self.read(move |conn| Queries { conn }.play_dates(player_id)).await
+    }
+
+    async fn unlock_achievement(&mut self, player_id: i64, achievement: Achievement, date: i64) -> Result<bool> {
+        self.call(move |conn| Queries { conn }.unlock_achievement(player_id, achievement, date))
+            .await
+    }
+
+    async fn player_achievements(&mut self, player_id: i64) -> Result<Vec<UnlockedAchievement>> {
+        self.read(move |conn| Queries { conn }.player_achievements(player_id))
+            .await
+    }
+
+    async fn player_team(&mut self, player_id: i64) -> Result<Option<i64>> {
+        self.read(move |conn| Queries { conn }.player_team(player_id)).await
+    }
+
+    async fn join_team(&mut self, player_id: i64, team: &str) -> Result<()> {
+        let team = team.to_string();
+        self.call(move |conn| Queries { conn }.join_team(player_id, &team))
+            .await
+    }
+
+    async fn leave_team(&mut self, player_id: i64) -> Result<bool> {
+        self.call(move |conn| Queries { conn }.leave_team(player_id)).await
+    }
+
+    async fn session_teams(&mut self, session_id: i64) -> Result<Vec<(i64, i64)>> {
+        self.read(move |conn| Queries { conn }.session_teams(session_id)).await
+    }
+
+    async fn team_stats(&mut self) -> Result<Vec<TeamStats>> {
+        self.read(|conn| Queries { conn }.team_stats()).await
+    }
+
+    async fn recent_words(&mut self, count: usize) -> Result<Vec<String>> {
+        self.read(move |conn| Queries { conn }.recent_words(count)).await
+    }
+
+    async fn definition(&mut self, word: &str) -> Result<Option<String>> {
+        let word = word.to_string();
+        self.read(move |conn| Queries { conn }.definition(&word)).await
+    }
+
+    async fn insert_definition(&mut self, word: &str, definition: &str) -> Result<()> {
+        let word = word.to_string();
+        let definition = definition.to_string();
+        self.call(move |conn| Queries { conn }.insert_definition(&word, &definition))
+            .await
+    }
+
+    async fn backup(&mut self) -> Result<String> {
+        let (directory, keep) = self
+            .backups
+            .clone()
+            .ok_or_else(|| anyhow!("backups are disabled, set `backup_directory` in the `[sqlite]` section"))?;
+        if self.db_path == ":memory:" {
+            bail!("in-memory databases can't be backed up");
+        }
+        let directory = Path::new(&directory).to_path_buf();
+        fs::create_dir_all(&directory)?;
+        let stem = Path::new(&self.db_path)
+            .file_stem()
+            .map_or("game".into(), |stem| stem.to_string_lossy().into_owned());
+        let prefix = format!("{}-", stem);
+        let path = directory.join(format!("{}{}.db", prefix, Utc::now().format("%Y%m%d-%H%M%S")));
+        let backup_path = path.clone();
+        // the writes wait for the copy, which would otherwise start over after each of them
+        self.call(move |conn| Ok(conn.backup(DatabaseName::Main, &backup_path, None)?))
+            .await?;
+        if keep > 0 {
+            prune_backups(&directory, &prefix, keep)?;
+        }
+        Ok(path.to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tables, views and indexes of a database, with their SQL.
+    fn schema(conn: &rusqlite::Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT type || ' ' || name || ': ' || IFNULL(sql, '') FROM sqlite_master ORDER BY type, name")
+            .unwrap();
+        let schema = stmt.query_map([], |row| row.get(0)).unwrap();
+        schema.collect::<Result<_, _>>().unwrap()
+    }
+
+    fn version(conn: &rusqlite::Connection) -> usize {
+        conn.query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn setup_new_database() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        setup_schema(&mut conn).unwrap();
+        assert_eq!(version(&conn), MIGRATIONS.len());
+        let schema_before = schema(&conn);
+
+        // already up to date: the migrations would fail if they ran again
+        setup_schema(&mut conn).unwrap();
+        assert_eq!(version(&conn), MIGRATIONS.len());
+        assert_eq!(schema(&conn), schema_before);
+        assert_eq!(
+            conn.query_row("SELECT COUNT(*) FROM schema_version", [], |row| row.get::<_, i64>(0))
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn upgrade_baseline_database() {
+        // the schema before migrations were versioned, with a column added since then
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            // language=SQLITE-SQL
+            r#"
+CREATE TABLE players (id INTEGER PRIMARY KEY, nick TEXT UNIQUE NOT NULL, score INTEGER);
+CREATE TABLE sessions
+         (id INTEGER PRIMARY KEY, start_date INTEGER, end_date INTEGER, planned_end_date INTEGER,
+          word TEXT NOT NULL, winner_id INTEGER REFERENCES players(id) ON DELETE NO ACTION, playing INTEGER);
+CREATE TABLE current_session
+         (id INTEGER PRIMARY KEY DEFAULT 0, session_id INTEGER REFERENCES sessions(id) ON DELETE NO ACTION);
+INSERT INTO current_session(id) VALUES (0);
+CREATE TABLE guesses
+         (id INTEGER PRIMARY KEY, session_id INTEGER REFERENCES sessions(id) ON DELETE NO ACTION,
+          player_id INTEGER REFERENCES players(id) ON DELETE NO ACTION, guess TEXT NOT NULL, cosine NUMERIC);
+ALTER TABLE sessions ADD COLUMN metric TEXT;
+INSERT INTO players(nick, score) VALUES ('alice', 12);
+INSERT INTO sessions(start_date, end_date, planned_end_date, word, winner_id, metric)
+    VALUES (1000, 2000, 3000, 'chat', 1, 'cosine');
+INSERT INTO guesses(session_id, player_id, guess, cosine) VALUES (1, 1, 'chat', 1.0);
+"#,
+        )
+        .unwrap();
+
+        setup_schema(&mut conn).unwrap();
+        assert_eq!(version(&conn), MIGRATIONS.len());
+        let schema_before = schema(&conn);
+        // the data is still there, and the new columns and tables can be used
+        let (nick, word, metric): (String, String, String) = conn
+            .query_row(
+                "SELECT p.nick, s.word, s.metric FROM sessions s JOIN players p ON p.id = s.winner_id",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            (nick.as_str(), word.as_str(), metric.as_str()),
+            ("alice", "chat", "cosine")
+        );
+        let guesses: i64 = conn
+            .query_row(
+                "SELECT guesses FROM session_players WHERE session_id=1 AND player_id=1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(guesses, 1);
+
+        // running the migrations again does nothing
+        setup_schema(&mut conn).unwrap();
+        assert_eq!(version(&conn), MIGRATIONS.len());
+        assert_eq!(schema(&conn), schema_before);
+
+        // same schema as a new database, apart from the column order
+        let mut new = rusqlite::Connection::open_in_memory().unwrap();
+        setup_schema(&mut new).unwrap();
+        let tables = |conn: &rusqlite::Connection| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT m.name, p.name FROM sqlite_master m, pragma_table_info(m.name) p ORDER BY m.name, p.name",
+                )
+                .unwrap();
+            let columns = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+            columns.collect::<Result<Vec<(String, String)>, _>>().unwrap()
+        };
+        assert_eq!(tables(&conn), tables(&new));
+    }
+
+    #[test]
+    fn archive_partly_archived_session() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        setup_schema(&mut conn).unwrap();
+        conn.execute_batch(
+            // language=SQLITE-SQL
+            r#"
+INSERT INTO players(nick, score) VALUES ('alice', 0), ('bob', 0);
+INSERT INTO sessions(start_date, end_date, planned_end_date, word) VALUES (1000, 2000, 3000, 'chat');
+INSERT INTO session_stats(session_id, player_id, guesses, best_miss) VALUES (1, 1, 3, 0.5);
+INSERT INTO guesses(session_id, player_id, guess, cosine) VALUES (1, 1, 'chien', 0.7), (1, 1, 'chat', 1.0),
+                                                                  (1, 2, 'lion', 0.4);
+"#,
+        )
+        .unwrap();
+
+        let mut queries = Queries { conn: &mut conn };
+        assert_eq!(queries.archive_guesses(2500).unwrap(), 3);
+        assert_eq!(queries.archive_guesses(2500).unwrap(), 0);
+        let mut stmt = conn
+            .prepare("SELECT player_id, guesses, best_miss FROM session_players WHERE session_id=1 ORDER BY player_id")
+            .unwrap();
+        let stats = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<Vec<(i64, i64, f64)>, _>>()
+            .unwrap();
+        assert_eq!(stats, vec![(1, 5, 0.7), (2, 1, 0.4)]);
+    }
+
+    #[test]
+    fn refuse_newer_schema() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        setup_schema(&mut conn).unwrap();
+        conn.execute("UPDATE schema_version SET version=?1", [MIGRATIONS.len() + 1])
+            .unwrap();
+        assert!(setup_schema(&mut conn).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+//! Checks of a word model file (`wordlebot validate-model <path>`)
+use crate::words::{LoadProgress, ModelConfig, Words};
+use anyhow::{bail, Context, Result};
+
+/// Reads the model file at `path` (or the configured one) with the configured format and filters, and
+/// prints a summary of its contents. Fails if the file can't be parsed or has no usable words.
+pub fn validate_model(mut config: ModelConfig, path: Option<&str>) -> Result<()> {
+    if let Some(path) = path {
+        config.word2vec_model_file = path.to_string();
+    }
+    let report = Words::check_model(&config, &LoadProgress::default())
+        .with_context(|| format!("could not read model file `{}`", config.word2vec_model_file))?;
+
+    println!("model file: `{}` ({:?})", config.word2vec_model_file, config.model_format);
+    println!("vector size: {}", report.dim);
+    println!("vocabulary size: {}", report.words);
+    if report.duplicates > 0 {
+        println!("duplicate words: {} (only the first occurrence is used)", report.duplicates);
+    }
+    println!(
+        "normalized vectors: {}",
+        if report.normalized {
+            "yes"
+        } else if config.normalize_vectors {
+            "no (normalized when loading)"
+        } else {
+            "no"
+        }
+    );
+    if report.zero_vectors > 0 {
+        println!("zero vectors: {}", report.zero_vectors);
+    }
+    if report.invalid_vectors > 0 {
+        println!("vectors with infinite or NaN components: {}", report.invalid_vectors);
+    }
+    println!(
+        "words passing the vocabulary filters: {} ({} dropped)",
+        report.kept,
+        report.words - report.kept
+    );
+
+    if report.kept == 0 {
+        bail!("no word of the model passes the vocabulary filters");
+    }
+    if report.invalid_vectors > 0 {
+        bail!("the model contains invalid vectors");
+    }
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! Classic Wordle rules
+use serde::Serialize;
+
+/// Length of the words in Wordle mode.
+pub const WORDLE_WORD_LENGTH: usize = 5;
+
+/// Feedback for one letter of a guess.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum LetterHint {
+    /// The letter is at the right place (green).
+    Correct,
+    /// The letter is in the word but at another place (yellow).
+    Present,
+    /// The letter is not in the word, or not as many times (gray).
+    Absent,
+}
+
+/// Returns whether the word can be used in Wordle mode (as a guess or as the word to find).
+pub fn is_wordle_word(word: &str) -> bool {
+    word.chars().count() == WORDLE_WORD_LENGTH && word.chars().all(char::is_alphabetic)
+}
+
+/// Compares a guess with the word to find, letter by letter. Both must have the same number of letters.
+pub fn evaluate(guess: &str, target: &str) -> Vec<LetterHint> {
+    let guess: Vec<char> = guess.chars().collect();
+    let target: Vec<char> = target.chars().collect();
+    let mut hints = vec![LetterHint::Absent; guess.len()];
+    // letters of the target not matched by a correct letter
+    let mut remaining = Vec::new();
+
+    for (i, (&g, &t)) in guess.iter().zip(target.iter()).enumerate() {
+        if g == t {
+            hints[i] = LetterHint::Correct;
+        } else {
+            remaining.push(t);
+        }
+    }
+    for (i, &g) in guess.iter().enumerate() {
+        if hints[i] == LetterHint::Correct {
+            continue;
+        }
+        if let Some(pos) = remaining.iter().position(|&t| t == g) {
+            remaining.swap_remove(pos);
+            hints[i] = LetterHint::Present;
+        }
+    }
+    hints
+}
+
+/// Score of a guess between 0 (no letter found) and 1 (word found), recorded in place of the similarity.
+pub fn score(hints: &[LetterHint]) -> f32 {
+    let points: usize = hints
+        .iter()
+        .map(|hint| match hint {
+            LetterHint::Correct => 2,
+            LetterHint::Present => 1,
+            LetterHint::Absent => 0,
+        })
+        .sum();
+    points as f32 / (2 * hints.len()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses hints written as `G` (correct), `Y` (present) and `.` (absent).
+    fn hints(pattern: &str) -> Vec<LetterHint> {
+        pattern
+            .chars()
+            .map(|c| match c {
+                'G' => LetterHint::Correct,
+                'Y' => LetterHint::Present,
+                _ => LetterHint::Absent,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn evaluate_guesses() {
+        let cases = [
+            // (guess, word, hints)
+            ("crane", "crane", "GGGGG"),
+            ("fghij", "crane", "....."),
+            ("nacre", "crane", "YYYYG"),
+            // a repeated letter of the guess is only present as many times as in the word
+            ("allee", "table", "YY..G"),
+            ("speed", "abide", "..Y.Y"),
+            ("eerie", "there", "Y.Y.G"),
+            // correct letters are matched first, even after the same letter elsewhere
+            ("lolly", "hello", ".YGG."),
+            ("aabbb", "bbaaa", "YYYY."),
+            // repeated letters of the word
+            ("pizza", "jazzy", "..GGY"),
+            // accented letters are different letters
+            ("passé", "pâtés", "G.Y.Y"),
+        ];
+        for (guess, word, expected) in cases {
+            assert_eq!(evaluate(guess, word), hints(expected), "guess \"{}\" for \"{}\"", guess, word);
+        }
+    }
+
+    #[test]
+    fn score_bounds() {
+        assert_eq!(score(&hints("GGGGG")), 1.0);
+        assert_eq!(score(&hints(".....")), 0.0);
+        assert_eq!(score(&hints("YY..G")), 0.4);
+    }
+}
@@ -1,66 +1,1805 @@
-use rand::Rng;
-use std::{cmp::Ordering, fs::File, io::BufReader};
+use crate::{
+    backend::EmbeddingBackend,
+    embedding_service::EmbeddingService,
+    fasttext::FastTextModel,
+    hnsw::Hnsw,
+    lru::LruCache,
+    matrix::{self, FlatFile, Matrix},
+};
+use anyhow::{anyhow, Result};
+use flate2::read::GzDecoder;
+use rand::{seq::SliceRandom, Rng, RngCore};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    cmp::{Ordering, Reverse},
+    collections::{hash_map::DefaultHasher, BinaryHeap, HashMap, HashSet},
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{BufRead, BufReader, Read},
+    ops::RangeInclusive,
+    str::FromStr,
+    sync::{
+        atomic::{self, AtomicU32, AtomicU64},
+        Arc, Mutex, OnceLock,
+    },
+    time::UNIX_EPOCH,
+};
+use rust_stemmers::{Algorithm, Stemmer};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 use word2vec::vectorreader::WordVectorReader;
 
+fn default_model_file() -> String {
+    "word2vec.bin".to_string()
+}
+
+/// Format of the word model file.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelFormat {
+    /// word2vec format, binary or text (detected automatically).
+    #[default]
+    Word2vec,
+    /// ConceptNet Numberbatch text format (possibly gzipped), with `/c/<lang>/<word>` keys.
+    Numberbatch,
+    /// GloVe text format (possibly gzipped): like word2vec text files, but without a header.
+    Glove,
+    /// fastText `.vec` text export (possibly gzipped).
+    Fasttext,
+}
+
+/// Representation of the vectors in memory.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorStorage {
+    /// Single-precision floats.
+    #[default]
+    F32,
+    /// Half-precision floats: half the memory, with a negligible effect on similarities.
+    F16,
+    /// 8-bit integers with a scale factor per vector: a quarter of the memory, with a small effect on
+    /// similarities.
+    Int8,
+}
+
+/// How the similarity between two word vectors is computed.
+#[derive(Copy, Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    /// Cosine similarity.
+    #[default]
+    Cosine,
+    /// Dot product.
+    Dot,
+    /// Negative euclidean distance.
+    Euclidean,
+}
+
+impl Metric {
+    /// Returns the similarity between two vectors according to this metric. Higher means more similar.
+    pub fn similarity(self, a: &[f32], b: &[f32]) -> f32 {
+        self.similarity_from_dot(dot(a, b), norm(a), norm(b))
+    }
+
+    /// Same as `similarity`, from the dot product and the L2 norms of the vectors.
+    pub fn similarity_from_dot(self, dot: f32, norm_a: f32, norm_b: f32) -> f32 {
+        match self {
+            Metric::Cosine => {
+                if norm_a == 0.0 || norm_b == 0.0 {
+                    0.0
+                } else {
+                    dot / (norm_a * norm_b)
+                }
+            }
+            Metric::Dot => dot,
+            // |a - b|² = |a|² + |b|² - 2 a.b
+            Metric::Euclidean => -(norm_a * norm_a + norm_b * norm_b - 2.0 * dot).max(0.0).sqrt(),
+        }
+    }
+
+    /// Name of the metric, as stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Metric::Cosine => "cosine",
+            Metric::Dot => "dot",
+            Metric::Euclidean => "euclidean",
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Metric> {
+        match s {
+            "cosine" => Ok(Metric::Cosine),
+            "dot" => Ok(Metric::Dot),
+            "euclidean" => Ok(Metric::Euclidean),
+            _ => Err(anyhow!("unknown metric `{}`", s)),
+        }
+    }
+}
+
+/// Dot product of two vectors.
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(&a, &b)| a * b).sum()
+}
+
+/// L2 norm of a vector.
+pub fn norm(v: &[f32]) -> f32 {
+    dot(v, v).sqrt()
+}
+
+/// Scales a vector to unit length. Null vectors are left as is.
+pub fn normalize(v: &mut [f32]) {
+    let norm = norm(v);
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+fn default_normalize_vectors() -> bool {
+    true
+}
+
+fn default_max_neighbors() -> usize {
+    100
+}
+
+/// Word model configuration.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModelConfig {
+    /// Word2Vec model binary
+    #[serde(default = "default_model_file")]
+    pub word2vec_model_file: String,
+    /// Format of the model file.
+    #[serde(default)]
+    pub model_format: ModelFormat,
+    /// Language of the words to keep when loading a multilingual model (e.g. `fr`).
+    #[serde(default)]
+    pub model_language: Option<String>,
+    /// Default similarity metric for this model.
+    #[serde(default)]
+    pub metric: Metric,
+    /// Scale all vectors to unit length when loading the model. Can be disabled to save some loading
+    /// time if the model is already normalized. Note that this also affects the `dot` and `euclidean` metrics.
+    #[serde(default = "default_normalize_vectors")]
+    pub normalize_vectors: bool,
+    /// fastText binary model (`.bin`) used to compute the vectors of guesses that are not in the
+    /// vocabulary, from their character n-grams. It should be the model the vocabulary was exported from.
+    #[serde(default)]
+    pub fasttext_model_file: Option<String>,
+    /// Flat vector file to memory-map instead of keeping the vectors in memory. It is built from the
+    /// model file on the first start, and rebuilt when the model or its settings change.
+    #[serde(default)]
+    pub mmap_file: Option<String>,
+    /// Keep a preprocessed copy of the model next to it (`<model file>.cache`), which is much faster
+    /// to load than the model itself. Not used if `mmap_file` is set.
+    #[serde(default)]
+    pub model_cache: bool,
+    /// How the vectors are stored in memory. Only `f32` is supported with `mmap_file`.
+    #[serde(default)]
+    pub vector_storage: VectorStorage,
+    /// Maximum number of neighbors returned by `!thesaurus` and `!analogy`.
+    #[serde(default = "default_max_neighbors")]
+    pub max_neighbors: usize,
+    /// Build an approximate nearest-neighbor index (HNSW) at startup. It speeds up `!thesaurus`, hints
+    /// and ranks with the default metric, at the cost of some accuracy. The index is kept next to the
+    /// model (`<model file>.hnsw`) if `model_cache` is set.
+    #[serde(default)]
+    pub hnsw_index: bool,
+    /// Drop the words of the model that contain anything else than letters (digits, `_`, punctuation...).
+    #[serde(default)]
+    pub letters_only: bool,
+    /// Drop the words of the model written in capitals, such as acronyms.
+    #[serde(default)]
+    pub skip_acronyms: bool,
+    /// Drop the words of the model shorter than this number of characters.
+    #[serde(default)]
+    pub min_word_length: usize,
+    /// Word frequency file: one word per line followed by its number of occurrences, separated by
+    /// whitespace. Words missing from the file are considered to never occur.
+    #[serde(default)]
+    pub frequency_file: Option<String>,
+    /// Words occurring fewer times than this in `frequency_file` are never picked as the word to find nor
+    /// listed as neighbors, but are still accepted as guesses.
+    #[serde(default)]
+    pub min_word_frequency: u64,
+    /// Stop word file, with one word per line. Stop words are never picked as the word to find.
+    #[serde(default)]
+    pub stop_word_file: Option<String>,
+    /// Reject stop words as guesses instead of giving their similarity.
+    #[serde(default)]
+    pub reject_stop_words: bool,
+    /// Curated list of words to find (e.g. `targets.txt`), with one word per line. If set, the word to
+    /// find is only picked from this list, while guesses are still checked against the whole vocabulary.
+    #[serde(default)]
+    pub target_file: Option<String>,
+    /// Dictionary word list, with one word per line. If set, only words of the dictionary (ignoring case)
+    /// are picked as the word to find, which avoids typos and concatenations found in the training corpus.
+    #[serde(default)]
+    pub dictionary_file: Option<String>,
+    /// Accept guesses typed without accents (e.g. `eleve` for `élève`).
+    #[serde(default)]
+    pub ignore_accents: bool,
+    /// Snowball stemmer (e.g. `French`) used to match guesses that are not in the vocabulary with a word of the
+    /// vocabulary that has the same stem (e.g. `chevaux` with `cheval`).
+    #[serde(default)]
+    pub stemmer: Option<Algorithm>,
+    /// Allow multi-word phrases of the model (e.g. `new_york`) to be picked as the word to find. They can be
+    /// guessed in any case.
+    #[serde(default)]
+    pub phrase_targets: bool,
+    /// Minimum number of characters of the word to find. Shorter words can still be guessed.
+    #[serde(default)]
+    pub target_min_len: usize,
+    /// Maximum number of characters of the word to find. Longer words can still be guessed.
+    #[serde(default)]
+    pub target_max_len: Option<usize>,
+    /// Keep the case of guesses instead of converting them to lowercase, for models with capitalized words
+    /// (e.g. German nouns).
+    #[serde(default)]
+    pub case_sensitive_guesses: bool,
+    /// Keep the punctuation typed in guesses instead of removing it.
+    #[serde(default)]
+    pub keep_guess_punctuation: bool,
+    /// Punctuation that is not removed from guesses because the words of the model contain it (e.g.
+    /// `aujourd'hui`, `arc-en-ciel`).
+    #[serde(default = "default_guess_word_punctuation")]
+    pub guess_word_punctuation: String,
+    /// Second model (`[ensemble]`, with the same settings as the main model) whose similarities are
+    /// averaged with the ones of the main model. Only the words of both models are kept.
+    #[serde(default)]
+    pub ensemble: Option<Box<EnsembleConfig>>,
+}
+
+fn default_guess_word_punctuation() -> String {
+    "-'".to_string()
+}
+
+fn default_ensemble_weight() -> f32 {
+    0.5
+}
+
+/// Second model of an ensemble.
+#[derive(Clone, Debug, Deserialize)]
+pub struct EnsembleConfig {
+    #[serde(flatten)]
+    pub model: ModelConfig,
+    /// Weight of the similarities of this model, between 0 and 1. The similarities of the main model have
+    /// the remaining weight.
+    #[serde(default = "default_ensemble_weight")]
+    pub weight: f32,
+}
+
+impl ModelConfig {
+    /// Identifies the model file and the settings that affect its loading, to detect stale caches.
+    fn fingerprint(&self) -> anyhow::Result<u64> {
+        let metadata = std::fs::metadata(&self.word2vec_model_file)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+        let mut hasher = DefaultHasher::new();
+        (
+            &self.word2vec_model_file,
+            metadata.len(),
+            modified,
+            format!("{:?}", self.model_format),
+            &self.model_language,
+            self.normalize_vectors,
+            self.letters_only,
+            self.skip_acronyms,
+            self.min_word_length,
+        )
+            .hash(&mut hasher);
+        if let Some(ref ensemble) = self.ensemble {
+            (ensemble.model.fingerprint()?, ensemble.weight.to_bits()).hash(&mut hasher);
+        }
+        Ok(hasher.finish())
+    }
+
+    /// Returns whether a word of the model passes the vocabulary filters.
+    fn keep_word(&self, word: &str) -> bool {
+        if self.letters_only && !word.chars().all(char::is_alphabetic) {
+            return false;
+        }
+        if self.skip_acronyms
+            && word.chars().count() > 1
+            && word.chars().any(char::is_uppercase)
+            && !word.chars().any(char::is_lowercase)
+        {
+            return false;
+        }
+        word.chars().count() >= self.min_word_length
+    }
+}
+
+/// How much of the word model has been loaded.
+#[derive(Clone, Default)]
+pub struct LoadProgress(Arc<LoadCounters>);
+
+#[derive(Default)]
+struct LoadCounters {
+    percent: AtomicU32,
+    /// Number of bytes of the model file read so far.
+    bytes: AtomicU64,
+    /// Number of words read so far.
+    words: AtomicU64,
+    /// Number of words of the model, 0 if unknown.
+    total_words: AtomicU64,
+}
+
+impl LoadProgress {
+    fn set(&self, done: u64, total: u64) {
+        let percent = (done * 100).checked_div(total).unwrap_or(0).min(100);
+        self.0.percent.store(percent as u32, atomic::Ordering::Relaxed);
+    }
+
+    fn set_bytes(&self, bytes: u64) {
+        self.0.bytes.store(bytes, atomic::Ordering::Relaxed);
+    }
+
+    fn set_total_words(&self, total: u64) {
+        self.0.total_words.store(total, atomic::Ordering::Relaxed);
+    }
+
+    fn add_words(&self, count: u64) {
+        self.0.words.fetch_add(count, atomic::Ordering::Relaxed);
+    }
+
+    /// Percentage of the model that has been loaded.
+    pub fn percent(&self) -> u32 {
+        self.0.percent.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Number of bytes of the model file read so far.
+    pub fn bytes(&self) -> u64 {
+        self.0.bytes.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Number of words read so far, and number of words of the model if it's known.
+    pub fn words(&self) -> (u64, Option<u64>) {
+        let total = self.0.total_words.load(atomic::Ordering::Relaxed);
+        (self.0.words.load(atomic::Ordering::Relaxed), (total > 0).then_some(total))
+    }
+}
+
+/// Reader that reports how much of a file has been read.
+struct ProgressReader<R> {
+    inner: R,
+    read: u64,
+    total: u64,
+    progress: LoadProgress,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        self.progress.set(self.read, self.total);
+        self.progress.set_bytes(self.read);
+        Ok(n)
+    }
+}
+
+/// Opens a file, reporting the progress of reading it.
+fn open_with_progress(path: &str, progress: &LoadProgress) -> std::io::Result<ProgressReader<File>> {
+    let file = File::open(path)?;
+    let total = file.metadata()?.len();
+    Ok(ProgressReader {
+        inner: file,
+        read: 0,
+        total,
+        progress: progress.clone(),
+    })
+}
+
+/// Returned when the word model is needed but is still loading.
+#[derive(Debug, thiserror::Error)]
+#[error("model still loading, {0}% done")]
+pub struct StillLoading(pub u32);
+
+/// Handle to the word model, which may still be loading in the background.
+#[derive(Clone, Default)]
+pub struct SharedWords {
+    words: Arc<OnceLock<Arc<dyn EmbeddingBackend>>>,
+    progress: LoadProgress,
+}
+
+impl SharedWords {
+    /// Returns the word model if it's loaded.
+    pub fn get(&self) -> Result<&Arc<dyn EmbeddingBackend>, StillLoading> {
+        self.words.get().ok_or_else(|| StillLoading(self.progress.percent()))
+    }
+
+    /// Makes the loaded word model available. Only the first call has an effect.
+    pub fn set(&self, words: impl EmbeddingBackend + 'static) {
+        let _ = self.words.set(Arc::new(words));
+    }
+
+    /// Progress of the loading, to pass to the loading functions.
+    pub fn progress(&self) -> &LoadProgress {
+        &self.progress
+    }
+}
+
+impl From<Words> for SharedWords {
+    fn from(words: Words) -> SharedWords {
+        let shared = SharedWords::default();
+        shared.set(words);
+        shared
+    }
+}
+
+/// Word models of each language, which may still be loading.
+#[derive(Clone, Default)]
+pub struct WordModels {
+    /// Model used by sessions that don't specify a language.
+    default: SharedWords,
+    /// Models of the other languages, by language code (e.g. `en`).
+    languages: HashMap<String, SharedWords>,
+}
+
+impl WordModels {
+    pub fn new(default: SharedWords, languages: HashMap<String, SharedWords>) -> WordModels {
+        WordModels { default, languages }
+    }
+
+    /// Returns the model of a language, or the default model if `language` is `None`.
+    pub fn get(&self, language: Option<&str>) -> Result<&SharedWords> {
+        match language {
+            None => Ok(&self.default),
+            Some(language) => self
+                .languages
+                .get(language)
+                .ok_or_else(|| anyhow!("unknown language `{}`", language)),
+        }
+    }
+}
+
+impl From<SharedWords> for WordModels {
+    fn from(default: SharedWords) -> WordModels {
+        WordModels::new(default, HashMap::new())
+    }
+}
+
+/// Returns whether a word of the model is a phrase of several words joined by underscores (`new_york`).
+fn is_phrase(word: &str) -> bool {
+    word.trim_matches('_').contains('_')
+}
+
+/// How guesses are cleaned up before being looked up in the vocabulary.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GuessPolicy {
+    /// Convert guesses to lowercase.
+    casefold: bool,
+    /// Remove the punctuation of guesses, except `word_punctuation`.
+    strip_punctuation: bool,
+    /// Punctuation found in the words of the model.
+    word_punctuation: String,
+}
+
+impl Default for GuessPolicy {
+    fn default() -> GuessPolicy {
+        GuessPolicy {
+            casefold: true,
+            strip_punctuation: true,
+            word_punctuation: default_guess_word_punctuation(),
+        }
+    }
+}
+
+impl GuessPolicy {
+    fn new(config: &ModelConfig) -> GuessPolicy {
+        GuessPolicy {
+            casefold: !config.case_sensitive_guesses,
+            strip_punctuation: !config.keep_guess_punctuation,
+            word_punctuation: config.guess_word_punctuation.clone(),
+        }
+    }
+
+    /// Trims a guess, removes its punctuation, joins its words with underscores like the phrases of the
+    /// models (`New  York!` -> `new_york`) and converts it to lowercase, as configured.
+    pub fn normalize(&self, guess: &str) -> String {
+        let guess: String = guess
+            .chars()
+            .filter(|&c| {
+                !self.strip_punctuation
+                    || c.is_alphanumeric()
+                    || c.is_whitespace()
+                    || c == '_'
+                    || is_combining_mark(c)
+                    || self.word_punctuation.contains(c)
+            })
+            .collect();
+        let guess = guess.split(|c: char| c.is_whitespace() || c == '_').filter(|word| !word.is_empty());
+        let guess = guess.collect::<Vec<_>>().join("_");
+        if self.casefold {
+            guess.to_lowercase()
+        } else {
+            guess
+        }
+    }
+}
+
+/// Words and vectors read from a model, with the vectors stored one after the other in a single buffer to
+/// limit the memory used while loading.
+#[derive(Default)]
+struct Vocabulary {
+    words: Vec<String>,
+    /// Size of the vectors, known once the first one is read.
+    dim: Option<usize>,
+    data: Vec<f32>,
+}
+
+impl Vocabulary {
+    fn with_capacity(len: usize, dim: usize) -> Vocabulary {
+        Vocabulary {
+            words: Vec::with_capacity(len),
+            dim: Some(dim),
+            data: Vec::with_capacity(len * dim),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Checks the size of the last vector added to `data`, which starts at `start`.
+    fn check_last(&mut self, word: &str, start: usize) -> anyhow::Result<()> {
+        let len = self.data.len() - start;
+        if len != *self.dim.get_or_insert(len) {
+            return Err(anyhow!("invalid vector size for `{}`", word));
+        }
+        Ok(())
+    }
+
+    /// Adds a word and its vector. All vectors must have the same size.
+    fn push(&mut self, word: String, vector: &[f32]) -> anyhow::Result<()> {
+        let start = self.data.len();
+        self.data.extend_from_slice(vector);
+        self.check_last(&word, start)?;
+        self.words.push(word);
+        Ok(())
+    }
+
+    /// Adds a word and its vector given as text. All vectors must have the same size.
+    fn push_text(&mut self, word: &str, vector: &[&str]) -> anyhow::Result<()> {
+        let start = self.data.len();
+        for component in vector {
+            self.data.push(component.parse()?);
+        }
+        self.check_last(word, start)?;
+        self.words.push(word.to_string());
+        Ok(())
+    }
+
+    /// Keeps only the words that satisfy the predicate.
+    fn retain(&mut self, keep: impl Fn(&str) -> bool) {
+        let dim = self.dim.unwrap_or(0);
+        let mut kept = 0;
+        for i in 0..self.words.len() {
+            if keep(&self.words[i]) {
+                self.words.swap(kept, i);
+                self.data.copy_within(i * dim..(i + 1) * dim, kept * dim);
+                kept += 1;
+            }
+        }
+        self.words.truncate(kept);
+        self.data.truncate(kept * dim);
+    }
+
+    /// Combines the vectors of the words of both vocabularies, so that the cosine similarity of the combined
+    /// vectors is the weighted mean of the cosine similarities in each vocabulary: each vector is normalized,
+    /// scaled by the square root of the weight of its vocabulary, and the two are concatenated.
+    fn ensemble(self, other: Vocabulary, weight: f32) -> anyhow::Result<Vocabulary> {
+        if !(0.0..=1.0).contains(&weight) {
+            return Err(anyhow!("the ensemble weight must be between 0 and 1"));
+        }
+        let (dim, other_dim) = (self.dim.unwrap_or(0), other.dim.unwrap_or(0));
+        let mut index = HashMap::with_capacity(other.len());
+        for (i, word) in other.words.iter().enumerate() {
+            index.entry(word.as_str()).or_insert(i);
+        }
+
+        let mut combined = Vocabulary::with_capacity(self.len(), dim + other_dim);
+        let (scale, other_scale) = ((1.0 - weight).sqrt(), weight.sqrt());
+        let mut vector = vec![0.0; dim + other_dim];
+        for (i, word) in self.words.into_iter().enumerate() {
+            let Some(&j) = index.get(word.as_str()) else {
+                continue;
+            };
+            let (head, tail) = vector.split_at_mut(dim);
+            head.copy_from_slice(&self.data[i * dim..(i + 1) * dim]);
+            tail.copy_from_slice(&other.data[j * other_dim..(j + 1) * other_dim]);
+            normalize(head);
+            normalize(tail);
+            head.iter_mut().for_each(|x| *x *= scale);
+            tail.iter_mut().for_each(|x| *x *= other_scale);
+            combined.push(word, &vector)?;
+        }
+        Ok(combined)
+    }
+
+    /// Scales all vectors to unit length.
+    fn normalize(&mut self) {
+        if let Some(dim) = self.dim.filter(|&dim| dim > 0) {
+            self.data.chunks_mut(dim).for_each(normalize);
+        }
+    }
+}
+
+/// Maximum difference between the norm of normalized vectors and 1.
+const NORMALIZED_TOLERANCE: f32 = 1e-3;
+
+/// Summary of the contents of a model file (`wordlebot validate-model`).
+pub struct ModelReport {
+    /// Size of the vectors.
+    pub dim: usize,
+    /// Number of entries of the model.
+    pub words: usize,
+    /// Number of entries whose word already appears earlier in the model.
+    pub duplicates: usize,
+    /// Whether all vectors have a unit length.
+    pub normalized: bool,
+    /// Number of vectors whose components are all zero.
+    pub zero_vectors: usize,
+    /// Number of vectors with infinite or NaN components.
+    pub invalid_vectors: usize,
+    /// Number of entries that pass the vocabulary filters.
+    pub kept: usize,
+}
+
+/// Number of texts sent at once to the embedding service when loading the vocabulary.
+const EMBEDDING_BATCH_SIZE: usize = 64;
+
+/// Word of the vocabulary considered by a nearest-neighbor search.
+struct Candidate {
+    index: usize,
+    similarity: f32,
+}
+
+// Candidates are ordered from the most to the least similar, so that a `BinaryHeap` of candidates pops the
+// least similar first.
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.similarity.total_cmp(&self.similarity)
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
 pub struct Words {
-    pub vocabulary: Vec<(String, Vec<f32>)>,
+    /// Words of the vocabulary.
+    words: Vec<String>,
+    /// Vector of each word of the vocabulary.
+    vectors: Matrix,
+    /// Position of each word in `words`.
+    index: HashMap<String, usize>,
+    /// L2 norm of each vector.
+    norms: Vec<f32>,
+    /// fastText model used for guesses that are not in the vocabulary.
+    subwords: Option<FastTextModel>,
+    /// Whether the vectors of the vocabulary are normalized.
+    normalized: bool,
+    /// External embedding service used for guesses that are not in the vocabulary.
+    service: Option<EmbeddingService>,
+    /// Default similarity metric.
+    metric: Metric,
+    /// Maximum number of neighbors returned by `thesaurus` and `analogy`.
+    max_neighbors: usize,
+    /// Approximate nearest-neighbor index, for the default metric.
+    ann: Option<Hnsw>,
+    /// Whether each word of the vocabulary is too rare to be picked or listed as a neighbor.
+    rare: Vec<bool>,
+    /// Number of occurrences of each word of the vocabulary, if there's a frequency file.
+    frequencies: Option<Vec<u64>>,
+    /// Words that are never picked as the word to find.
+    stop_words: HashSet<String>,
+    /// Whether stop words are rejected as guesses.
+    reject_stop_words: bool,
+    /// Whether phrases may be picked as the word to find.
+    phrase_targets: bool,
+    /// Allowed number of characters of the word to find.
+    target_len: RangeInclusive<usize>,
+    /// Curated words to find, if any.
+    targets: Option<HashSet<String>>,
+    /// Whether each word of the vocabulary is in the dictionary, if there's one.
+    in_dictionary: Option<Vec<bool>>,
+    /// Position in `words` of each word without its accents, if guesses without accents are accepted.
+    unaccented_index: HashMap<String, usize>,
+    /// Stemmer used to match unknown guesses, and position in `words` of a word of each stem.
+    stems: Option<(Stemmer, HashMap<String, usize>)>,
+    /// How guesses are cleaned up.
+    guess_policy: GuessPolicy,
+    /// Results of the last neighbor queries.
+    neighbor_cache: Mutex<NeighborCache>,
+}
+
+/// Levenshtein distance between two strings, in characters.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Removes the accents and other diacritics of a string (`élève` -> `eleve`).
+fn strip_accents(s: &str) -> String {
+    s.nfd().filter(|&c| !is_combining_mark(c)).collect()
+}
+
+/// Reads a file with one word per line, ignoring empty lines. Words are converted to lowercase.
+pub fn read_word_list(path: &str) -> anyhow::Result<HashSet<String>> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .map(|line| line.map(|line| line.trim().to_lowercase()))
+        .filter(|line| !matches!(line, Ok(line) if line.is_empty()))
+        .collect::<Result<_, _>>()
+        .map_err(Into::into)
+}
+
+/// Reads a word frequency file (`<word> <count>` lines).
+fn read_frequencies(path: &str) -> anyhow::Result<HashMap<String, u64>> {
+    let mut frequencies = HashMap::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let mut fields = line.split_whitespace();
+        let (word, count) = match (fields.next(), fields.next()) {
+            (Some(word), Some(count)) => (word, count),
+            (None, _) => continue,
+            _ => return Err(anyhow!("invalid line in frequency file `{}`: `{}`", path, line)),
+        };
+        let count = count
+            .parse()
+            .map_err(|_| anyhow!("invalid count in frequency file `{}`: `{}`", path, line))?;
+        frequencies.insert(word.to_string(), count);
+    }
+    Ok(frequencies)
+}
+
+/// Number of neighbors whose similarity is averaged to estimate the difficulty of a word.
+const DIFFICULTY_NEIGHBORS: usize = 100;
+
+/// Number of neighbor queries whose result is cached.
+const NEIGHBOR_CACHE_SIZE: usize = 256;
+
+/// Neighbors of a word, by word, number of neighbors and metric.
+type NeighborCache = LruCache<(String, usize, Metric), Vec<(String, f32)>>;
+
+/// Maximum edit distance between an unknown guess and the words suggested instead.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Number of candidates whose difficulty is estimated to pick a word of a given difficulty level.
+pub const DIFFICULTY_SAMPLE_SIZE: usize = 30;
+
+/// Number of entries of rank tables computed with the approximate nearest-neighbor index.
+pub const APPROXIMATE_RANK_TABLE_SIZE: usize = 1000;
+
+/// How hard a word is to find.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Difficulty {
+    /// Mean similarity of the nearest neighbors of the word. Words in sparse neighborhoods, with a low
+    /// density, are harder to approach.
+    pub density: f32,
+    /// Number of occurrences of the word in the frequency file, if there's one. Rare words are harder to
+    /// think of.
+    pub frequency: Option<u64>,
+}
+
+/// Difficulty of the words picked as the word to find.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DifficultyLevel {
+    /// Common words in dense neighborhoods.
+    Easy,
+    #[default]
+    Normal,
+    /// Rare words in sparse neighborhoods.
+    Hard,
+}
+
+impl FromStr for DifficultyLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<DifficultyLevel> {
+        match s {
+            "easy" => Ok(DifficultyLevel::Easy),
+            "normal" => Ok(DifficultyLevel::Normal),
+            "hard" => Ok(DifficultyLevel::Hard),
+            _ => Err(anyhow!("unknown difficulty `{}`", s)),
+        }
+    }
+}
+
+/// Number of ranges of similarity of the histograms of neighbor tables.
+const HISTOGRAM_BINS: usize = 20;
+
+/// Nearest neighbors of a word, and distribution of the similarities of the vocabulary to the word.
+#[derive(Serialize, Deserialize)]
+pub struct NeighborTable {
+    /// Nearest neighbors of the word (excluding itself) with their similarity, the closest first.
+    pub neighbors: Vec<(String, f32)>,
+    /// Lowest and highest similarity of the words of the vocabulary to the word (excluding itself).
+    pub min: f32,
+    pub max: f32,
+    /// Number of words of the vocabulary in each of `HISTOGRAM_BINS` equal ranges of similarity from `min`
+    /// to `max`.
+    pub histogram: Vec<usize>,
+}
+
+impl NeighborTable {
+    /// Number of words of the vocabulary closer to the word than `similarity`: exact among the nearest neighbors,
+    /// estimated from the histogram beyond them.
+    pub fn closer_words(&self, similarity: f32) -> usize {
+        let known = self.neighbors.partition_point(|&(_, s)| s > similarity);
+        let bins = self.histogram.len();
+        if known < self.neighbors.len() || bins == 0 {
+            return known;
+        }
+        let bin_width = (self.max - self.min) / bins as f32;
+        let position = if bin_width > 0.0 {
+            ((similarity - self.min) / bin_width).clamp(0.0, bins as f32)
+        } else {
+            0.0
+        };
+        // the words of the bin of `similarity` are assumed to be evenly spread in it
+        let bin = position as usize;
+        let mut closer = self.histogram.iter().skip(bin + 1).sum::<usize>() as f32;
+        if let Some(&count) = self.histogram.get(bin) {
+            closer += count as f32 * (bin as f32 + 1.0 - position);
+        }
+        (closer.round() as usize).max(known)
+    }
+
+    /// Heat of a guess with the specified similarity to the word, from 0 (further than all the words of the
+    /// vocabulary) to 1 (closer than all of them), on a logarithmic scale of the number of closer words.
+    pub fn heat(&self, similarity: f32) -> f32 {
+        let words: usize = self.histogram.iter().sum();
+        let closer = self.closer_words(similarity);
+        (1.0 - ((closer + 2) as f32).ln() / ((words + 2) as f32).ln()).max(0.0)
+    }
+}
+
+/// Similarities of the nearest words of the vocabulary to a word, from the most to the least similar.
+/// Used to rank guesses.
+#[derive(Serialize, Deserialize)]
+pub struct RankTable {
+    /// Position of the word in the vocabulary.
+    index: usize,
+    metric: Metric,
+    similarities: Vec<f32>,
+    /// Whether the table contains all the words of the vocabulary.
+    complete: bool,
+}
+
+impl RankTable {
+    /// A table with none of the words of the vocabulary, to count the words closer to the word at `index`.
+    pub fn empty(index: usize, metric: Metric) -> RankTable {
+        RankTable {
+            index,
+            metric,
+            similarities: Vec::new(),
+            complete: false,
+        }
+    }
+
+    /// Position of the word in the vocabulary.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Keeps only the `len` nearest words.
+    pub fn truncate(&mut self, len: usize) {
+        if self.similarities.len() > len {
+            self.similarities.truncate(len);
+            self.complete = false;
+        }
+    }
+
+    /// Rank of a guess with the specified similarity, if it can be found from the table alone.
+    pub fn known_rank(&self, similarity: f32) -> Option<usize> {
+        let rank = self.similarities.partition_point(|&s| s > similarity);
+        (self.complete || rank < self.similarities.len()).then_some(rank + 1)
+    }
+}
+
+/// Picks a word of the specified difficulty level from a sample of candidates, given their difficulty: the word
+/// is picked from the easiest, middle or hardest third of the sample.
+pub fn pick_by_difficulty(
+    rng: &mut dyn RngCore,
+    level: DifficultyLevel,
+    sample: &[&str],
+    difficulties: &[Difficulty],
+) -> Option<String> {
+    // rank the sample by density and frequency, the easiest first
+    let mut easiness = vec![0; sample.len()];
+    let mut order: Vec<usize> = (0..sample.len()).collect();
+    order.sort_by(|&a, &b| difficulties[b].density.total_cmp(&difficulties[a].density));
+    for (rank, &i) in order.iter().enumerate() {
+        easiness[i] += rank;
+    }
+    if difficulties.iter().any(|difficulty| difficulty.frequency.is_some()) {
+        order.sort_by_key(|&i| Reverse(difficulties[i].frequency));
+        for (rank, &i) in order.iter().enumerate() {
+            easiness[i] += rank;
+        }
+    }
+    order.sort_by_key(|&i| easiness[i]);
+
+    let third = order.len().div_ceil(3);
+    let bucket = match level {
+        DifficultyLevel::Easy => &order[..third],
+        DifficultyLevel::Normal => &order[third..(2 * third).min(order.len())],
+        DifficultyLevel::Hard => &order[(2 * third).min(order.len())..],
+    };
+    bucket
+        .choose(rng)
+        .or_else(|| order.choose(rng))
+        .map(|&i| sample[i].to_string())
 }
 
 impl Words {
-    pub fn load(word2vec_model_file: &str) -> anyhow::Result<Words> {
+    /// Loads the word model. `progress` is updated while reading the model file.
+    pub fn load(config: &ModelConfig, progress: &LoadProgress) -> anyhow::Result<Words> {
+        let mut words = if let Some(ref path) = config.mmap_file {
+            let fingerprint = config.fingerprint()?;
+            if matrix::read_flat_file(path, fingerprint, false)?.is_none() {
+                let words = Words::load_model(config, progress)?;
+                matrix::write_flat_file(path, fingerprint, &words.words, &words.vectors, &words.norms)?;
+                info!("wrote flat vector file `{}`", path);
+            }
+            let file = matrix::read_flat_file(path, fingerprint, true)?
+                .ok_or_else(|| anyhow!("could not read back flat vector file `{}`", path))?;
+            Words::from_flat_file(file, config.metric)
+        } else if config.model_cache {
+            let path = format!("{}.cache", config.word2vec_model_file);
+            let fingerprint = config.fingerprint()?;
+            match matrix::read_flat_file(&path, fingerprint, false) {
+                Ok(Some(file)) => Words::from_flat_file(file, config.metric),
+                cached => {
+                    if let Err(err) = cached {
+                        warn!("could not read model cache `{}`: {}", path, err);
+                    }
+                    let words = Words::load_model(config, progress)?;
+                    match matrix::write_flat_file(&path, fingerprint, &words.words, &words.vectors, &words.norms) {
+                        Ok(()) => info!("wrote model cache `{}`", path),
+                        Err(err) => warn!("could not write model cache `{}`: {}", path, err),
+                    }
+                    words
+                }
+            }
+        } else {
+            Words::load_model(config, progress)?
+        };
+        if config.vector_storage != VectorStorage::F32 && config.mmap_file.is_some() {
+            warn!("`vector_storage` is not supported with `mmap_file`, ignoring it");
+        } else {
+            match config.vector_storage {
+                VectorStorage::F32 => {}
+                VectorStorage::F16 => words.vectors = words.vectors.into_half(),
+                VectorStorage::Int8 => words.vectors = words.vectors.into_int8(),
+            }
+        }
+        if config.fasttext_model_file.is_some() && config.ensemble.is_some() {
+            warn!("`fasttext_model_file` is not supported with an ensemble of models, ignoring it");
+        } else if let Some(ref path) = config.fasttext_model_file {
+            words.subwords = Some(FastTextModel::load(path)?);
+        }
+        words.normalized = config.normalize_vectors;
+        words.max_neighbors = config.max_neighbors;
+        if let Some(ref path) = config.frequency_file {
+            let frequencies = read_frequencies(path)?;
+            let frequencies: Vec<u64> = words
+                .words
+                .iter()
+                .map(|word| frequencies.get(word).copied().unwrap_or(0))
+                .collect();
+            words.rare = frequencies.iter().map(|&count| count < config.min_word_frequency).collect();
+            let rare = words.rare.iter().filter(|&&rare| rare).count();
+            info!("{} words of the vocabulary are too rare to be picked", rare);
+            words.frequencies = Some(frequencies);
+        }
+        if let Some(ref path) = config.stop_word_file {
+            words.stop_words = read_word_list(path)?;
+        }
+        words.reject_stop_words = config.reject_stop_words;
+        words.guess_policy = GuessPolicy::new(config);
+        words.phrase_targets = config.phrase_targets;
+        words.target_len = config.target_min_len..=config.target_max_len.unwrap_or(usize::MAX);
+        if let Some(ref path) = config.target_file {
+            let targets = read_word_list(path)?;
+            let unknown = targets.iter().filter(|word| !words.index.contains_key(*word)).count();
+            if unknown > 0 {
+                warn!("{} words of target file `{}` are not in the vocabulary", unknown, path);
+            }
+            words.targets = Some(targets);
+        }
+        if let Some(ref path) = config.dictionary_file {
+            let dictionary = read_word_list(path)?;
+            let in_dictionary: Vec<bool> = words
+                .words
+                .iter()
+                .map(|word| dictionary.contains(&word.to_lowercase()))
+                .collect();
+            let count = in_dictionary.iter().filter(|&&listed| listed).count();
+            info!("{} words of the vocabulary are in dictionary `{}`", count, path);
+            words.in_dictionary = Some(in_dictionary);
+        }
+        if config.ignore_accents {
+            for (i, word) in words.words.iter().enumerate() {
+                // keep the first (usually most frequent) of the words spelled the same without accents
+                words.unaccented_index.entry(strip_accents(word)).or_insert(i);
+            }
+        }
+        if let Some(algorithm) = config.stemmer {
+            let stemmer = Stemmer::create(algorithm);
+            let mut stems = HashMap::new();
+            for (i, word) in words.words.iter().enumerate() {
+                let stem = stemmer.stem(word);
+                // prefer the word that is its own stem, which is usually the base form
+                if stem == word.as_str() {
+                    stems.insert(stem.into_owned(), i);
+                } else {
+                    stems.entry(stem.into_owned()).or_insert(i);
+                }
+            }
+            words.stems = Some((stemmer, stems));
+        }
+        if config.hnsw_index {
+            words.ann = Some(words.load_hnsw(config)?);
+        }
+        Ok(words)
+    }
+
+    /// Builds the approximate nearest-neighbor index, or reads it from the model cache.
+    fn load_hnsw(&self, config: &ModelConfig) -> anyhow::Result<Hnsw> {
+        let cache = if config.model_cache {
+            let mut hasher = DefaultHasher::new();
+            (config.fingerprint()?, format!("{:?}", self.metric)).hash(&mut hasher);
+            Some((format!("{}.hnsw", config.word2vec_model_file), hasher.finish()))
+        } else {
+            None
+        };
+        if let Some((ref path, fingerprint)) = cache {
+            match Hnsw::read(path, fingerprint) {
+                Ok(Some(index)) => return Ok(index),
+                Ok(None) => {}
+                Err(err) => warn!("could not read HNSW index `{}`: {}", path, err),
+            }
+        }
+
+        info!("building HNSW index, this may take a while");
+        let index = Hnsw::build(self.words.len(), |a, b| self.similarity_between(a, b, self.metric));
+        if let Some((ref path, fingerprint)) = cache {
+            match index.write(path, fingerprint) {
+                Ok(()) => info!("wrote HNSW index `{}`", path),
+                Err(err) => warn!("could not write HNSW index `{}`: {}", path, err),
+            }
+        }
+        Ok(index)
+    }
+
+    /// Loads the model file.
+    fn load_model(config: &ModelConfig, progress: &LoadProgress) -> anyhow::Result<Words> {
+        let mut vocabulary = Words::read_model(config, progress)?;
+        let len = vocabulary.len();
+        vocabulary.retain(|word| config.keep_word(word));
+        if vocabulary.len() < len {
+            info!("dropped {} words of the model", len - vocabulary.len());
+        }
+        if let Some(ref ensemble) = config.ensemble {
+            let other = Words::read_model(&ensemble.model, progress)?;
+            let len = vocabulary.len();
+            vocabulary = vocabulary.ensemble(other, ensemble.weight)?;
+            if vocabulary.len() < len {
+                info!("dropped {} words missing from the second model", len - vocabulary.len());
+            }
+        }
+        if config.normalize_vectors {
+            vocabulary.normalize();
+        }
+        Ok(Words::new(vocabulary, None, config.metric))
+    }
+
+    /// Reads the words and vectors of the model file, in the configured format.
+    fn read_model(config: &ModelConfig, progress: &LoadProgress) -> anyhow::Result<Vocabulary> {
+        match config.model_format {
+            ModelFormat::Word2vec => Words::load_word2vec(&config.word2vec_model_file, progress),
+            ModelFormat::Numberbatch => {
+                Words::load_numberbatch(&config.word2vec_model_file, config.model_language.as_deref(), progress)
+            }
+            ModelFormat::Glove => Words::load_glove(&config.word2vec_model_file, progress),
+            ModelFormat::Fasttext => Words::load_fasttext(&config.word2vec_model_file, progress),
+        }
+    }
+
+    /// Reads the model file and checks its contents, without building the vocabulary.
+    pub fn check_model(config: &ModelConfig, progress: &LoadProgress) -> anyhow::Result<ModelReport> {
+        let vocabulary = Words::read_model(config, progress)?;
+        let dim = vocabulary.dim.unwrap_or(0);
+        let norms: Vec<f32> = if dim > 0 {
+            vocabulary.data.chunks(dim).map(norm).collect()
+        } else {
+            Vec::new()
+        };
+        let unique: HashSet<&str> = vocabulary.words.iter().map(String::as_str).collect();
+        Ok(ModelReport {
+            dim,
+            words: vocabulary.len(),
+            duplicates: vocabulary.len() - unique.len(),
+            normalized: norms.iter().all(|&norm| (norm - 1.0).abs() < NORMALIZED_TOLERANCE),
+            zero_vectors: norms.iter().filter(|&&norm| norm == 0.0).count(),
+            invalid_vectors: norms.iter().filter(|norm| !norm.is_finite()).count(),
+            kept: vocabulary.words.iter().filter(|word| config.keep_word(word)).count(),
+        })
+    }
+
+    fn new(vocabulary: Vocabulary, service: Option<EmbeddingService>, metric: Metric) -> Words {
+        let dim = vocabulary.dim.unwrap_or(0);
+        let norms = if dim > 0 {
+            vocabulary.data.chunks(dim).map(norm).collect()
+        } else {
+            vec![0.0; vocabulary.len()]
+        };
+        let vectors = Matrix::from_data(dim, vocabulary.data);
+        Words::from_parts(vocabulary.words, vectors, norms, service, metric)
+    }
+
+    fn from_flat_file(file: FlatFile, metric: Metric) -> Words {
+        Words::from_parts(file.words, file.vectors, file.norms, None, metric)
+    }
+
+    fn from_parts(
+        words: Vec<String>,
+        vectors: Matrix,
+        norms: Vec<f32>,
+        service: Option<EmbeddingService>,
+        metric: Metric,
+    ) -> Words {
+        let mut index = HashMap::with_capacity(words.len());
+        for (i, word) in words.iter().enumerate() {
+            // keep the first occurrence of duplicate words
+            index.entry(word.clone()).or_insert(i);
+        }
+        let rare = vec![false; words.len()];
+        Words {
+            words,
+            vectors,
+            index,
+            norms,
+            subwords: None,
+            normalized: false,
+            service,
+            metric,
+            max_neighbors: default_max_neighbors(),
+            ann: None,
+            rare,
+            frequencies: None,
+            stop_words: HashSet::new(),
+            reject_stop_words: false,
+            phrase_targets: false,
+            target_len: 0..=usize::MAX,
+            targets: None,
+            in_dictionary: None,
+            unaccented_index: HashMap::new(),
+            stems: None,
+            guess_policy: GuessPolicy::default(),
+            neighbor_cache: Mutex::new(LruCache::new(NEIGHBOR_CACHE_SIZE)),
+        }
+    }
+
+    fn load_word2vec(word2vec_model_file: &str, progress: &LoadProgress) -> anyhow::Result<Vocabulary> {
         let _span = trace_span!("Loading word2vec db").entered();
 
-        let file = File::open(word2vec_model_file)?;
+        if Words::is_text_word2vec(word2vec_model_file)? {
+            return Words::load_text(word2vec_model_file, progress, true, |key| Ok(Some(key)));
+        }
+
+        let file = open_with_progress(word2vec_model_file, progress)?;
         let reader = WordVectorReader::new_from_reader(BufReader::new(file))?;
 
-        let mut vocabulary = Vec::with_capacity(reader.vocabulary_size());
+        let mut vocabulary = Vocabulary::with_capacity(reader.vocabulary_size(), reader.vector_size());
+        progress.set_total_words(reader.vocabulary_size() as u64);
 
         for (word, vec) in reader {
-            vocabulary.push((word.clone(), vec));
+            vocabulary.push(word, &vec)?;
+            progress.add_words(1);
+        }
+
+        Ok(vocabulary)
+    }
+
+    /// Opens a model file. Files ending in `.gz` are decompressed on the fly.
+    fn open_model_file(path: &str, progress: &LoadProgress) -> anyhow::Result<BufReader<Box<dyn Read>>> {
+        let file = open_with_progress(path, progress)?;
+        let reader: Box<dyn Read> = if path.ends_with(".gz") {
+            Box::new(GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        Ok(BufReader::new(reader))
+    }
+
+    /// Returns whether a word2vec model file is in text format, by checking that the first entry after
+    /// the header is a word followed by the expected number of numbers, in text.
+    fn is_text_word2vec(path: &str) -> anyhow::Result<bool> {
+        let mut reader = Words::open_model_file(path, &LoadProgress::default())?;
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let vector_size: usize = match header.split_whitespace().nth(1).map(str::parse) {
+            Some(Ok(vector_size)) => vector_size,
+            _ => return Err(anyhow!("invalid word2vec header")),
+        };
+
+        let mut entry = Vec::new();
+        reader.read_until(b'\n', &mut entry)?;
+        Ok(std::str::from_utf8(&entry).is_ok_and(|entry| {
+            let mut fields = entry.split_whitespace();
+            fields.next().is_some() && {
+                let values: Vec<_> = fields.collect();
+                values.len() == vector_size && values.iter().all(|v| v.parse::<f32>().is_ok())
+            }
+        }))
+    }
+
+    /// Loads a model in text format: a `<vocabulary size> <vector size>` header (if `has_header` is set),
+    /// then one word per line followed by the components of its vector, separated by spaces.
+    /// Without a header, the vector size is the one of the first entry.
+    ///
+    /// If the header gives the vector size, words made of several space-separated tokens are accepted, and
+    /// their tokens are joined with `_` as in word2vec phrases.
+    ///
+    /// `map_key` returns the word for each key of the file, or `None` to skip the entry.
+    fn load_text(
+        path: &str,
+        progress: &LoadProgress,
+        has_header: bool,
+        map_key: impl Fn(&str) -> anyhow::Result<Option<&str>>,
+    ) -> anyhow::Result<Vocabulary> {
+        let mut lines = Words::open_model_file(path, progress)?.lines();
+
+        let mut vocabulary = Vocabulary::default();
+        let mut vector_size = None;
+        if has_header {
+            // header: <vocabulary size> <vector size>
+            let header = lines.next().ok_or_else(|| anyhow!("empty model file"))??;
+            let header: Vec<usize> = header
+                .trim_start_matches('\u{feff}')
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| anyhow!("invalid model file header"))?;
+            match header[..] {
+                [vocabulary_size, size] => {
+                    vocabulary = Vocabulary::with_capacity(vocabulary_size, size);
+                    progress.set_total_words(vocabulary_size as u64);
+                    vector_size = Some(size);
+                }
+                _ => return Err(anyhow!("invalid model file header")),
+            };
+        }
+
+        for line in lines {
+            let line = line?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+            let key_tokens = match vector_size {
+                Some(size) if fields.len() > size + 1 => fields.len() - size,
+                _ => 1,
+            };
+            let key = fields[..key_tokens].join("_");
+            let word = match map_key(&key)? {
+                Some(word) => word,
+                None => continue,
+            };
+
+            vocabulary.push_text(word, &fields[key_tokens..])?;
+            vector_size = vocabulary.dim;
+            progress.add_words(1);
         }
 
-        Ok(Words { vocabulary })
+        Ok(vocabulary)
     }
 
-    pub fn vector(&self, word: &str) -> Option<&[f32]> {
-        self.vocabulary
+    /// Loads a GloVe file.
+    fn load_glove(path: &str, progress: &LoadProgress) -> anyhow::Result<Vocabulary> {
+        let _span = trace_span!("Loading GloVe db").entered();
+
+        Words::load_text(path, progress, false, |key| Ok(Some(key)))
+    }
+
+    /// Loads a fastText `.vec` export. Unlike word2vec text files, these may contain words with spaces.
+    fn load_fasttext(path: &str, progress: &LoadProgress) -> anyhow::Result<Vocabulary> {
+        let _span = trace_span!("Loading fastText db").entered();
+
+        Words::load_text(path, progress, true, |key| Ok(Some(key)))
+    }
+
+    /// Loads a ConceptNet Numberbatch file.
+    ///
+    /// Keys of multilingual releases look like `/c/fr/chat`: if `language` is set, only the entries of
+    /// this language are kept, and the prefix is removed in all cases.
+    fn load_numberbatch(
+        path: &str,
+        language: Option<&str>,
+        progress: &LoadProgress,
+    ) -> anyhow::Result<Vocabulary> {
+        let _span = trace_span!("Loading numberbatch db").entered();
+
+        Words::load_text(path, progress, true, |key| {
+            if let Some(rest) = key.strip_prefix("/c/") {
+                let (lang, word) = rest.split_once('/').ok_or_else(|| anyhow!("invalid key: `{}`", key))?;
+                if language.is_some_and(|language| language != lang) {
+                    return Ok(None);
+                }
+                Ok(Some(word))
+            } else {
+                Ok(Some(key))
+            }
+        })
+    }
+
+    /// Builds the vocabulary by querying an external embedding service for each entry of the
+    /// configured vocabulary file. The service is also used afterwards to embed unknown guesses.
+    pub fn load_from_service(
+        service: EmbeddingService,
+        metric: Metric,
+        progress: &LoadProgress,
+    ) -> anyhow::Result<Words> {
+        let _span = trace_span!("Loading vocabulary from embedding service").entered();
+
+        let file = File::open(&service.config().vocabulary_file)?;
+        let entries = BufReader::new(file)
+            .lines()
+            .map(|line| line.map(|line| line.trim().to_lowercase()))
+            .filter(|line| !matches!(line, Ok(line) if line.is_empty()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut vocabulary = Vocabulary::default();
+        progress.set_total_words(entries.len() as u64);
+        for batch in entries.chunks(EMBEDDING_BATCH_SIZE) {
+            let texts: Vec<&str> = batch.iter().map(String::as_str).collect();
+            let vectors = service.embed(&texts)?;
+            for (entry, vector) in batch.iter().zip(vectors) {
+                vocabulary.push(entry.clone(), &vector)?;
+            }
+            progress.set(vocabulary.len() as u64, entries.len() as u64);
+            progress.add_words(batch.len() as u64);
+        }
+
+        Ok(Words::new(vocabulary, Some(service), metric))
+    }
+
+    /// Returns the `count` words nearest to a vector of norm `norm` with their similarity, closest first. Rare
+    /// words and the words at the `excluded` positions are skipped.
+    fn nearest(&self, vector: &[f32], norm: f32, count: usize, metric: Metric, excluded: &[usize]) -> Vec<(String, f32)> {
+        let query = self.vectors.query(vector);
+        let similarity = |i: usize| metric.similarity_from_dot(self.vectors.dot(i, &query), norm, self.norms[i]);
+        let skipped = |i: usize| self.rare[i] || excluded.contains(&i);
+
+        if let Some(ref ann) = self.ann {
+            if metric == self.metric {
+                // ask for more candidates in proportion to the rare words, which are skipped
+                let common = self.rare.iter().filter(|&&rare| !rare).count().max(1);
+                let wanted = ((count + excluded.len()) * self.words.len() / common).min(self.words.len());
+                return ann
+                    .search(wanted, similarity)
+                    .into_iter()
+                    .filter(|&(i, _)| !skipped(i))
+                    .take(count)
+                    .map(|(i, similarity)| (self.words[i].clone(), similarity))
+                    .collect();
+            }
+        }
+
+        // each thread keeps its `count` best candidates, then the heaps are merged
+        let push = |mut heap: BinaryHeap<Candidate>, candidate| {
+            heap.push(candidate);
+            if heap.len() > count {
+                heap.pop();
+            }
+            heap
+        };
+        let best = (0..self.words.len())
+            .into_par_iter()
+            .filter(|&i| !skipped(i))
+            .map(|i| Candidate {
+                index: i,
+                similarity: similarity(i),
+            })
+            .fold(|| BinaryHeap::with_capacity(count + 1), push)
+            .reduce(BinaryHeap::new, |a, b| b.into_iter().fold(a, push));
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|candidate| (self.words[candidate.index].clone(), candidate.similarity))
+            .collect()
+    }
+
+    /// Similarity between two words of the vocabulary.
+    fn similarity_between(&self, a: usize, b: usize, metric: Metric) -> f32 {
+        let val = self.vectors.row(a);
+        let dot = self.vectors.dot(b, &self.vectors.query(&val));
+        metric.similarity_from_dot(dot, self.norms[a], self.norms[b])
+    }
+
+    /// Words that may be picked as the word to find and satisfy the predicate: words of the target list if
+    /// there's one, that are neither rare nor stop words, nor phrases unless they're allowed, have an allowed
+    /// length and are in the dictionary if there's one.
+    fn candidates(&self, predicate: impl Fn(&str) -> bool) -> Vec<&str> {
+        self.words
             .iter()
-            .position(|x| x.0.as_str() == word)
-            .map(|index| &self.vocabulary[index].1[..])
-    }
-
-    /// `!thesaurus <word> <count>`
-    pub fn thesaurus(&self, word: &str, count: usize) -> String {
-        let _span = trace_span!("thesaurus", word, count).entered();
-
-        let results = match self.vector(word) {
-            Some(val) => {
-                let mut metrics: Vec<(usize, f32)> = Vec::with_capacity(self.vocabulary.len());
-                metrics.extend(
-                    self.vocabulary
-                        .iter()
-                        .enumerate()
-                        .map(|(i, other_val)| (i, val.iter().zip(other_val.1.iter()).map(|(&a, &b)| a * b).sum())),
-                );
-
-                metrics.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
-                Some(
-                    metrics[1..count + 1]
-                        .iter()
-                        .map(|&(idx, dist)| (self.vocabulary[idx].clone().0, dist))
-                        .collect::<Vec<_>>(),
-                )
-            }
-            None => None,
+            .enumerate()
+            .zip(&self.rare)
+            .filter(|&((i, word), &rare)| {
+                !rare
+                    && self.in_dictionary.as_ref().is_none_or(|in_dictionary| in_dictionary[i])
+                    && !self.stop_words.contains(word)
+                    && (self.phrase_targets || !is_phrase(word))
+                    && self.target_len.contains(&word.chars().count())
+                    && self.targets.as_ref().is_none_or(|targets| targets.contains(word))
+                    && predicate(word)
+            })
+            .map(|((_, word), _)| word.as_str())
+            .collect()
+    }
+}
+
+impl EmbeddingBackend for Words {
+    /// Returns the vector of a word of the vocabulary.
+    fn vector(&self, word: &str) -> Option<Cow<'_, [f32]>> {
+        self.index.get(word).map(|&index| self.vectors.row(index))
+    }
+
+    fn guess_policy(&self) -> &GuessPolicy {
+        &self.guess_policy
+    }
+
+    /// Returns the word of the vocabulary a guess refers to: the guess itself if it's in the vocabulary, or
+    /// if accents are ignored, the word that is spelled like the guess without accents, or if there's a
+    /// stemmer, a word with the same stem.
+    fn resolve_guess<'a>(&'a self, guess: &'a str) -> &'a str {
+        if self.index.contains_key(guess) {
+            return guess;
+        }
+        if let Some(&i) = self.unaccented_index.get(&strip_accents(guess)) {
+            return &self.words[i];
+        }
+        if let Some((ref stemmer, ref stems)) = self.stems {
+            if let Some(&i) = stems.get(stemmer.stem(guess).as_ref()) {
+                return &self.words[i];
+            }
+        }
+        guess
+    }
+
+    /// Returns up to `count` words of the vocabulary spelled like `word`, the closest first. Rare words are
+    /// not suggested.
+    fn spell_suggest(&self, word: &str, count: usize) -> Vec<String> {
+        let _span = trace_span!("spell_suggest", word).entered();
+
+        let word: Vec<char> = word.chars().collect();
+        let mut suggestions: Vec<(usize, usize)> = (0..self.words.len())
+            .into_par_iter()
+            .filter(|&i| !self.rare[i])
+            .filter_map(|i| {
+                let candidate: Vec<char> = self.words[i].chars().collect();
+                if candidate.len().abs_diff(word.len()) > MAX_SUGGESTION_DISTANCE {
+                    return None;
+                }
+                let distance = edit_distance(&word, &candidate);
+                (distance <= MAX_SUGGESTION_DISTANCE).then_some((distance, i))
+            })
+            .collect();
+        // words that come first in the model are usually the most frequent
+        suggestions.sort_unstable();
+        suggestions
+            .into_iter()
+            .take(count)
+            .map(|(_, i)| self.words[i].clone())
+            .collect()
+    }
+
+    fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Number of words in the vocabulary.
+    fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Guesses outside of the vocabulary are sent to the embedding service, if there's one.
+    fn may_block(&self) -> bool {
+        self.service.is_some()
+    }
+
+    /// Returns the `i`-th word of the vocabulary.
+    fn word(&self, i: usize) -> &str {
+        &self.words[i]
+    }
+
+    /// Returns the vector of a guessed word or phrase.
+    ///
+    /// Unlike `vector`, this falls back to the fastText subword model or the embedding service (if there's
+    /// one) for entries outside of the vocabulary, which may block.
+    fn guess_vector(&self, guess: &str) -> Result<Option<Cow<'_, [f32]>>> {
+        if let Some(v) = self.vector(guess) {
+            return Ok(Some(v));
+        }
+        if let Some(mut vector) = self.subwords.as_ref().and_then(|subwords| subwords.word_vector(guess)) {
+            if self.normalized {
+                normalize(&mut vector);
+            }
+            return Ok(Some(Cow::Owned(vector)));
+        }
+        match self.service {
+            Some(ref service) => {
+                let mut vectors = service.embed(&[guess])?;
+                Ok(vectors.pop().map(Cow::Owned))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the `count` nearest neighbors of a word (excluding the word itself) with their similarity,
+    /// closest first. Returns `None` if the word is not in the vocabulary.
+    fn neighbors(&self, word: &str, count: usize, metric: Metric) -> Option<Vec<(String, f32)>> {
+        let _span = trace_span!("neighbors", word, count).entered();
+
+        let index = *self.index.get(word)?;
+        let key = (word.to_string(), count, metric);
+        if let Some(neighbors) = self.neighbor_cache.lock().unwrap().get(&key) {
+            return Some(neighbors);
+        }
+        let neighbors = self.nearest(&self.vectors.row(index), self.norms[index], count, metric, &[index]);
+        self.neighbor_cache.lock().unwrap().insert(key, neighbors.clone());
+        Some(neighbors)
+    }
+
+    /// Returns the `count` words nearest to the sum of the `positive` words minus the `negative` words (e.g.
+    /// `king - man + woman`) with their similarity, closest first. The words of the query are skipped.
+    fn analogy(&self, positive: &[&str], negative: &[&str], count: usize) -> Result<Vec<(String, f32)>> {
+        let _span = trace_span!("analogy", ?positive, ?negative, count).entered();
+
+        let mut vector = vec![0.0; self.vectors.dim()];
+        let mut excluded = Vec::with_capacity(positive.len() + negative.len());
+        let terms = positive.iter().map(|word| (word, 1.0)).chain(negative.iter().map(|word| (word, -1.0)));
+        for (word, sign) in terms {
+            let index = *self
+                .index
+                .get(*word)
+                .ok_or_else(|| anyhow!("unknown word `{}`", word))?;
+            // the vectors are normalized so that each word weighs the same
+            let norm = self.norms[index];
+            if norm > 0.0 {
+                for (x, y) in vector.iter_mut().zip(self.vectors.row(index).iter()) {
+                    *x += sign * y / norm;
+                }
+            }
+            excluded.push(index);
+        }
+        let norm = norm(&vector);
+        Ok(self.nearest(&vector, norm, count.min(self.max_neighbors), self.metric, &excluded))
+    }
+
+    /// Returns the `count` nearest neighbors of a word and the distribution of the similarities of the
+    /// vocabulary to it. Returns `None` if the word is not in the vocabulary.
+    fn neighbor_table(&self, word: &str, count: usize, metric: Metric) -> Option<NeighborTable> {
+        let _span = trace_span!("neighbor_table", word, count).entered();
+
+        let index = *self.index.get(word)?;
+        let neighbors = self.neighbors(word, count, metric)?;
+
+        let (val, norm) = (self.vectors.row(index), self.norms[index]);
+        let query = self.vectors.query(&val);
+        let similarity = |i: usize| metric.similarity_from_dot(self.vectors.dot(i, &query), norm, self.norms[i]);
+        let (min, max) = (0..self.words.len())
+            .into_par_iter()
+            .filter(|&i| i != index)
+            .map(|i| {
+                let s = similarity(i);
+                (s, s)
+            })
+            .reduce(
+                || (f32::INFINITY, f32::NEG_INFINITY),
+                |(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)),
+            );
+        let bin_width = (max - min) / HISTOGRAM_BINS as f32;
+        let histogram = (0..self.words.len())
+            .into_par_iter()
+            .filter(|&i| i != index)
+            .fold(
+                || vec![0; HISTOGRAM_BINS],
+                |mut histogram, i| {
+                    let bin = if bin_width > 0.0 {
+                        ((similarity(i) - min) / bin_width) as usize
+                    } else {
+                        0
+                    };
+                    histogram[bin.min(HISTOGRAM_BINS - 1)] += 1;
+                    histogram
+                },
+            )
+            .reduce(
+                || vec![0; HISTOGRAM_BINS],
+                |a, b| a.iter().zip(&b).map(|(a, b)| a + b).collect(),
+            );
+        Some(NeighborTable {
+            neighbors,
+            min,
+            max,
+            histogram,
+        })
+    }
+
+    /// Returns the similarity between two words with the default metric. Words that are not in the vocabulary
+    /// are handled like guesses, which may block.
+    fn similarity(&self, a: &str, b: &str) -> Result<f32> {
+        let va = self.guess_vector(a)?.ok_or_else(|| anyhow!("unknown word `{}`", a))?;
+        let vb = self.guess_vector(b)?.ok_or_else(|| anyhow!("unknown word `{}`", b))?;
+        Ok(self.metric.similarity(&va, &vb))
+    }
+
+    /// Returns the similarities of the words of the vocabulary to `word` (including itself), to rank
+    /// guesses. With the approximate nearest-neighbor index, only the nearest words are included.
+    fn rank_table(&self, word: &str, metric: Metric) -> Option<RankTable> {
+        let _span = trace_span!("rank_table", word).entered();
+
+        let index = *self.index.get(word)?;
+        if let Some(ref ann) = self.ann {
+            if metric == self.metric && self.words.len() > APPROXIMATE_RANK_TABLE_SIZE {
+                let similarities = ann
+                    .search(APPROXIMATE_RANK_TABLE_SIZE, |i| {
+                        self.similarity_between(index, i, metric)
+                    })
+                    .into_iter()
+                    .map(|(_, similarity)| similarity)
+                    .collect();
+                return Some(RankTable {
+                    index,
+                    metric,
+                    similarities,
+                    complete: false,
+                });
+            }
+        }
+
+        let (val, norm) = (self.vectors.row(index), self.norms[index]);
+        let query = self.vectors.query(&val);
+        let mut similarities: Vec<f32> = (0..self.words.len())
+            .into_par_iter()
+            .map(|i| metric.similarity_from_dot(self.vectors.dot(i, &query), norm, self.norms[i]))
+            .collect();
+        similarities.par_sort_unstable_by(|a, b| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        Some(RankTable {
+            index,
+            metric,
+            similarities,
+            complete: true,
+        })
+    }
+
+    /// Rank of a guess with the specified similarity to the word of a rank table.
+    fn rank(&self, table: &RankTable, similarity: f32) -> usize {
+        if let Some(rank) = table.known_rank(similarity) {
+            return rank;
+        }
+        // further than the words of the table: count the words that are closer
+        let (val, norm) = (self.vectors.row(table.index), self.norms[table.index]);
+        let query = self.vectors.query(&val);
+        let closer = (0..self.words.len())
+            .into_par_iter()
+            .filter(|&i| {
+                table
+                    .metric
+                    .similarity_from_dot(self.vectors.dot(i, &query), norm, self.norms[i])
+                    > similarity
+            })
+            .count();
+        closer + 1
+    }
+
+    /// Estimates how hard a word is to find. Returns `None` if the word is not in the vocabulary.
+    fn difficulty(&self, word: &str, metric: Metric) -> Option<Difficulty> {
+        let _span = trace_span!("difficulty", word).entered();
+
+        let index = *self.index.get(word)?;
+        let neighbors = self.neighbors(word, DIFFICULTY_NEIGHBORS, metric)?;
+        let density = if neighbors.is_empty() {
+            0.0
+        } else {
+            neighbors.iter().map(|(_, similarity)| similarity).sum::<f32>() / neighbors.len() as f32
         };
+        Some(Difficulty {
+            density,
+            frequency: self.frequencies.as_ref().map(|frequencies| frequencies[index]),
+        })
+    }
+
+    /// `!thesaurus <word> <count>`: the nearest neighbors of a word with the default metric, up to
+    /// `max_neighbors`. Returns `None` if the word is not in the vocabulary.
+    fn thesaurus(&self, word: &str, count: usize) -> Option<Vec<(String, f32)>> {
+        self.neighbors(word, count.min(self.max_neighbors), self.metric)
+    }
+
+    /// Returns whether a guess must be rejected because it's a stop word.
+    fn is_rejected_stop_word(&self, guess: &str) -> bool {
+        self.reject_stop_words && self.stop_words.contains(guess)
+    }
+
+    /// Picks a random word from the target list if there's one, or from the vocabulary. Rare words and stop
+    /// words are avoided unless there are only such words.
+    fn pick_word(&self, rng: &mut dyn RngCore) -> String {
+        self.pick_word_where(rng, &|_| true).unwrap_or_else(|| {
+            let pos: usize = rng.gen_range(0..self.words.len());
+            self.words[pos].clone()
+        })
+    }
+
+    /// Picks a random word among those that satisfy the predicate, are in the target list if there's one,
+    /// and are neither rare nor stop words. Returns `None` if there's none.
+    fn pick_word_where(&self, rng: &mut dyn RngCore, predicate: &dyn Fn(&str) -> bool) -> Option<String> {
+        self.candidates(predicate)
+            .choose(rng)
+            .map(|word| word.to_string())
+    }
+
+    /// Like `pick_word_where`, but only picks words of the specified difficulty level.
+    ///
+    /// The difficulty of every candidate would be too long to estimate: the word is picked from the
+    /// easiest, middle or hardest third of a random sample of candidates.
+    fn pick_word_of_difficulty(
+        &self,
+        rng: &mut dyn RngCore,
+        level: DifficultyLevel,
+        metric: Metric,
+        predicate: &dyn Fn(&str) -> bool,
+    ) -> Option<String> {
+        let candidates = self.candidates(predicate);
+        let sample: Vec<&str> = candidates
+            .choose_multiple(rng, DIFFICULTY_SAMPLE_SIZE)
+            .copied()
+            .collect();
+        let difficulties: Vec<Difficulty> = sample
+            .iter()
+            .map(|word| self.difficulty(word, metric))
+            .collect::<Option<_>>()?;
+        pick_by_difficulty(rng, level, &sample, &difficulties)
+    }
 
-        format!("{:?}", results)
+    /// Words that may be picked as the word to find.
+    fn pickable_words(&self) -> Vec<&str> {
+        self.candidates(|_| true)
     }
 
-    /// Picks a random word from the vocabulary.
-    pub fn pick_word(&self) -> String {
-        let mut rng = rand::thread_rng();
-        let pos: usize = rng.gen_range(0..self.vocabulary.len());
-        self.vocabulary[pos].0.clone()
+    /// Number of occurrences of a word of the vocabulary in the frequency file, if there's one.
+    fn frequency(&self, word: &str) -> Option<u64> {
+        let index = *self.index.get(word)?;
+        self.frequencies.as_ref().map(|frequencies| frequencies[index])
     }
 }